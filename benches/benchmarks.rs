@@ -2,11 +2,17 @@
 //!
 //! Run with: cargo bench
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
-use harmony_agent::config::{Config, NetworkConfig, PeerConfig};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId, Throughput};
+use harmony_agent::config::{Config, NetworkConfig, PeerConfig, WireguardBackend};
 use harmony_agent::monitoring::{Monitor, ConnectionState};
 use harmony_agent::security::{validate_network_name, validate_interface_name};
-use harmony_agent::wireguard::PrivateKey;
+use harmony_agent::wireguard::{
+    DeviceConfig, KeyPair, MemoryNetwork, MemorySocket, MemoryTun, MemoryTunHandle, PrivateKey,
+    WgDevice,
+};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::runtime::Runtime;
 
 fn bench_key_generation(c: &mut Criterion) {
     c.bench_function("key_generation", |b| {
@@ -150,7 +156,11 @@ fn bench_network_config_with_peers(c: &mut Criterion) {
                         mtu: 1420,
                         private_key_path: "/tmp/test.key".to_string(),
                         dns: vec![],
-                        address: Some("10.0.0.1/24".to_string()),
+                        addresses: vec!["10.0.0.1/24".to_string()],
+                        listen_port: 0,
+                        bind_address: None,
+                        backend: WireguardBackend::Auto,
+                        queues: 0,
                         peers: vec![],
                         http: None,
                     };
@@ -162,6 +172,9 @@ fn bench_network_config_with_peers(c: &mut Criterion) {
                             endpoint: format!("192.168.{}.1:51820", i),
                             allowed_ips: vec![format!("10.0.{}.0/24", i)],
                             persistent_keepalive_secs: 25,
+                            endpoint_refresh_secs: 300,
+                            preshared_key_path: None,
+                            preshared_key: None,
                         };
                         network.peers.push(peer);
                     }
@@ -175,6 +188,185 @@ fn bench_network_config_with_peers(c: &mut Criterion) {
     group.finish();
 }
 
+/// Peer reachable at `addr` that owns the single address `tunnel_ip`
+fn datapath_peer(
+    keypair: &KeyPair,
+    addr: SocketAddr,
+    tunnel_ip: Ipv4Addr,
+) -> harmony_agent::wireguard::PeerConfig {
+    let mut peer =
+        harmony_agent::wireguard::PeerConfig::new(addr.to_string(), keypair.public.clone());
+    peer.endpoint = Some(addr);
+    peer.allowed_ips = vec![format!("{}/32", tunnel_ip)];
+    peer
+}
+
+/// Create a device with in-memory I/O
+async fn datapath_device(
+    keypair: &KeyPair,
+    peers: Vec<harmony_agent::wireguard::PeerConfig>,
+    socket: MemorySocket,
+) -> (WgDevice, MemoryTunHandle) {
+    let config = DeviceConfig {
+        interface: "wg-bench".to_string(),
+        mtu: 1420,
+        keypair: keypair.clone(),
+        listen_port: 0,
+        bind_address: None,
+        queues: 1,
+        peers,
+    };
+
+    let (tun, handle) = MemoryTun::pair();
+    let device = WgDevice::with_io(config, Arc::new(tun), Arc::new(socket))
+        .await
+        .unwrap();
+    (device, handle)
+}
+
+/// IPv4 packet of `len` bytes from `src` to `dst`
+fn datapath_packet(src: Ipv4Addr, dst: Ipv4Addr, len: usize) -> Vec<u8> {
+    let mut packet = vec![0u8; len];
+    packet[0] = 0x45;
+    packet[2..4].copy_from_slice(&(len as u16).to_be_bytes());
+    packet[8] = 64;
+    packet[9] = 17;
+    packet[12..16].copy_from_slice(&src.octets());
+    packet[16..20].copy_from_slice(&dst.octets());
+    packet
+}
+
+fn bench_device_datapath(c: &mut Criterion) {
+    const BATCH: usize = 64;
+
+    let runtime = Runtime::new().unwrap();
+    let a_addr: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+    let b_addr: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+    let (a_ip, b_ip) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+    let (a_keys, b_keys) = (KeyPair::generate(), KeyPair::generate());
+
+    let (a, a_tun, b, b_tun) = runtime.block_on(async {
+        let (a_socket, b_socket) = MemorySocket::pair(a_addr, b_addr);
+        let a_peers = vec![datapath_peer(&b_keys, b_addr, b_ip)];
+        let b_peers = vec![datapath_peer(&a_keys, a_addr, a_ip)];
+        let (a, a_tun) = datapath_device(&a_keys, a_peers, a_socket).await;
+        let (b, b_tun) = datapath_device(&b_keys, b_peers, b_socket).await;
+
+        // Complete the handshake before measuring
+        a_tun.send(&datapath_packet(a_ip, b_ip, 64));
+        b_tun.recv().await.unwrap();
+        (a, a_tun, b, b_tun)
+    });
+
+    let mut group = c.benchmark_group("device_datapath");
+    for size in [64, 1420] {
+        let packet = datapath_packet(a_ip, b_ip, size);
+
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::new("latency", size), &packet, |bench, packet| {
+            bench.iter(|| {
+                runtime.block_on(async {
+                    a_tun.send(packet);
+                    black_box(b_tun.recv().await.unwrap());
+                })
+            });
+        });
+
+        group.throughput(Throughput::Bytes((size * BATCH) as u64));
+        group.bench_with_input(BenchmarkId::new("throughput", size), &packet, |bench, packet| {
+            bench.iter(|| {
+                runtime.block_on(async {
+                    for _ in 0..BATCH {
+                        a_tun.send(packet);
+                    }
+                    for _ in 0..BATCH {
+                        black_box(b_tun.recv().await.unwrap());
+                    }
+                })
+            });
+        });
+    }
+    group.finish();
+
+    runtime.block_on(async {
+        a.stop().await.unwrap();
+        b.stop().await.unwrap();
+    });
+}
+
+/// Traffic in both directions between a hub device and many peer devices
+fn bench_device_peers(c: &mut Criterion) {
+    const PACKET_SIZE: usize = 1420;
+
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("device_peers");
+    group.sample_size(20);
+
+    for peer_count in [1u16, 10, 100, 200] {
+        let network = MemoryNetwork::new();
+        let hub_keys = KeyPair::generate();
+        let hub_addr: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+        let hub_ip = Ipv4Addr::new(10, 0, 0, 1);
+        let spoke_keys: Vec<KeyPair> = (0..peer_count).map(|_| KeyPair::generate()).collect();
+        let spoke_addr = |i: u16| SocketAddr::from(([192, 0, 2, 2], 10000 + i));
+        let spoke_ip = |i: u16| Ipv4Addr::new(10, 1, (i >> 8) as u8, i as u8);
+
+        let (hub, hub_tun, spokes) = runtime.block_on(async {
+            let hub_peers = spoke_keys
+                .iter()
+                .enumerate()
+                .map(|(i, keys)| datapath_peer(keys, spoke_addr(i as u16), spoke_ip(i as u16)))
+                .collect();
+            let hub = datapath_device(&hub_keys, hub_peers, network.bind(hub_addr)).await;
+
+            let mut spokes = Vec::new();
+            for (i, keys) in spoke_keys.iter().enumerate() {
+                let peers = vec![datapath_peer(&hub_keys, hub_addr, hub_ip)];
+                let socket = network.bind(spoke_addr(i as u16));
+                spokes.push(datapath_device(keys, peers, socket).await);
+            }
+
+            // Complete every handshake before measuring
+            for (i, (_, spoke_tun)) in spokes.iter().enumerate() {
+                spoke_tun.send(&datapath_packet(spoke_ip(i as u16), hub_ip, 64));
+                hub.1.recv().await.unwrap();
+            }
+            (hub.0, hub.1, spokes)
+        });
+
+        let uplink: Vec<Vec<u8>> = (0..peer_count)
+            .map(|i| datapath_packet(spoke_ip(i), hub_ip, PACKET_SIZE))
+            .collect();
+        let downlink: Vec<Vec<u8>> = (0..peer_count)
+            .map(|i| datapath_packet(hub_ip, spoke_ip(i), PACKET_SIZE))
+            .collect();
+
+        group.throughput(Throughput::Bytes(2 * PACKET_SIZE as u64 * peer_count as u64));
+        group.bench_function(BenchmarkId::from_parameter(peer_count), |bench| {
+            bench.iter(|| {
+                runtime.block_on(async {
+                    for (i, (_, spoke_tun)) in spokes.iter().enumerate() {
+                        hub_tun.send(&downlink[i]);
+                        spoke_tun.send(&uplink[i]);
+                    }
+                    for (_, spoke_tun) in &spokes {
+                        black_box(spoke_tun.recv().await.unwrap());
+                        black_box(hub_tun.recv().await.unwrap());
+                    }
+                })
+            });
+        });
+
+        runtime.block_on(async {
+            hub.stop().await.unwrap();
+            for (spoke, _) in spokes {
+                spoke.stop().await.unwrap();
+            }
+        });
+    }
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_key_generation,
@@ -184,6 +376,8 @@ criterion_group!(
     bench_monitoring,
     bench_metrics_export,
     bench_network_config_with_peers,
+    bench_device_datapath,
+    bench_device_peers,
 );
 
 criterion_main!(benches);
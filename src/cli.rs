@@ -0,0 +1,570 @@
+//! Command line client for a running agent
+//!
+//! These subcommands build an `ApiRequest`, send it over the control socket
+//! and render the `ApiResponse` as text or JSON. The `key` and `config`
+//! subcommands run locally and do not need the agent.
+
+use harmony_agent::{
+    APP_NAME,
+    config::{Config, ControlAction, TomlConfig, WgQuickConfig},
+    control::{ApiError, ApiRequest, ControlClient},
+    security::validate_interface_name,
+    wireguard::{write_key_file, PresharedKey, PrivateKey},
+};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Exit code: success, or agent running and all networks healthy
+pub const EXIT_OK: i32 = 0;
+/// Exit code: command failed
+pub const EXIT_ERROR: i32 = 1;
+/// Exit code: agent running but a network is down or unhealthy
+pub const EXIT_DEGRADED: i32 = 2;
+
+/// Send an action to the agent and return the response data
+async fn call(
+    client: &ControlClient,
+    action: ControlAction,
+    network: &str,
+    config: Option<serde_json::Value>,
+) -> Result<serde_json::Value, ApiError> {
+    let mut request = ApiRequest::new(
+        format!("cli-{}", std::process::id()),
+        action,
+        network.to_string(),
+    );
+    request.config = config;
+
+    let response = client.send(&request).await?;
+    if response.success {
+        Ok(response.data.unwrap_or(serde_json::Value::Null))
+    } else {
+        Err(response
+            .error
+            .unwrap_or_else(|| ApiError::InternalError("Request failed".to_string())))
+    }
+}
+
+/// Report an error and return its exit code
+fn fail(error: ApiError, json: bool) -> i32 {
+    if json {
+        let error = serde_json::to_value(&error).unwrap_or(serde_json::Value::Null);
+        println!("{}", serde_json::json!({ "success": false, "error": error }));
+    } else {
+        eprintln!("Error: {}", error);
+    }
+    error.exit_code()
+}
+
+/// Send an action and print the result, using `render` for text output
+async fn run_action(
+    client: &ControlClient,
+    json: bool,
+    action: ControlAction,
+    network: &str,
+    config: Option<serde_json::Value>,
+    render: impl FnOnce(&serde_json::Value),
+) -> i32 {
+    match call(client, action, network, config).await {
+        Ok(data) => {
+            if json {
+                let output = serde_json::json!({ "success": true, "data": data });
+                println!("{}", serde_json::to_string_pretty(&output).unwrap_or_default());
+            } else {
+                render(&data);
+            }
+            EXIT_OK
+        }
+        Err(e) => fail(e, json),
+    }
+}
+
+/// Read an inline JSON network configuration from a file
+fn read_network_config(path: Option<&Path>) -> Result<Option<serde_json::Value>, ApiError> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        ApiError::ConfigError(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|e| ApiError::ConfigError(format!("Invalid JSON in {}: {}", path.display(), e)))
+}
+
+/// Connect a network, optionally with an inline configuration file
+pub async fn connect(
+    client: &ControlClient,
+    json: bool,
+    network: &str,
+    network_config: Option<&Path>,
+) -> i32 {
+    let config = match read_network_config(network_config) {
+        Ok(config) => config,
+        Err(e) => return fail(e, json),
+    };
+
+    run_action(client, json, ControlAction::Connect, network, config, |data| {
+        println!(
+            "Network '{}' connected (interface {}, {} peer(s))",
+            network,
+            data["interface"].as_str().unwrap_or("?"),
+            data["peers"].as_u64().unwrap_or(0),
+        );
+    })
+    .await
+}
+
+/// Disconnect a network
+pub async fn disconnect(client: &ControlClient, json: bool, network: &str) -> i32 {
+    run_action(client, json, ControlAction::Disconnect, network, None, |_| {
+        println!("Network '{}' disconnected", network);
+    })
+    .await
+}
+
+/// Reload a network, optionally with an inline configuration file
+pub async fn reload(
+    client: &ControlClient,
+    json: bool,
+    network: &str,
+    network_config: Option<&Path>,
+) -> i32 {
+    let config = match read_network_config(network_config) {
+        Ok(config) => config,
+        Err(e) => return fail(e, json),
+    };
+
+    run_action(client, json, ControlAction::Reload, network, config, |data| {
+        println!(
+            "Network '{}' reloaded (state: {})",
+            network,
+            data["state"].as_str().unwrap_or("?"),
+        );
+    })
+    .await
+}
+
+/// Rotate a network's key pair
+pub async fn rotate_keys(client: &ControlClient, json: bool, network: &str) -> i32 {
+    run_action(client, json, ControlAction::RotateKeys, network, None, |data| {
+        println!(
+            "New public key for '{}': {}",
+            network,
+            data["public_key"].as_str().unwrap_or("?"),
+        );
+        if data["applied"] == false {
+            println!("Network is not connected; the key is used on the next connect");
+        }
+        if data["sessions_restarted"] == true {
+            println!("Kernel WireGuard restarted all sessions; peers handshake again on next use");
+        }
+    })
+    .await
+}
+
+/// List a network's peers
+pub async fn list_peers(client: &ControlClient, json: bool, network: &str) -> i32 {
+    run_action(client, json, ControlAction::ListPeers, network, None, |data| {
+        let peers = data["peers"].as_array().cloned().unwrap_or_default();
+        if peers.is_empty() {
+            println!("No peers on '{}'", network);
+            return;
+        }
+
+        println!(
+            "{:<16} {:<44} {:<22} {:<15} {:>10} {:>10}  ALLOWED IPS",
+            "NAME", "PUBLIC KEY", "ENDPOINT", "LAST HANDSHAKE", "TX", "RX"
+        );
+        for peer in &peers {
+            let allowed_ips: Vec<&str> = peer["allowed_ips"]
+                .as_array()
+                .map(|ips| ips.iter().filter_map(|ip| ip.as_str()).collect())
+                .unwrap_or_default();
+            println!(
+                "{:<16} {:<44} {:<22} {:<15} {:>10} {:>10}  {}",
+                peer["name"].as_str().unwrap_or("?"),
+                peer["public_key"].as_str().unwrap_or("?"),
+                peer["endpoint"].as_str().unwrap_or("-"),
+                handshake_age(peer["last_handshake"].as_u64()),
+                format_bytes(peer["tx_bytes"].as_u64().unwrap_or(0)),
+                format_bytes(peer["rx_bytes"].as_u64().unwrap_or(0)),
+                allowed_ips.join(","),
+            );
+        }
+    })
+    .await
+}
+
+/// Peer settings given on the command line for `peers add`
+pub struct NewPeer<'a> {
+    /// Peer name
+    pub name: &'a str,
+    /// Base64 public key
+    pub public_key: &'a str,
+    /// Endpoint (`host:port`), if any
+    pub endpoint: Option<&'a str>,
+    /// Allowed IP ranges
+    pub allowed_ips: &'a [String],
+    /// Persistent keepalive in seconds
+    pub keepalive: Option<u16>,
+    /// Preshared key file on the agent host
+    pub preshared_key_path: Option<&'a str>,
+}
+
+/// Add a peer to a connected network
+pub async fn add_peer(client: &ControlClient, json: bool, network: &str, peer: NewPeer<'_>) -> i32 {
+    let mut config = serde_json::json!({
+        "name": peer.name,
+        "publicKey": peer.public_key,
+        "endpoint": peer.endpoint.unwrap_or_default(),
+        "allowedIps": peer.allowed_ips,
+    });
+    if let Some(keepalive) = peer.keepalive {
+        config["keepaliveSecs"] = keepalive.into();
+    }
+    if let Some(path) = peer.preshared_key_path {
+        config["presharedKeyPath"] = path.into();
+    }
+
+    run_action(client, json, ControlAction::AddPeer, network, Some(config), |_| {
+        println!("Peer '{}' added to '{}'", peer.name, network);
+    })
+    .await
+}
+
+/// Remove a peer from a connected network
+pub async fn remove_peer(client: &ControlClient, json: bool, network: &str, public_key: &str) -> i32 {
+    let config = serde_json::json!({ "publicKey": public_key });
+    run_action(client, json, ControlAction::RemovePeer, network, Some(config), |data| {
+        println!(
+            "Peer '{}' removed from '{}'",
+            data["peer"].as_str().unwrap_or(public_key),
+            network
+        );
+    })
+    .await
+}
+
+/// Print a new key or write it to `out`, like `wg genkey` / `wg genpsk`
+fn emit_key(encoded: &str, out: Option<&Path>, force: bool) -> i32 {
+    let Some(path) = out else {
+        println!("{}", encoded);
+        return EXIT_OK;
+    };
+
+    if path.exists() && !force {
+        eprintln!("Error: {} already exists (use --force to overwrite)", path.display());
+        return EXIT_ERROR;
+    }
+    match write_key_file(path, encoded, force) {
+        Ok(()) => EXIT_OK,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+/// Generate a private key
+pub fn genkey(out: Option<&Path>, force: bool) -> i32 {
+    emit_key(&PrivateKey::generate().to_base64(), out, force)
+}
+
+/// Generate a preshared key
+pub fn genpsk(out: Option<&Path>, force: bool) -> i32 {
+    emit_key(&PresharedKey::generate().to_base64(), out, force)
+}
+
+/// Print the public key for a private key read from `input` or stdin
+pub fn pubkey(input: Option<&Path>) -> i32 {
+    let mut encoded = String::new();
+    let read = match input {
+        Some(path) => std::fs::read_to_string(path).map(|contents| encoded = contents),
+        None => std::io::stdin().read_to_string(&mut encoded).map(|_| ()),
+    };
+    if let Err(e) = read {
+        eprintln!("Error: Failed to read private key: {}", e);
+        return EXIT_ERROR;
+    }
+
+    match PrivateKey::from_base64(encoded.trim()) {
+        Ok(private) => {
+            println!("{}", private.public_key().to_base64());
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+/// Import a wg-quick file: write its keys to `key_dir` and print a TOML network section
+pub fn import_wgquick(file: &Path, network: &str, key_dir: &Path, force: bool) -> i32 {
+    match import_network(file, network, key_dir, force) {
+        Ok(toml) => {
+            print!("{}", toml);
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn import_network(
+    file: &Path,
+    network: &str,
+    key_dir: &Path,
+    force: bool,
+) -> harmony_agent::Result<String> {
+    let wgquick = WgQuickConfig::from_file(file)?;
+    let private_key = wgquick.private_key.as_deref().ok_or_else(|| {
+        harmony_agent::WgAgentError::Config(format!("{} has no PrivateKey", file.display()))
+    })?;
+    PrivateKey::from_base64(private_key)?;
+
+    // wg-quick names the interface after the file (wg0.conf -> wg0)
+    let interface = file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .filter(|stem| validate_interface_name(stem).is_ok())
+        .unwrap_or("wg0");
+
+    let key_path = key_dir.join(format!("{}.key", network));
+    let mut config = wgquick.to_network_config(interface, &key_path.to_string_lossy());
+
+    // TOML configs reference preshared keys by path
+    let mut key_files: Vec<(PathBuf, String)> = vec![(key_path, private_key.to_string())];
+    for peer in &mut config.peers {
+        if let Some(psk) = peer.preshared_key.take() {
+            let file_name: String = peer
+                .name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+                .collect();
+            let path = key_dir.join(format!("{}-{}.psk", network, file_name));
+            peer.preshared_key_path = Some(path.to_string_lossy().into_owned());
+            key_files.push((path, psk));
+        }
+    }
+    config.validate()?;
+
+    for (path, _) in &key_files {
+        if path.exists() && !force {
+            return Err(harmony_agent::WgAgentError::Config(format!(
+                "{} already exists (use --force to overwrite)",
+                path.display()
+            )));
+        }
+    }
+    for (path, key) in &key_files {
+        write_key_file(path, key, force)?;
+        eprintln!("Wrote {}", path.display());
+    }
+
+    let mut output = Config::new();
+    output.add_network(network.to_string(), config);
+    TomlConfig::from(&output).to_toml_string()
+}
+
+/// Print a configured network as a wg-quick file
+pub fn export_wgquick(config_path: &str, network: &str) -> i32 {
+    let exported = Config::from_file(config_path).and_then(|config| {
+        let network_config = config.get_network(network).ok_or_else(|| {
+            harmony_agent::WgAgentError::NotFound(format!(
+                "Network '{}' not found in {}",
+                network, config_path
+            ))
+        })?;
+        WgQuickConfig::from_network_config(network_config)
+    });
+
+    match exported {
+        Ok(wgquick) => {
+            print!("{}", wgquick);
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+/// Ask the agent to shut down and wait for its socket to disappear
+pub async fn stop_agent(client: &ControlClient, json: bool, timeout: u64) -> i32 {
+    if let Err(e) = call(client, ControlAction::Shutdown, "default", None).await {
+        if let ApiError::Unreachable(ref msg) = e {
+            if !json {
+                println!("Agent is not running ({})", msg);
+                return e.exit_code();
+            }
+        }
+        return fail(e, json);
+    }
+
+    if !json {
+        println!("Waiting for agent to stop...");
+    }
+    if let Err(e) = client.wait_for_shutdown(Duration::from_secs(timeout)).await {
+        return fail(e, json);
+    }
+
+    if json {
+        println!("{}", serde_json::json!({ "success": true, "data": { "stopped": true } }));
+    } else {
+        println!("Agent stopped");
+    }
+    EXIT_OK
+}
+
+/// Query the agent and every network, print a summary and derive the exit code
+pub async fn agent_status(client: &ControlClient, json: bool) -> i32 {
+    let agent = match call(client, ControlAction::AgentStatus, "default", None).await {
+        Ok(data) => data,
+        Err(ApiError::Unreachable(msg)) => {
+            if json {
+                println!("{}", serde_json::json!({ "running": false, "error": msg }));
+            } else {
+                println!("Agent is not running ({})", msg);
+            }
+            return ApiError::Unreachable(msg).exit_code();
+        }
+        Err(e) => return fail(e, json),
+    };
+
+    let mut names: Vec<String> = agent["networks"]
+        .as_object()
+        .map(|networks| networks.keys().cloned().collect())
+        .unwrap_or_default();
+    names.sort();
+
+    let mut networks = serde_json::Map::new();
+    let mut degraded = false;
+    for name in &names {
+        let status = match call(client, ControlAction::Status, name, None).await {
+            Ok(status) => status,
+            Err(e) => serde_json::json!({ "network": name, "error": e.to_string() }),
+        };
+        degraded |= !network_healthy(&status);
+        networks.insert(name.clone(), status);
+    }
+
+    if json {
+        let output = serde_json::json!({
+            "running": true,
+            "degraded": degraded,
+            "agent": agent,
+            "networks": networks,
+        });
+        println!("{}", serde_json::to_string_pretty(&output).unwrap_or_default());
+    } else {
+        print_status(&agent, &networks);
+    }
+
+    if degraded {
+        EXIT_DEGRADED
+    } else {
+        EXIT_OK
+    }
+}
+
+/// A network is healthy when its tunnel is active and every peer has a recent handshake
+fn network_healthy(status: &serde_json::Value) -> bool {
+    let peers = &status["peers"];
+    status["state"] == "active" && peers["healthy"].as_u64() == peers["total"].as_u64()
+}
+
+/// Print the status summary as a table
+fn print_status(agent: &serde_json::Value, networks: &serde_json::Map<String, serde_json::Value>) {
+    println!(
+        "{} v{} running (pid {}, up {})",
+        APP_NAME,
+        agent["version"].as_str().unwrap_or("?"),
+        agent["pid"],
+        format_duration(agent["uptime_secs"].as_u64().unwrap_or(0)),
+    );
+
+    if networks.is_empty() {
+        println!("No networks connected");
+        return;
+    }
+
+    println!();
+    println!(
+        "{:<16} {:<12} {:>7} {:<12} {:>7} {:>7}  {:<15} {:>10} {:>10}",
+        "NETWORK", "STATE", "RETRIES", "INTERFACE", "PEERS", "HEALTHY", "LAST HANDSHAKE", "TX", "RX"
+    );
+    for (name, status) in networks {
+        if let Some(error) = status["error"].as_str() {
+            println!("{:<16} error: {}", name, error);
+            continue;
+        }
+
+        let peers = &status["peers"];
+        let last_handshake = peers["details"].as_object().and_then(|details| {
+            details
+                .values()
+                .filter_map(|peer| peer["last_handshake"].as_u64())
+                .max()
+        });
+
+        println!(
+            "{:<16} {:<12} {:>7} {:<12} {:>7} {:>7}  {:<15} {:>10} {:>10}",
+            name,
+            status["state"].as_str().unwrap_or("?"),
+            status["reconnect"]["attempts"].as_u64().unwrap_or(0),
+            status["interface"].as_str().unwrap_or("?"),
+            peers["total"].as_u64().unwrap_or(0),
+            peers["healthy"].as_u64().unwrap_or(0),
+            handshake_age(last_handshake),
+            format_bytes(status["traffic"]["tx_bytes"].as_u64().unwrap_or(0)),
+            format_bytes(status["traffic"]["rx_bytes"].as_u64().unwrap_or(0)),
+        );
+    }
+}
+
+/// Format a handshake Unix timestamp as its age, e.g. "15s ago"
+fn handshake_age(at: Option<u64>) -> String {
+    let Some(at) = at else {
+        return "never".to_string();
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!("{} ago", format_duration(now.saturating_sub(at)))
+}
+
+/// Format a duration in seconds as e.g. "1h 2m 3s"
+fn format_duration(secs: u64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Format a byte count with a binary unit
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -3,7 +3,7 @@
 //! This module handles parsing of JSON control messages from Harmony or other
 //! applications via the control plane API.
 
-use crate::config::{HttpConfig, NetworkConfig, PeerConfig};
+use crate::config::{HttpConfig, NetworkConfig, PeerConfig, WireguardBackend};
 use crate::error::{Result, WgAgentError};
 use serde::{Deserialize, Serialize};
 
@@ -21,6 +21,18 @@ pub enum ControlAction {
     Reload,
     /// Perform key rotation
     RotateKeys,
+    /// Add a peer to a connected network
+    AddPeer,
+    /// Remove a peer from a connected network
+    RemovePeer,
+    /// Replace a peer's configuration
+    UpdatePeer,
+    /// List peers with their statistics
+    ListPeers,
+    /// Agent-wide summary (version, uptime, networks)
+    AgentStatus,
+    /// Shut the agent down gracefully
+    Shutdown,
 }
 
 /// Control message received from applications
@@ -57,9 +69,30 @@ pub struct JsonNetworkConfig {
     #[serde(rename = "privateKeyPath")]
     pub private_key_path: String,
 
-    /// Interface IP address (CIDR notation)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub address: Option<String>,
+    /// Interface IP addresses (CIDR notation); a single `address` string is also accepted
+    #[serde(
+        default,
+        alias = "address",
+        deserialize_with = "super::string_or_list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub addresses: Vec<String>,
+
+    /// UDP listen port (0 = random)
+    #[serde(rename = "listenPort", default)]
+    pub listen_port: u16,
+
+    /// Address to bind the UDP socket to
+    #[serde(rename = "bindAddress", default, skip_serializing_if = "Option::is_none")]
+    pub bind_address: Option<String>,
+
+    /// WireGuard implementation (`auto`, `kernel` or `userspace`)
+    #[serde(default, skip_serializing_if = "WireguardBackend::is_auto")]
+    pub backend: WireguardBackend,
+
+    /// TUN queues and packet workers (0 = one per CPU)
+    #[serde(default)]
+    pub queues: usize,
 
     /// WireGuard peers
     #[serde(default)]
@@ -80,7 +113,8 @@ pub struct JsonPeerConfig {
     #[serde(rename = "publicKey")]
     pub public_key: String,
 
-    /// Peer endpoint
+    /// Peer endpoint (optional, learned from inbound packets if omitted)
+    #[serde(default)]
     pub endpoint: String,
 
     /// Allowed IP addresses/ranges
@@ -93,6 +127,21 @@ pub struct JsonPeerConfig {
         default = "default_keepalive"
     )]
     pub keepalive_secs: u16,
+
+    /// Hostname endpoint re-resolution interval in seconds (0 = never)
+    #[serde(
+        rename = "endpointRefreshSecs",
+        default = "default_endpoint_refresh"
+    )]
+    pub endpoint_refresh_secs: u64,
+
+    /// Path to a base64 preshared key file on the agent host
+    #[serde(rename = "presharedKeyPath", default, skip_serializing_if = "Option::is_none")]
+    pub preshared_key_path: Option<String>,
+
+    /// Inline base64 preshared key
+    #[serde(rename = "presharedKey", default, skip_serializing_if = "Option::is_none")]
+    pub preshared_key: Option<String>,
 }
 
 /// JSON HTTP configuration
@@ -139,7 +188,11 @@ impl From<JsonNetworkConfig> for NetworkConfig {
             mtu: json.mtu,
             private_key_path: json.private_key_path,
             dns: json.dns,
-            address: json.address,
+            addresses: json.addresses,
+            listen_port: json.listen_port,
+            bind_address: json.bind_address,
+            backend: json.backend,
+            queues: json.queues,
             peers: json.peers.into_iter().map(|p| p.into()).collect(),
             http: json.http.map(|h| h.into()),
         }
@@ -163,6 +216,9 @@ impl From<JsonPeerConfig> for PeerConfig {
             endpoint: json.endpoint,
             allowed_ips: json.allowed_ips,
             persistent_keepalive_secs: json.keepalive_secs,
+            endpoint_refresh_secs: json.endpoint_refresh_secs,
+            preshared_key_path: json.preshared_key_path,
+            preshared_key: json.preshared_key,
         }
     }
 }
@@ -184,6 +240,10 @@ fn default_keepalive() -> u16 {
     25
 }
 
+fn default_endpoint_refresh() -> u64 {
+    300
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -274,7 +334,11 @@ mod tests {
             config: Some(JsonNetworkConfig {
                 interface: "wg0".to_string(),
                 mtu: 1420,
-                address: Some("10.0.0.2/24".to_string()),
+                addresses: vec!["10.0.0.2/24".to_string()],
+                listen_port: 0,
+                bind_address: None,
+                backend: WireguardBackend::Auto,
+                queues: 0,
                 dns: vec!["10.100.0.2".to_string()],
                 private_key_path: "/etc/harmony-agent/private.key".to_string(),
                 peers: vec![],
@@ -292,7 +356,11 @@ mod tests {
         let json_config = JsonNetworkConfig {
             interface: "wg0".to_string(),
             mtu: 1420,
-            address: Some("10.0.0.1/24".to_string()),
+            addresses: vec!["10.0.0.1/24".to_string()],
+            listen_port: 0,
+            bind_address: None,
+            backend: WireguardBackend::Auto,
+            queues: 0,
             dns: vec!["10.100.0.2".to_string()],
             private_key_path: "/etc/harmony-agent/private.key".to_string(),
             peers: vec![],
@@ -305,6 +373,26 @@ mod tests {
         assert_eq!(network_config.mtu, 1420);
     }
 
+    #[test]
+    fn test_parse_inline_preshared_key() {
+        let json = r#"{
+            "name": "server",
+            "publicKey": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+            "endpoint": "192.168.1.1:51820",
+            "allowedIps": ["10.0.0.0/24"],
+            "presharedKey": "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE="
+        }"#;
+
+        let peer: PeerConfig = serde_json::from_str::<JsonPeerConfig>(json).unwrap().into();
+        assert!(peer.validate().is_ok());
+        assert!(peer.preshared_key.is_some());
+        assert!(peer.preshared_key_path.is_none());
+
+        // Inline keys are never echoed back out
+        let serialized = serde_json::to_string(&peer).unwrap();
+        assert!(!serialized.contains("AQEBAQEB"));
+    }
+
     #[test]
     fn test_action_serialization() {
         assert_eq!(
@@ -5,13 +5,15 @@
 
 mod json;
 mod toml_parser;
-mod validation;
+pub(crate) mod validation;
+pub mod wgquick;
 
-pub use json::{ControlAction, ControlMessage};
+pub use json::{ControlAction, ControlMessage, JsonNetworkConfig, JsonPeerConfig};
 pub use toml_parser::TomlConfig;
+pub use wgquick::{WgQuickConfig, WgQuickPeer};
 
 use crate::error::{Result, WgAgentError};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
 
@@ -21,6 +23,118 @@ pub struct Config {
     /// Named network configurations
     #[serde(default)]
     pub networks: HashMap<String, NetworkConfig>,
+
+    /// Agent-wide platform settings
+    #[serde(default)]
+    pub platform: PlatformConfig,
+
+    /// Tunnel supervision settings
+    #[serde(default)]
+    pub supervisor: SupervisorConfig,
+}
+
+/// How interfaces, addresses and routes are configured on Linux
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlatformBackend {
+    /// rtnetlink requests (no external tools needed)
+    #[default]
+    Netlink,
+    /// `ip` and `resolvconf` commands
+    Command,
+}
+
+/// Platform settings shared by all networks
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlatformConfig {
+    /// Linux backend (ignored on other platforms)
+    #[serde(default)]
+    pub backend: PlatformBackend,
+
+    /// Metric for routes installed for peers' allowed IPs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route_metric: Option<u32>,
+}
+
+/// How the agent restarts failed or stalled tunnels
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupervisorConfig {
+    /// Restart tunnels automatically
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Restart a tunnel after this long without a handshake from any peer with an
+    /// endpoint and persistent keepalive (0 = only restart failed tunnels)
+    #[serde(default = "default_handshake_timeout")]
+    pub handshake_timeout_secs: u64,
+
+    /// Delay before the first restart attempt, doubled on every failure
+    #[serde(default = "default_initial_backoff")]
+    pub initial_backoff_secs: u64,
+
+    /// Upper bound for the delay between restart attempts
+    #[serde(default = "default_max_backoff")]
+    pub max_backoff_secs: u64,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            handshake_timeout_secs: default_handshake_timeout(),
+            initial_backoff_secs: default_initial_backoff(),
+            max_backoff_secs: default_max_backoff(),
+        }
+    }
+}
+
+impl SupervisorConfig {
+    /// Check if all settings have their default values
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Validate supervisor settings
+    pub fn validate(&self) -> Result<()> {
+        validation::validate_backoff(self.initial_backoff_secs, self.max_backoff_secs)
+    }
+}
+
+/// Which WireGuard implementation runs a tunnel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireguardBackend {
+    /// In-kernel WireGuard on Linux when the module is available, userspace otherwise
+    #[default]
+    Auto,
+    /// In-kernel WireGuard (Linux only)
+    Kernel,
+    /// Userspace implementation (boringtun, or wireguard-go on macOS)
+    Userspace,
+}
+
+impl WireguardBackend {
+    /// Check if the backend is chosen automatically
+    pub fn is_auto(&self) -> bool {
+        *self == WireguardBackend::Auto
+    }
+}
+
+impl std::fmt::Display for WireguardBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireguardBackend::Auto => write!(f, "auto"),
+            WireguardBackend::Kernel => write!(f, "kernel"),
+            WireguardBackend::Userspace => write!(f, "userspace"),
+        }
+    }
+}
+
+impl PlatformConfig {
+    /// Check if all settings have their default values
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 /// Configuration for a single network
@@ -45,8 +159,31 @@ pub struct NetworkConfig {
     #[serde(default)]
     pub dns: Vec<String>,
 
-    /// Interface IP address (CIDR notation, e.g., "10.100.0.2/24")
-    pub address: Option<String>,
+    /// Interface IP addresses (CIDR notation, e.g., ["10.100.0.2/24", "fd00::2/64"]).
+    /// A single `address` string is also accepted.
+    #[serde(
+        default,
+        alias = "address",
+        deserialize_with = "string_or_list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub addresses: Vec<String>,
+
+    /// UDP listen port (0 = random)
+    #[serde(default)]
+    pub listen_port: u16,
+
+    /// Address to bind the UDP socket to (default: all IPv4 and IPv6 addresses)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_address: Option<String>,
+
+    /// WireGuard implementation (`auto`, `kernel` or `userspace`)
+    #[serde(default, skip_serializing_if = "WireguardBackend::is_auto")]
+    pub backend: WireguardBackend,
+
+    /// TUN queues and packet workers for the userspace backend (0 = one per CPU, Linux only)
+    #[serde(default)]
+    pub queues: usize,
 
     /// WireGuard peers
     #[serde(default)]
@@ -66,7 +203,9 @@ pub struct PeerConfig {
     /// Base64-encoded public key
     pub public_key: String,
 
-    /// Peer endpoint (host:port)
+    /// Peer endpoint (host:port). Empty if the peer has no fixed endpoint and
+    /// will be learned when the peer contacts us.
+    #[serde(default)]
     pub endpoint: String,
 
     /// Allowed IP addresses/ranges (CIDR notation)
@@ -75,6 +214,18 @@ pub struct PeerConfig {
     /// Persistent keepalive interval in seconds
     #[serde(default = "default_keepalive")]
     pub persistent_keepalive_secs: u16,
+
+    /// How often to re-resolve a hostname endpoint, in seconds (0 = never)
+    #[serde(default = "default_endpoint_refresh")]
+    pub endpoint_refresh_secs: u64,
+
+    /// Path to a base64 preshared key file (must be 0600)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preshared_key_path: Option<String>,
+
+    /// Inline base64 preshared key (JSON control API only)
+    #[serde(default, skip_serializing)]
+    pub preshared_key: Option<String>,
 }
 
 /// HTTP configuration (preserved from Harmony, not used by agent)
@@ -92,6 +243,8 @@ impl Config {
     pub fn new() -> Self {
         Self {
             networks: HashMap::new(),
+            platform: PlatformConfig::default(),
+            supervisor: SupervisorConfig::default(),
         }
     }
 
@@ -127,6 +280,9 @@ impl Config {
             network.validate()
                 .map_err(|e| WgAgentError::Config(format!("Network '{}': {}", name, e)))?;
         }
+        self.supervisor
+            .validate()
+            .map_err(|e| WgAgentError::Config(format!("Supervisor: {}", e)))?;
         Ok(())
     }
 }
@@ -141,6 +297,16 @@ impl NetworkConfig {
         for dns in &self.dns {
             validation::validate_ip_address(dns)?;
         }
+
+        for address in &self.addresses {
+            validation::validate_cidr(address)?;
+        }
+
+        if let Some(ref bind_address) = self.bind_address {
+            validation::validate_bind_address(bind_address)?;
+        }
+
+        validation::validate_queues(self.queues)?;
         
         for peer in &self.peers {
             peer.validate()?;
@@ -154,13 +320,27 @@ impl PeerConfig {
     /// Validate peer configuration
     pub fn validate(&self) -> Result<()> {
         validation::validate_public_key(&self.public_key)?;
-        validation::validate_endpoint(&self.endpoint)?;
+        if !self.endpoint.is_empty() {
+            validation::validate_endpoint(&self.endpoint)?;
+        }
         
         for allowed_ip in &self.allowed_ips {
             validation::validate_cidr(allowed_ip)?;
         }
         
         validation::validate_keepalive(self.persistent_keepalive_secs)?;
+        validation::validate_endpoint_refresh(self.endpoint_refresh_secs)?;
+
+        match (&self.preshared_key, &self.preshared_key_path) {
+            (Some(_), Some(_)) => {
+                return Err(WgAgentError::Config(
+                    "Specify either preshared_key or preshared_key_path, not both".to_string(),
+                ));
+            }
+            (Some(key), None) => validation::validate_preshared_key(key)?,
+            (None, Some(path)) => validation::validate_file_path(path)?,
+            (None, None) => {}
+        }
         
         Ok(())
     }
@@ -184,3 +364,42 @@ fn default_mtu() -> u16 {
 fn default_keepalive() -> u16 {
     25
 }
+
+fn default_endpoint_refresh() -> u64 {
+    300
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_handshake_timeout() -> u64 {
+    300
+}
+
+fn default_initial_backoff() -> u64 {
+    1
+}
+
+fn default_max_backoff() -> u64 {
+    300
+}
+
+/// Deserialize a single string or a list of strings into a list
+fn string_or_list<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Option::<StringOrList>::deserialize(deserializer)? {
+        Some(StringOrList::One(value)) => vec![value],
+        Some(StringOrList::Many(values)) => values,
+        None => Vec::new(),
+    })
+}
@@ -4,7 +4,10 @@
 //! agent operation. It supports the Harmony configuration schema with multiple
 //! named networks.
 
-use crate::config::{Config, HttpConfig, NetworkConfig, PeerConfig};
+use crate::config::{
+    Config, HttpConfig, NetworkConfig, PeerConfig, PlatformConfig, SupervisorConfig,
+    WireguardBackend,
+};
 use crate::error::{Result, WgAgentError};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -18,6 +21,14 @@ pub struct TomlConfig {
     /// Network configurations
     #[serde(default)]
     pub network: HashMap<String, TomlNetworkConfig>,
+
+    /// Platform settings (`[platform]` section)
+    #[serde(default, skip_serializing_if = "PlatformConfig::is_default")]
+    pub platform: PlatformConfig,
+
+    /// Tunnel supervision settings (`[supervisor]` section)
+    #[serde(default, skip_serializing_if = "SupervisorConfig::is_default")]
+    pub supervisor: SupervisorConfig,
 }
 
 /// TOML network configuration
@@ -38,13 +49,35 @@ pub struct TomlNetworkConfig {
     /// Path to private key file
     pub private_key_path: String,
 
-    /// Interface IP address (CIDR notation)
-    pub address: Option<String>,
+    /// Interface IP addresses (CIDR notation); a single string is also accepted
+    #[serde(
+        default,
+        alias = "address",
+        deserialize_with = "super::string_or_list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub addresses: Vec<String>,
 
     /// DNS servers
     #[serde(default)]
     pub dns: Vec<String>,
 
+    /// UDP listen port (0 = random)
+    #[serde(default)]
+    pub listen_port: u16,
+
+    /// Address to bind the UDP socket to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_address: Option<String>,
+
+    /// WireGuard implementation (`auto`, `kernel` or `userspace`)
+    #[serde(default, skip_serializing_if = "WireguardBackend::is_auto")]
+    pub backend: WireguardBackend,
+
+    /// TUN queues and packet workers (0 = one per CPU)
+    #[serde(default)]
+    pub queues: usize,
+
     /// HTTP configuration (optional, from Harmony)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub http: Option<TomlHttpConfig>,
@@ -73,7 +106,8 @@ pub struct TomlPeerConfig {
     /// Base64-encoded public key
     pub public_key: String,
 
-    /// Peer endpoint
+    /// Peer endpoint (optional, learned from inbound packets if omitted)
+    #[serde(default)]
     pub endpoint: String,
 
     /// Allowed IP addresses/ranges
@@ -82,6 +116,14 @@ pub struct TomlPeerConfig {
     /// Persistent keepalive interval in seconds
     #[serde(default = "default_keepalive")]
     pub persistent_keepalive_secs: u16,
+
+    /// Hostname endpoint re-resolution interval in seconds (0 = never)
+    #[serde(default = "default_endpoint_refresh")]
+    pub endpoint_refresh_secs: u64,
+
+    /// Path to a base64 preshared key file
+    #[serde(default)]
+    pub preshared_key_path: Option<String>,
 }
 
 impl TomlConfig {
@@ -105,12 +147,21 @@ impl TomlConfig {
             WgAgentError::Config(format!("Failed to parse TOML: {}", e))
         })
     }
+
+    /// Serialize configuration to a TOML string
+    pub fn to_toml_string(&self) -> Result<String> {
+        toml::to_string(self).map_err(|e| {
+            WgAgentError::Serialization(format!("Failed to serialize TOML: {}", e))
+        })
+    }
 }
 
 // Convert TOML config to internal Config
 impl From<TomlConfig> for Config {
     fn from(toml: TomlConfig) -> Self {
         let mut config = Config::new();
+        config.platform = toml.platform;
+        config.supervisor = toml.supervisor;
 
         for (name, network) in toml.network {
             config.add_network(name, network.into());
@@ -128,13 +179,65 @@ impl From<TomlNetworkConfig> for NetworkConfig {
             mtu: toml.mtu,
             private_key_path: toml.private_key_path,
             dns: toml.dns,
-            address: toml.address,
+            addresses: toml.addresses,
+            listen_port: toml.listen_port,
+            bind_address: toml.bind_address,
+            backend: toml.backend,
+            queues: toml.queues,
             peers: toml.peers.into_iter().map(|p| p.into()).collect(),
             http: toml.http.map(|h| h.into()),
         }
     }
 }
 
+impl From<&Config> for TomlConfig {
+    fn from(config: &Config) -> Self {
+        TomlConfig {
+            network: config
+                .networks
+                .iter()
+                .map(|(name, network)| (name.clone(), network.into()))
+                .collect(),
+            platform: config.platform.clone(),
+            supervisor: config.supervisor.clone(),
+        }
+    }
+}
+
+impl From<&NetworkConfig> for TomlNetworkConfig {
+    fn from(network: &NetworkConfig) -> Self {
+        TomlNetworkConfig {
+            enable_wireguard: network.enable_wireguard,
+            interface: network.interface.clone(),
+            mtu: network.mtu,
+            private_key_path: network.private_key_path.clone(),
+            addresses: network.addresses.clone(),
+            dns: network.dns.clone(),
+            listen_port: network.listen_port,
+            bind_address: network.bind_address.clone(),
+            backend: network.backend,
+            queues: network.queues,
+            http: network.http.as_ref().map(|h| TomlHttpConfig {
+                bind_address: h.bind_address.clone(),
+                bind_port: h.bind_port,
+            }),
+            peers: network
+                .peers
+                .iter()
+                .map(|p| TomlPeerConfig {
+                    name: p.name.clone(),
+                    public_key: p.public_key.clone(),
+                    endpoint: p.endpoint.clone(),
+                    allowed_ips: p.allowed_ips.clone(),
+                    persistent_keepalive_secs: p.persistent_keepalive_secs,
+                    endpoint_refresh_secs: p.endpoint_refresh_secs,
+                    preshared_key_path: p.preshared_key_path.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
 impl From<TomlHttpConfig> for HttpConfig {
     fn from(toml: TomlHttpConfig) -> Self {
         HttpConfig {
@@ -152,6 +255,9 @@ impl From<TomlPeerConfig> for PeerConfig {
             endpoint: toml.endpoint,
             allowed_ips: toml.allowed_ips,
             persistent_keepalive_secs: toml.persistent_keepalive_secs,
+            endpoint_refresh_secs: toml.endpoint_refresh_secs,
+            preshared_key_path: toml.preshared_key_path,
+            preshared_key: None,
         }
     }
 }
@@ -169,9 +275,14 @@ fn default_keepalive() -> u16 {
     25
 }
 
+fn default_endpoint_refresh() -> u64 {
+    300
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::PlatformBackend;
 
     #[test]
     fn test_parse_basic_toml() {
@@ -263,6 +374,110 @@ mod tests {
         assert!(network.peers.is_empty());
     }
 
+    #[test]
+    fn test_parse_addresses() {
+        let toml = r#"
+            [network.single]
+            private_key_path = "/etc/harmony-agent/private.key"
+            address = "10.0.0.2/24"
+
+            [network.dual]
+            private_key_path = "/etc/harmony-agent/private.key"
+            addresses = ["10.0.0.2/24", "fd00::2/64"]
+        "#;
+
+        let config = TomlConfig::parse(toml).expect("Failed to parse TOML");
+        assert_eq!(config.network["single"].addresses, vec!["10.0.0.2/24"]);
+        assert_eq!(
+            config.network["dual"].addresses,
+            vec!["10.0.0.2/24", "fd00::2/64"]
+        );
+    }
+
+    #[test]
+    fn test_parse_wireguard_backend() {
+        let toml = r#"
+            [network.fast]
+            private_key_path = "/etc/harmony-agent/private.key"
+            backend = "kernel"
+
+            [network.default]
+            private_key_path = "/etc/harmony-agent/private.key"
+        "#;
+
+        let config: Config = TomlConfig::parse(toml).expect("Failed to parse TOML").into();
+        assert_eq!(config.networks["fast"].backend, WireguardBackend::Kernel);
+        assert_eq!(config.networks["default"].backend, WireguardBackend::Auto);
+
+        // The default is left out when writing TOML back
+        let written = TomlConfig::from(&config).to_toml_string().unwrap();
+        assert_eq!(written.matches("backend").count(), 1);
+
+        let invalid = "[network.x]\nprivate_key_path = \"k\"\nbackend = \"gpu\"";
+        assert!(TomlConfig::parse(invalid).is_err());
+    }
+
+    #[test]
+    fn test_parse_platform_section() {
+        let toml = r#"
+            [platform]
+            backend = "command"
+            route_metric = 100
+        "#;
+
+        let config: Config = TomlConfig::parse(toml).expect("Failed to parse TOML").into();
+        assert_eq!(config.platform.backend, PlatformBackend::Command);
+        assert_eq!(config.platform.route_metric, Some(100));
+
+        let defaults: Config = TomlConfig::parse("").unwrap().into();
+        assert_eq!(defaults.platform.backend, PlatformBackend::Netlink);
+        assert!(TomlConfig::parse("[platform]\nbackend = \"ifconfig\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_supervisor_section() {
+        let toml = r#"
+            [supervisor]
+            handshake_timeout_secs = 0
+            max_backoff_secs = 60
+        "#;
+
+        let config: Config = TomlConfig::parse(toml).expect("Failed to parse TOML").into();
+        assert!(config.supervisor.enabled);
+        assert_eq!(config.supervisor.handshake_timeout_secs, 0);
+        assert_eq!(config.supervisor.initial_backoff_secs, 1);
+        assert_eq!(config.supervisor.max_backoff_secs, 60);
+        assert!(config.validate().is_ok());
+
+        // The defaults are left out when writing TOML back
+        let defaults: Config = TomlConfig::parse("").unwrap().into();
+        let written = TomlConfig::from(&defaults).to_toml_string().unwrap();
+        assert!(!written.contains("supervisor"));
+
+        let invalid: Config = TomlConfig::parse("[supervisor]\ninitial_backoff_secs = 0")
+            .unwrap()
+            .into();
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_peer_without_endpoint() {
+        let toml = r#"
+            [network.hub]
+            private_key_path = "/etc/harmony-agent/private.key"
+
+            [[network.hub.peers]]
+            name = "roaming-laptop"
+            public_key = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOP=="
+            allowed_ips = ["10.0.0.5/32"]
+        "#;
+
+        let config: Config = TomlConfig::parse(toml).expect("Failed to parse TOML").into();
+        let peer = &config.get_network("hub").unwrap().peers[0];
+        assert!(peer.endpoint.is_empty());
+        assert!(peer.validate().is_ok());
+    }
+
     #[test]
     fn test_convert_to_config() {
         let toml = r#"
@@ -4,7 +4,8 @@
 //! interface names, IP addresses, file paths, keys, and network parameters.
 
 use crate::error::{Result, WgAgentError};
-use std::net::IpAddr;
+use base64::Engine;
+use std::net::{IpAddr, Ipv6Addr};
 use std::path::Path;
 
 /// Validate interface name (alphanumeric, max 15 chars)
@@ -43,6 +44,38 @@ pub fn validate_mtu(mtu: u16) -> Result<()> {
     Ok(())
 }
 
+/// Most TUN queues Linux allows on one device
+pub const MAX_TUN_QUEUES: usize = 256;
+
+/// Validate the number of TUN queues (0 = one per CPU)
+pub fn validate_queues(queues: usize) -> Result<()> {
+    if queues > MAX_TUN_QUEUES {
+        return Err(WgAgentError::Config(format!(
+            "Queue count {} exceeds the maximum of {}",
+            queues, MAX_TUN_QUEUES
+        )));
+    }
+    Ok(())
+}
+
+/// Validate the supervisor's reconnect backoff bounds
+pub fn validate_backoff(initial_secs: u64, max_secs: u64) -> Result<()> {
+    if initial_secs == 0 {
+        return Err(WgAgentError::Config(
+            "Initial reconnect backoff must be at least 1 second".to_string(),
+        ));
+    }
+
+    if max_secs < initial_secs {
+        return Err(WgAgentError::Config(format!(
+            "Maximum reconnect backoff {} is below the initial backoff {}",
+            max_secs, initial_secs
+        )));
+    }
+
+    Ok(())
+}
+
 /// Validate IP address
 pub fn validate_ip_address(ip: &str) -> Result<()> {
     ip.parse::<IpAddr>()
@@ -86,7 +119,7 @@ pub fn validate_cidr(cidr: &str) -> Result<()> {
     Ok(())
 }
 
-/// Validate endpoint format (host:port)
+/// Validate endpoint format (host:port, or [IPv6]:port)
 pub fn validate_endpoint(endpoint: &str) -> Result<()> {
     let parts: Vec<&str> = endpoint.rsplitn(2, ':').collect();
     
@@ -116,6 +149,21 @@ pub fn validate_endpoint(endpoint: &str) -> Result<()> {
         ));
     }
 
+    // IPv6 literals must be bracketed so the port is unambiguous
+    if let Some(bracketed) = host.strip_prefix('[') {
+        bracketed
+            .strip_suffix(']')
+            .and_then(|ip| ip.parse::<Ipv6Addr>().ok())
+            .ok_or_else(|| {
+                WgAgentError::Config(format!("Invalid IPv6 address in endpoint: {}", endpoint))
+            })?;
+    } else if host.contains(':') {
+        return Err(WgAgentError::Config(format!(
+            "IPv6 endpoint {} must be written as [address]:port",
+            endpoint
+        )));
+    }
+
     Ok(())
 }
 
@@ -167,6 +215,23 @@ pub fn validate_public_key(key: &str) -> Result<()> {
     Ok(())
 }
 
+/// Validate base64-encoded preshared key
+pub fn validate_preshared_key(key: &str) -> Result<()> {
+    // Never include the key itself in error messages
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(key.trim())
+        .map_err(|_| WgAgentError::Config("Preshared key is not valid base64".to_string()))?;
+
+    if decoded.len() != 32 {
+        return Err(WgAgentError::Config(format!(
+            "Invalid preshared key length: expected 32 bytes, got {}",
+            decoded.len()
+        )));
+    }
+
+    Ok(())
+}
+
 /// Validate keepalive timeout
 pub fn validate_keepalive(secs: u16) -> Result<()> {
     // Reasonable range: 0 (disabled) or 10-300 seconds
@@ -187,6 +252,36 @@ pub fn validate_keepalive(secs: u16) -> Result<()> {
     Ok(())
 }
 
+/// Validate endpoint re-resolution interval
+pub fn validate_endpoint_refresh(secs: u64) -> Result<()> {
+    // 0 disables re-resolution; otherwise avoid hammering DNS
+    if secs > 0 && secs < 10 {
+        return Err(WgAgentError::Config(format!(
+            "Endpoint refresh interval {} is too short (minimum 10 seconds or 0 to disable)",
+            secs
+        )));
+    }
+
+    Ok(())
+}
+
+/// Validate UDP bind address (unicast or unspecified IP)
+pub fn validate_bind_address(addr: &str) -> Result<()> {
+    let ip: IpAddr = addr
+        .parse()
+        .map_err(|_| WgAgentError::Config(format!("Invalid bind address: {}", addr)))?;
+
+    let broadcast = matches!(ip, IpAddr::V4(v4) if v4.is_broadcast());
+    if ip.is_multicast() || broadcast {
+        return Err(WgAgentError::Config(format!(
+            "Bind address {} must be a unicast address",
+            addr
+        )));
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -210,6 +305,22 @@ mod tests {
         assert!(validate_mtu(1501).is_err());
     }
 
+    #[test]
+    fn test_validate_queues() {
+        assert!(validate_queues(0).is_ok());
+        assert!(validate_queues(8).is_ok());
+        assert!(validate_queues(MAX_TUN_QUEUES).is_ok());
+        assert!(validate_queues(MAX_TUN_QUEUES + 1).is_err());
+    }
+
+    #[test]
+    fn test_validate_backoff() {
+        assert!(validate_backoff(1, 300).is_ok());
+        assert!(validate_backoff(5, 5).is_ok());
+        assert!(validate_backoff(0, 300).is_err());
+        assert!(validate_backoff(10, 5).is_err());
+    }
+
     #[test]
     fn test_validate_ip_address() {
         assert!(validate_ip_address("192.168.1.1").is_ok());
@@ -235,6 +346,10 @@ mod tests {
         assert!(validate_endpoint("example.com:51820").is_ok());
         assert!(validate_endpoint("192.168.1.1:51820").is_ok());
         assert!(validate_endpoint("[::1]:51820").is_ok());
+        assert!(validate_endpoint("[2001:db8::1]:51820").is_ok());
+        assert!(validate_endpoint("2001:db8::1:51820").is_err());
+        assert!(validate_endpoint("[2001:db8::zz]:51820").is_err());
+        assert!(validate_endpoint("[2001:db8::1:51820").is_err());
         assert!(validate_endpoint("invalid").is_err());
         assert!(validate_endpoint("example.com:0").is_err());
         assert!(validate_endpoint(":51820").is_err());
@@ -258,4 +373,28 @@ mod tests {
         assert!(validate_keepalive(5).is_err());
         assert!(validate_keepalive(301).is_err());
     }
+
+    #[test]
+    fn test_validate_preshared_key() {
+        assert!(validate_preshared_key("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=").is_ok());
+        assert!(validate_preshared_key("AAAA").is_err());
+        assert!(validate_preshared_key("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_validate_endpoint_refresh() {
+        assert!(validate_endpoint_refresh(0).is_ok());
+        assert!(validate_endpoint_refresh(300).is_ok());
+        assert!(validate_endpoint_refresh(5).is_err());
+    }
+
+    #[test]
+    fn test_validate_bind_address() {
+        assert!(validate_bind_address("0.0.0.0").is_ok());
+        assert!(validate_bind_address("::").is_ok());
+        assert!(validate_bind_address("192.168.1.10").is_ok());
+        assert!(validate_bind_address("224.0.0.1").is_err());
+        assert!(validate_bind_address("255.255.255.255").is_err());
+        assert!(validate_bind_address("example.com").is_err());
+    }
 }
@@ -0,0 +1,429 @@
+//! wg-quick configuration files
+//!
+//! This module parses standard wg-quick `.conf` files (`[Interface]` and
+//! `[Peer]` sections) into `NetworkConfig` and writes any `NetworkConfig`
+//! back out in the same format.
+
+use crate::config::{validation, NetworkConfig, PeerConfig, WireguardBackend};
+use crate::error::{Result, WgAgentError};
+use crate::wireguard::{PresharedKey, PrivateKey};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+/// Contents of a wg-quick configuration file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WgQuickConfig {
+    /// Base64 private key
+    pub private_key: Option<String>,
+    /// Base64 public key (informational, checked against the private key)
+    pub public_key: Option<String>,
+    /// Interface addresses (CIDR notation)
+    pub addresses: Vec<String>,
+    /// DNS servers
+    pub dns: Vec<String>,
+    /// Maximum Transmission Unit
+    pub mtu: Option<u16>,
+    /// UDP listen port
+    pub listen_port: Option<u16>,
+    /// Peers
+    pub peers: Vec<WgQuickPeer>,
+}
+
+/// A `[Peer]` section
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WgQuickPeer {
+    /// Peer name, from a `# Name = ...` comment
+    pub name: Option<String>,
+    /// Base64 public key
+    pub public_key: String,
+    /// Base64 preshared key
+    pub preshared_key: Option<String>,
+    /// Endpoint (`host:port`)
+    pub endpoint: Option<String>,
+    /// Allowed IP ranges
+    pub allowed_ips: Vec<String>,
+    /// Persistent keepalive in seconds
+    pub persistent_keepalive: Option<u16>,
+}
+
+/// Section currently being parsed
+enum Section {
+    None,
+    Interface,
+    Peer,
+}
+
+impl WgQuickConfig {
+    /// Load a wg-quick configuration file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| {
+            WgAgentError::Config(format!("Failed to read wg-quick config {:?}: {}", path, e))
+        })?;
+        Self::parse(&contents)
+    }
+
+    /// Parse a wg-quick configuration
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut config = WgQuickConfig::default();
+        let mut section = Section::None;
+
+        for (number, raw) in contents.lines().enumerate() {
+            let line = raw.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            // `# Name = ...` inside a peer section names the peer
+            if let Some(comment) = line.strip_prefix('#') {
+                if let (Section::Peer, Some((key, value))) = (&section, comment.split_once('=')) {
+                    if key.trim().eq_ignore_ascii_case("name") {
+                        if let Some(peer) = config.peers.last_mut() {
+                            peer.name = Some(value.trim().to_string());
+                        }
+                    }
+                }
+                continue;
+            }
+
+            if line.starts_with('[') {
+                section = match line.to_ascii_lowercase().as_str() {
+                    "[interface]" => Section::Interface,
+                    "[peer]" => {
+                        config.peers.push(WgQuickPeer::default());
+                        Section::Peer
+                    }
+                    _ => {
+                        return Err(WgAgentError::Config(format!(
+                            "Line {}: unknown section {}",
+                            number + 1,
+                            line
+                        )));
+                    }
+                };
+                continue;
+            }
+
+            let line = line.split('#').next().unwrap_or_default();
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                WgAgentError::Config(format!("Line {}: expected 'Key = Value'", number + 1))
+            })?;
+            let (key, value) = (key.trim(), value.trim());
+
+            match section {
+                Section::None => {
+                    return Err(WgAgentError::Config(format!(
+                        "Line {}: '{}' outside of a section",
+                        number + 1,
+                        key
+                    )));
+                }
+                Section::Interface => config.set_interface_key(key, value, number + 1)?,
+                Section::Peer => {
+                    if let Some(peer) = config.peers.last_mut() {
+                        peer.set_key(key, value, number + 1)?;
+                    }
+                }
+            }
+        }
+
+        for (index, peer) in config.peers.iter().enumerate() {
+            if peer.public_key.is_empty() {
+                return Err(WgAgentError::Config(format!(
+                    "Peer {} has no PublicKey",
+                    index + 1
+                )));
+            }
+        }
+
+        if let (Some(private), Some(public)) = (&config.private_key, &config.public_key) {
+            let derived = PrivateKey::from_base64(private)?.public_key().to_base64();
+            if &derived != public {
+                return Err(WgAgentError::Config(
+                    "Interface PublicKey does not match PrivateKey".to_string(),
+                ));
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn set_interface_key(&mut self, key: &str, value: &str, line: usize) -> Result<()> {
+        match key.to_ascii_lowercase().as_str() {
+            "privatekey" => self.private_key = Some(value.to_string()),
+            "publickey" => self.public_key = Some(value.to_string()),
+            "address" => self.addresses.extend(split_list(value)),
+            "dns" => self.dns.extend(split_list(value)),
+            "mtu" => self.mtu = Some(parse_number(key, value, line)?),
+            "listenport" => self.listen_port = Some(parse_number(key, value, line)?),
+            _ => warn!("Ignoring unsupported wg-quick interface setting '{}'", key),
+        }
+        Ok(())
+    }
+
+    /// Convert into a `NetworkConfig` using the given interface name and key file path
+    ///
+    /// Preshared keys are kept inline in `PeerConfig::preshared_key`.
+    pub fn to_network_config(&self, interface: &str, private_key_path: &str) -> NetworkConfig {
+        // wg-quick allows DNS search domains next to server addresses
+        let (dns, search): (Vec<String>, Vec<String>) = self
+            .dns
+            .iter()
+            .cloned()
+            .partition(|entry| validation::validate_ip_address(entry).is_ok());
+        if !search.is_empty() {
+            warn!("Ignoring DNS search domains: {}", search.join(", "));
+        }
+
+        let peers = self
+            .peers
+            .iter()
+            .enumerate()
+            .map(|(index, peer)| PeerConfig {
+                name: peer
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("peer-{}", index + 1)),
+                public_key: peer.public_key.clone(),
+                endpoint: peer.endpoint.clone().unwrap_or_default(),
+                allowed_ips: peer.allowed_ips.clone(),
+                persistent_keepalive_secs: peer.persistent_keepalive.unwrap_or(0),
+                endpoint_refresh_secs: super::default_endpoint_refresh(),
+                preshared_key_path: None,
+                preshared_key: peer.preshared_key.clone(),
+            })
+            .collect();
+
+        NetworkConfig {
+            enable_wireguard: true,
+            interface: interface.to_string(),
+            mtu: self.mtu.unwrap_or_else(super::default_mtu),
+            private_key_path: private_key_path.to_string(),
+            dns,
+            addresses: self.addresses.clone(),
+            listen_port: self.listen_port.unwrap_or(0),
+            bind_address: None,
+            backend: WireguardBackend::Auto,
+            queues: 0,
+            peers,
+            http: None,
+        }
+    }
+
+    /// Build from a `NetworkConfig`, reading the private and preshared key files
+    pub fn from_network_config(network: &NetworkConfig) -> Result<Self> {
+        let private = PrivateKey::from_file(&network.private_key_path)?;
+
+        let peers = network
+            .peers
+            .iter()
+            .map(|peer| {
+                let preshared_key = match (&peer.preshared_key, &peer.preshared_key_path) {
+                    (Some(key), _) => Some(key.clone()),
+                    (None, Some(path)) => Some(PresharedKey::from_file(path)?.to_base64()),
+                    (None, None) => None,
+                };
+                Ok(WgQuickPeer {
+                    name: Some(peer.name.clone()),
+                    public_key: peer.public_key.clone(),
+                    preshared_key,
+                    endpoint: Some(peer.endpoint.clone()).filter(|e| !e.is_empty()),
+                    allowed_ips: peer.allowed_ips.clone(),
+                    persistent_keepalive: Some(peer.persistent_keepalive_secs)
+                        .filter(|secs| *secs > 0),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            public_key: Some(private.public_key().to_base64()),
+            private_key: Some(private.to_base64()),
+            addresses: network.addresses.clone(),
+            dns: network.dns.clone(),
+            mtu: Some(network.mtu),
+            listen_port: Some(network.listen_port).filter(|port| *port > 0),
+            peers,
+        })
+    }
+}
+
+impl WgQuickPeer {
+    fn set_key(&mut self, key: &str, value: &str, line: usize) -> Result<()> {
+        match key.to_ascii_lowercase().as_str() {
+            "publickey" => self.public_key = value.to_string(),
+            "presharedkey" => self.preshared_key = Some(value.to_string()),
+            "endpoint" => self.endpoint = Some(value.to_string()),
+            "allowedips" => self.allowed_ips.extend(split_list(value)),
+            "persistentkeepalive" => {
+                self.persistent_keepalive = if value.eq_ignore_ascii_case("off") {
+                    None
+                } else {
+                    Some(parse_number(key, value, line)?)
+                };
+            }
+            _ => warn!("Ignoring unsupported wg-quick peer setting '{}'", key),
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for WgQuickConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[Interface]")?;
+        if let Some(ref public_key) = self.public_key {
+            writeln!(f, "# PublicKey = {}", public_key)?;
+        }
+        if let Some(ref private_key) = self.private_key {
+            writeln!(f, "PrivateKey = {}", private_key)?;
+        }
+        if !self.addresses.is_empty() {
+            writeln!(f, "Address = {}", self.addresses.join(", "))?;
+        }
+        if !self.dns.is_empty() {
+            writeln!(f, "DNS = {}", self.dns.join(", "))?;
+        }
+        if let Some(mtu) = self.mtu {
+            writeln!(f, "MTU = {}", mtu)?;
+        }
+        if let Some(port) = self.listen_port {
+            writeln!(f, "ListenPort = {}", port)?;
+        }
+
+        for peer in &self.peers {
+            writeln!(f)?;
+            writeln!(f, "[Peer]")?;
+            if let Some(ref name) = peer.name {
+                writeln!(f, "# Name = {}", name)?;
+            }
+            writeln!(f, "PublicKey = {}", peer.public_key)?;
+            if let Some(ref preshared_key) = peer.preshared_key {
+                writeln!(f, "PresharedKey = {}", preshared_key)?;
+            }
+            if let Some(ref endpoint) = peer.endpoint {
+                writeln!(f, "Endpoint = {}", endpoint)?;
+            }
+            if !peer.allowed_ips.is_empty() {
+                writeln!(f, "AllowedIPs = {}", peer.allowed_ips.join(", "))?;
+            }
+            if let Some(keepalive) = peer.persistent_keepalive {
+                writeln!(f, "PersistentKeepalive = {}", keepalive)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Split a comma-separated list
+fn split_list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+}
+
+/// Parse a numeric setting
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str, line: usize) -> Result<T> {
+    value.parse().map_err(|_| {
+        WgAgentError::Config(format!("Line {}: invalid {} '{}'", line, key, value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wireguard::KeyPair;
+
+    fn sample(private: &PrivateKey, peer: &str, psk: &str) -> String {
+        format!(
+            r#"
+# Partner site
+[Interface]
+PrivateKey = {}
+Address = 10.100.0.2/24, fd00::2/64
+DNS = 10.100.0.1, corp.example
+MTU = 1380
+ListenPort = 51820
+PostUp = iptables -A FORWARD -i %i -j ACCEPT
+
+[Peer]
+# Name = gateway
+PublicKey = {}
+PresharedKey = {}
+Endpoint = vpn.example.com:51820
+AllowedIPs = 10.100.0.0/24,
+AllowedIPs = 192.168.10.0/24
+PersistentKeepalive = 25
+"#,
+            private.to_base64(),
+            peer,
+            psk
+        )
+    }
+
+    #[test]
+    fn test_parse_wgquick() {
+        let keys = KeyPair::generate();
+        let peer = KeyPair::generate().public.to_base64();
+        let psk = PresharedKey::generate().to_base64();
+
+        let config = WgQuickConfig::parse(&sample(&keys.private, &peer, &psk)).unwrap();
+        assert_eq!(config.addresses, vec!["10.100.0.2/24", "fd00::2/64"]);
+        assert_eq!(config.mtu, Some(1380));
+        assert_eq!(config.listen_port, Some(51820));
+        assert_eq!(config.peers.len(), 1);
+
+        let peer_config = &config.peers[0];
+        assert_eq!(peer_config.name.as_deref(), Some("gateway"));
+        assert_eq!(peer_config.allowed_ips, vec!["10.100.0.0/24", "192.168.10.0/24"]);
+        assert_eq!(peer_config.persistent_keepalive, Some(25));
+
+        let network = config.to_network_config("wg-partner", "/etc/harmony-agent/partner.key");
+        assert_eq!(network.addresses, vec!["10.100.0.2/24", "fd00::2/64"]);
+        assert_eq!(network.listen_port, 51820);
+        assert_eq!(network.dns, vec!["10.100.0.1"]);
+        assert_eq!(network.peers[0].preshared_key.as_deref(), Some(psk.as_str()));
+        network.validate().unwrap();
+    }
+
+    #[test]
+    fn test_parse_wgquick_errors() {
+        assert!(WgQuickConfig::parse("PrivateKey = abc").is_err());
+        assert!(WgQuickConfig::parse("[Tunnel]\nMTU = 1420").is_err());
+        assert!(WgQuickConfig::parse("[Interface]\nMTU = big").is_err());
+        assert!(WgQuickConfig::parse("[Peer]\nAllowedIPs = 10.0.0.0/8").is_err());
+
+        let keys = KeyPair::generate();
+        let other = KeyPair::generate();
+        let mismatched = format!(
+            "[Interface]\nPrivateKey = {}\nPublicKey = {}\n",
+            keys.private.to_base64(),
+            other.public.to_base64()
+        );
+        assert!(WgQuickConfig::parse(&mismatched).is_err());
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("private.key");
+        let keys = KeyPair::generate();
+        keys.private.save_to_file(&key_path).unwrap();
+
+        let peer = KeyPair::generate().public.to_base64();
+        let psk = PresharedKey::generate().to_base64();
+        let imported = WgQuickConfig::parse(&sample(&keys.private, &peer, &psk)).unwrap();
+        let network = imported.to_network_config("wg0", key_path.to_str().unwrap());
+
+        let exported = WgQuickConfig::from_network_config(&network).unwrap();
+        let reparsed = WgQuickConfig::parse(&exported.to_string()).unwrap();
+        assert_eq!(reparsed.private_key, imported.private_key);
+        assert_eq!(reparsed.peers[0].name.as_deref(), Some("gateway"));
+        assert_eq!(reparsed.peers[0].preshared_key, Some(psk));
+        assert_eq!(reparsed.peers[0].allowed_ips, imported.peers[0].allowed_ips);
+        assert_eq!(reparsed.peers[0].endpoint, imported.peers[0].endpoint);
+    }
+}
@@ -135,14 +135,39 @@ pub enum ApiError {
     /// Permission denied
     #[error("Permission denied: {0}")]
     PermissionDenied(String),
+
+    /// The agent could not be reached over the control socket
+    #[error("Agent unreachable: {0}")]
+    Unreachable(String),
+}
+
+impl ApiError {
+    /// Process exit code used by the command line client for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ApiError::ParseError(_)
+            | ApiError::SerializationError(_)
+            | ApiError::InternalError(_) => 1,
+            ApiError::Unreachable(_) => 3,
+            ApiError::NetworkNotFound(_) => 4,
+            ApiError::InvalidState(_) => 5,
+            ApiError::ConfigError(_) => 6,
+            ApiError::AuthenticationFailed | ApiError::PermissionDenied(_) => 7,
+            ApiError::PlatformError(_) => 8,
+        }
+    }
 }
 
 impl From<crate::error::WgAgentError> for ApiError {
     fn from(err: crate::error::WgAgentError) -> Self {
-        use crate::error::WgAgentError;
+        use crate::error::{PlatformError, WgAgentError};
         match err {
             WgAgentError::Config(msg) => ApiError::ConfigError(msg),
             WgAgentError::Platform(msg) => ApiError::PlatformError(msg),
+            WgAgentError::PlatformOperation(e @ PlatformError::PermissionDenied(_)) => {
+                ApiError::PermissionDenied(e.to_string())
+            }
+            WgAgentError::PlatformOperation(e) => ApiError::PlatformError(e.to_string()),
             WgAgentError::InvalidState(msg) => ApiError::InvalidState(msg),
             WgAgentError::NotFound(msg) => ApiError::NetworkNotFound(msg),
             WgAgentError::Permission(msg) => ApiError::PermissionDenied(msg),
@@ -231,6 +256,15 @@ mod tests {
         assert_eq!(resp.success, parsed.success);
     }
 
+    #[test]
+    fn test_api_error_exit_codes() {
+        assert_eq!(ApiError::Unreachable("gone".to_string()).exit_code(), 3);
+        assert_eq!(ApiError::NetworkNotFound("x".to_string()).exit_code(), 4);
+        assert_eq!(ApiError::InternalError("x".to_string()).exit_code(), 1);
+        // 2 is reserved for a degraded `status`
+        assert_ne!(ApiError::InvalidState("x".to_string()).exit_code(), 2);
+    }
+
     #[test]
     fn test_api_error_conversion() {
         let wg_error = crate::error::WgAgentError::Config("test error".to_string());
@@ -0,0 +1,163 @@
+//! Control client for talking to a running agent
+//!
+//! This module sends API requests over the control socket and reads back
+//! the response. It is used by the CLI subcommands.
+
+use crate::control::{ApiError, ApiRequest, ApiResponse};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[cfg(unix)]
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+/// Default time to wait for a response
+pub const DEFAULT_CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client for the control socket
+#[derive(Debug, Clone)]
+pub struct ControlClient {
+    /// Path to the Unix socket
+    socket_path: PathBuf,
+    /// Time to wait for a response
+    timeout: Duration,
+}
+
+impl ControlClient {
+    /// Create a new client for the given socket
+    pub fn new(socket_path: impl Into<PathBuf>) -> Self {
+        Self {
+            socket_path: socket_path.into(),
+            timeout: DEFAULT_CLIENT_TIMEOUT,
+        }
+    }
+
+    /// Set the response timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Socket path this client connects to
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Send a request and wait for its response
+    #[cfg(unix)]
+    pub async fn send(&self, request: &ApiRequest) -> Result<ApiResponse, ApiError> {
+        tokio::time::timeout(self.timeout, self.exchange(request))
+            .await
+            .map_err(|_| {
+                ApiError::Unreachable(format!(
+                    "No response from {} within {}s",
+                    self.socket_path.display(),
+                    self.timeout.as_secs()
+                ))
+            })?
+    }
+
+    /// Send a request (Windows stub)
+    #[cfg(windows)]
+    pub async fn send(&self, _request: &ApiRequest) -> Result<ApiResponse, ApiError> {
+        Err(ApiError::Unreachable(
+            "Windows client not yet implemented".to_string(),
+        ))
+    }
+
+    #[cfg(unix)]
+    async fn exchange(&self, request: &ApiRequest) -> Result<ApiResponse, ApiError> {
+        let stream = UnixStream::connect(&self.socket_path).await.map_err(|e| {
+            ApiError::Unreachable(format!("{}: {}", self.socket_path.display(), e))
+        })?;
+        let (reader, mut writer) = stream.into_split();
+
+        let mut line = request.to_json()?;
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await.map_err(|e| {
+            ApiError::Unreachable(format!("Failed to send request: {}", e))
+        })?;
+
+        let mut response = String::new();
+        let read = BufReader::new(reader)
+            .read_line(&mut response)
+            .await
+            .map_err(|e| ApiError::Unreachable(format!("Failed to read response: {}", e)))?;
+        if read == 0 {
+            return Err(ApiError::Unreachable(
+                "Agent closed the connection without responding".to_string(),
+            ));
+        }
+
+        ApiResponse::from_json(response.trim())
+    }
+
+    /// Wait until the socket file is removed, as the agent does on shutdown
+    pub async fn wait_for_shutdown(&self, timeout: Duration) -> Result<(), ApiError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.socket_path.exists() {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(ApiError::InvalidState(format!(
+                    "Agent did not shut down within {}s",
+                    timeout.as_secs()
+                )));
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::config::ControlAction;
+    use crate::control::{CommandHandler, ControlServer};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_client_round_trip() {
+        let tmp_dir = TempDir::new().unwrap();
+        let socket_path = tmp_dir.path().join("agent.sock");
+        let server = ControlServer::new(socket_path.clone(), Arc::new(CommandHandler::new()));
+        let server_task = tokio::spawn(async move { server.start().await });
+
+        while !socket_path.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let client = ControlClient::new(&socket_path);
+        let request = ApiRequest::new(
+            "test-1".to_string(),
+            ControlAction::AgentStatus,
+            "default".to_string(),
+        );
+        let response = client.send(&request).await.unwrap();
+        assert_eq!(response.id, "test-1");
+        assert!(response.success);
+
+        server_task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_client_unreachable() {
+        let tmp_dir = TempDir::new().unwrap();
+        let client = ControlClient::new(tmp_dir.path().join("missing.sock"));
+        let request = ApiRequest::new(
+            "test-1".to_string(),
+            ControlAction::AgentStatus,
+            "default".to_string(),
+        );
+
+        assert!(matches!(
+            client.send(&request).await,
+            Err(ApiError::Unreachable(_))
+        ));
+        client
+            .wait_for_shutdown(Duration::from_secs(1))
+            .await
+            .unwrap();
+    }
+}
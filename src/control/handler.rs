@@ -3,12 +3,21 @@
 //! This module handles execution of API commands by dispatching them
 //! to the appropriate tunnel operations.
 
-use crate::config::{Config, ControlAction, NetworkConfig};
-use crate::control::{ApiError, ApiRequest, ApiResponse};
-use crate::wireguard::{Tunnel, TunnelConfig};
-use std::collections::HashMap;
+use crate::config::{
+    Config, ControlAction, JsonNetworkConfig, JsonPeerConfig, NetworkConfig,
+    PeerConfig as ConfigPeer,
+};
+use crate::control::supervisor::Reconnect;
+use crate::control::{ApiError, ApiRequest, ApiResponse, RuntimeState, SupervisorPolicy};
+use crate::security::SecurityEvent;
+use crate::wireguard::{KeyPair, PeerConfig, PublicKey, Tunnel, TunnelConfig, TunnelState};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Instant;
+use tokio::sync::{Mutex, Notify, RwLock};
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
 /// Command handler manages tunnels and executes API commands
@@ -17,6 +26,20 @@ pub struct CommandHandler {
     tunnels: Arc<RwLock<HashMap<String, Arc<Tunnel>>>>,
     /// Agent configuration
     config: Arc<RwLock<Option<Config>>>,
+    /// Serializes key rotations so the key file and device never disagree
+    rotation_lock: Mutex<()>,
+    /// Networks supplied inline through the API, by name
+    runtime_networks: RwLock<HashMap<String, JsonNetworkConfig>>,
+    /// Where inline networks are persisted (None = not persisted)
+    state: Option<RuntimeState>,
+    /// When the handler was created, for the agent uptime
+    started_at: Instant,
+    /// Signalled when a client asks the agent to shut down
+    shutdown: Notify,
+    /// Reconnect progress of supervised networks, by name
+    reconnects: std::sync::Mutex<HashMap<String, Reconnect>>,
+    /// Enabled networks a client disconnected, which the supervisor leaves down
+    disconnected: std::sync::Mutex<HashSet<String>>,
 }
 
 impl CommandHandler {
@@ -25,9 +48,50 @@ impl CommandHandler {
         Self {
             tunnels: Arc::new(RwLock::new(HashMap::new())),
             config: Arc::new(RwLock::new(None)),
+            rotation_lock: Mutex::new(()),
+            runtime_networks: RwLock::new(HashMap::new()),
+            state: None,
+            started_at: Instant::now(),
+            shutdown: Notify::new(),
+            reconnects: std::sync::Mutex::new(HashMap::new()),
+            disconnected: std::sync::Mutex::new(HashSet::new()),
         }
     }
 
+    /// Wait until a client requests shutdown through the `shutdown` action
+    pub async fn shutdown_requested(&self) {
+        self.shutdown.notified().await;
+    }
+
+    /// Persist inline network configurations to a state file
+    pub fn with_state_file(mut self, path: PathBuf) -> Self {
+        self.state = Some(RuntimeState::new(path));
+        self
+    }
+
+    /// Merge networks saved in the state file into `config`, returning their names
+    pub async fn restore_state(&self, config: &mut Config) -> crate::error::Result<Vec<String>> {
+        let Some(ref state) = self.state else {
+            return Ok(Vec::new());
+        };
+
+        let saved = state.load()?;
+        let mut restored = Vec::new();
+        for (name, json) in &saved {
+            let network = NetworkConfig::from(json.clone());
+            if let Err(e) = network.validate() {
+                warn!("Skipping saved network '{}': {}", name, e);
+                continue;
+            }
+            config.add_network(name.clone(), network);
+            restored.push(name.clone());
+        }
+
+        *self.runtime_networks.write().await = saved;
+        info!("Restored {} network(s) from {:?}", restored.len(), state.path());
+        Ok(restored)
+    }
+
     /// Load configuration
     pub async fn load_config(&self, config: Config) {
         let mut cfg = self.config.write().await;
@@ -48,6 +112,12 @@ impl CommandHandler {
             ControlAction::Status => self.handle_status(&request).await,
             ControlAction::Reload => self.handle_reload(&request).await,
             ControlAction::RotateKeys => self.handle_rotate_keys(&request).await,
+            ControlAction::AddPeer => self.handle_add_peer(&request).await,
+            ControlAction::RemovePeer => self.handle_remove_peer(&request).await,
+            ControlAction::UpdatePeer => self.handle_update_peer(&request).await,
+            ControlAction::ListPeers => self.handle_list_peers(&request).await,
+            ControlAction::AgentStatus => self.handle_agent_status().await,
+            ControlAction::Shutdown => self.handle_shutdown().await,
         };
 
         match result {
@@ -65,6 +135,29 @@ impl CommandHandler {
         }
     }
 
+    /// Handle agent_status action
+    async fn handle_agent_status(&self) -> Result<Option<serde_json::Value>, ApiError> {
+        let states = self.get_all_states().await;
+
+        Ok(Some(serde_json::json!({
+            "version": crate::VERSION,
+            "pid": std::process::id(),
+            "uptime_secs": self.started_at.elapsed().as_secs(),
+            "networks": states,
+        })))
+    }
+
+    /// Handle shutdown action
+    async fn handle_shutdown(&self) -> Result<Option<serde_json::Value>, ApiError> {
+        info!("Shutdown requested through the control API");
+        // notify_one keeps the permit if the main loop is not waiting yet
+        self.shutdown.notify_one();
+
+        Ok(Some(serde_json::json!({
+            "shutting_down": true,
+        })))
+    }
+
     /// Handle connect action
     async fn handle_connect(
         &self,
@@ -86,8 +179,12 @@ impl CommandHandler {
             }
         }
 
-        // Get network configuration
-        let network_config = self.get_network_config(&request.network).await?;
+        // Get network configuration (inline payload takes precedence)
+        let inline = Self::parse_inline_config(request)?;
+        let network_config = match inline {
+            Some((ref network_config, _)) => network_config.clone(),
+            None => self.get_network_config(&request.network).await?,
+        };
 
         // Create tunnel
         let tunnel = Tunnel::from_network_config(&network_config).map_err(ApiError::from)?;
@@ -96,9 +193,16 @@ impl CommandHandler {
         // Start tunnel
         tunnel.start().await.map_err(ApiError::from)?;
 
+        if let Some((network_config, json)) = inline {
+            self.store_inline_config(&request.network, network_config, json).await;
+        }
+
         // Store tunnel
         let mut tunnels = self.tunnels.write().await;
         tunnels.insert(request.network.clone(), tunnel.clone());
+        drop(tunnels);
+        self.reconnects.lock().unwrap().remove(&request.network);
+        self.disconnected.lock().unwrap().remove(&request.network);
 
         // Get stats
         let stats = tunnel.stats().await;
@@ -118,21 +222,29 @@ impl CommandHandler {
     ) -> Result<Option<serde_json::Value>, ApiError> {
         info!("Disconnecting network: {}", request.network);
 
-        // Get tunnel
-        let tunnel = {
-            let tunnels = self.tunnels.read().await;
-            tunnels
-                .get(&request.network)
-                .cloned()
-                .ok_or_else(|| ApiError::NetworkNotFound(request.network.clone()))?
-        };
-
-        // Stop tunnel
-        tunnel.stop().await.map_err(ApiError::from)?;
+        // Get tunnel; an enabled network whose tunnel could not be created
+        // only has the supervisor's retries to cancel
+        let tunnel = self.tunnels.read().await.get(&request.network).cloned();
+        match tunnel {
+            Some(tunnel) => tunnel.stop().await.map_err(ApiError::from)?,
+            None if self
+                .get_network_config(&request.network)
+                .await
+                .is_ok_and(|network| network.enable_wireguard) => {}
+            None => return Err(ApiError::NetworkNotFound(request.network.clone())),
+        }
 
         // Remove from active tunnels
         let mut tunnels = self.tunnels.write().await;
         tunnels.remove(&request.network);
+        drop(tunnels);
+        self.reconnects.lock().unwrap().remove(&request.network);
+        self.disconnected.lock().unwrap().insert(request.network.clone());
+
+        // Inline networks are only restored while connected
+        if self.runtime_networks.write().await.remove(&request.network).is_some() {
+            self.save_state().await;
+        }
 
         Ok(Some(serde_json::json!({
             "network": request.network,
@@ -160,20 +272,46 @@ impl CommandHandler {
         let stats = tunnel.stats().await;
         let peer_names = tunnel.peer_names().await;
 
+        let details: serde_json::Map<String, serde_json::Value> = stats
+            .peer_stats
+            .iter()
+            .map(|(name, peer)| {
+                let last_handshake = peer
+                    .last_handshake
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs());
+                (
+                    name.clone(),
+                    serde_json::json!({
+                        "healthy": peer.has_recent_handshake(),
+                        "last_handshake": last_handshake,
+                        "tx_bytes": peer.tx_bytes,
+                        "rx_bytes": peer.rx_bytes,
+                        "handshake_attempts": peer.handshake_attempts,
+                        "successful_handshakes": peer.successful_handshakes,
+                    }),
+                )
+            })
+            .collect();
+
         Ok(Some(serde_json::json!({
             "network": request.network,
             "state": stats.state.to_string(),
             "interface": stats.interface,
+            "listen_port": stats.listen_port,
+            "backend": stats.backend,
             "peers": {
                 "total": stats.total_peers,
                 "active": stats.active_peers,
                 "healthy": stats.healthy_peers,
                 "names": peer_names,
+                "details": details,
             },
             "traffic": {
                 "tx_bytes": stats.total_tx_bytes,
                 "rx_bytes": stats.total_rx_bytes,
             },
+            "reconnect": self.reconnect_status(&request.network),
         })))
     }
 
@@ -193,13 +331,21 @@ impl CommandHandler {
                 .ok_or_else(|| ApiError::NetworkNotFound(request.network.clone()))?
         };
 
-        // Get new configuration
-        let network_config = self.get_network_config(&request.network).await?;
+        // Get new configuration (inline payload takes precedence)
+        let inline = Self::parse_inline_config(request)?;
+        let network_config = match inline {
+            Some((ref network_config, _)) => network_config.clone(),
+            None => self.get_network_config(&request.network).await?,
+        };
         let new_config = TunnelConfig::from_network_config(&network_config).map_err(ApiError::from)?;
 
         // Reload tunnel
         tunnel.reload(new_config).await.map_err(ApiError::from)?;
 
+        if let Some((network_config, json)) = inline {
+            self.store_inline_config(&request.network, network_config, json).await;
+        }
+
         let stats = tunnel.stats().await;
 
         Ok(Some(serde_json::json!({
@@ -212,12 +358,253 @@ impl CommandHandler {
     /// Handle rotate_keys action
     async fn handle_rotate_keys(
         &self,
-        _request: &ApiRequest,
+        request: &ApiRequest,
+    ) -> Result<Option<serde_json::Value>, ApiError> {
+        info!("Rotating keys for network: {}", request.network);
+        let _guard = self.rotation_lock.lock().await;
+
+        let network_config = self.get_network_config(&request.network).await?;
+        let tunnel = self.tunnels.read().await.get(&request.network).cloned();
+
+        let keypair = KeyPair::generate();
+
+        // Switch the running tunnel first, so the file never holds a key the
+        // tunnel could not use
+        let mut sessions_restarted = false;
+        let rotated = match &tunnel {
+            Some(tunnel) => {
+                // Kernel WireGuard restarts every session on a new private key
+                sessions_restarted = tunnel.backend().await == Some("kernel");
+                Some(tunnel.rotate_keys(keypair.clone()).await.map_err(ApiError::from)?)
+            }
+            None => None,
+        };
+
+        if let Err(e) = keypair
+            .private
+            .save_to_file_atomic(&network_config.private_key_path)
+        {
+            if let (Some(tunnel), Some(previous)) = (&tunnel, rotated) {
+                if let Err(rollback) = tunnel.rotate_keys(previous).await {
+                    warn!(
+                        "Failed to restore the previous key on tunnel '{}': {}",
+                        request.network, rollback
+                    );
+                }
+            }
+            return Err(ApiError::from(e));
+        }
+
+        SecurityEvent::KeyRotation {
+            network: request.network.clone(),
+        }
+        .log();
+
+        Ok(Some(serde_json::json!({
+            "network": request.network,
+            "public_key": keypair.public.to_base64(),
+            "applied": tunnel.is_some(),
+            "sessions_restarted": sessions_restarted,
+        })))
+    }
+
+    /// Handle add_peer action
+    async fn handle_add_peer(
+        &self,
+        request: &ApiRequest,
+    ) -> Result<Option<serde_json::Value>, ApiError> {
+        let tunnel = self.get_tunnel(&request.network).await?;
+        let (peer, json) = Self::parse_peer_payload(request)?;
+        info!("Adding peer '{}' to network: {}", peer.name, request.network);
+
+        let name = peer.name.clone();
+        tunnel.add_peer(peer).await.map_err(ApiError::from)?;
+        self.store_peer_edit(&request.network, PeerEdit::Upsert(json)).await;
+
+        Ok(Some(serde_json::json!({
+            "network": request.network,
+            "peer": name,
+            "added": true,
+        })))
+    }
+
+    /// Handle remove_peer action
+    async fn handle_remove_peer(
+        &self,
+        request: &ApiRequest,
+    ) -> Result<Option<serde_json::Value>, ApiError> {
+        let tunnel = self.get_tunnel(&request.network).await?;
+
+        #[derive(Deserialize)]
+        struct RemovePeerPayload {
+            #[serde(rename = "publicKey")]
+            public_key: String,
+        }
+
+        let value = request.config.clone().ok_or_else(|| {
+            ApiError::ConfigError("remove_peer requires config.publicKey".to_string())
+        })?;
+        let payload: RemovePeerPayload = serde_json::from_value(value)
+            .map_err(|e| ApiError::ConfigError(format!("Invalid remove_peer payload: {}", e)))?;
+        let public_key = PublicKey::from_base64(&payload.public_key).map_err(ApiError::from)?;
+        info!("Removing peer {} from network: {}", public_key, request.network);
+
+        let removed = tunnel.remove_peer(&public_key).await.map_err(ApiError::from)?;
+        self.store_peer_edit(&request.network, PeerEdit::Remove(public_key.to_base64()))
+            .await;
+
+        Ok(Some(serde_json::json!({
+            "network": request.network,
+            "peer": removed.name,
+            "removed": true,
+        })))
+    }
+
+    /// Handle update_peer action
+    async fn handle_update_peer(
+        &self,
+        request: &ApiRequest,
+    ) -> Result<Option<serde_json::Value>, ApiError> {
+        let tunnel = self.get_tunnel(&request.network).await?;
+        let (peer, json) = Self::parse_peer_payload(request)?;
+        info!("Updating peer '{}' on network: {}", peer.name, request.network);
+
+        let name = peer.name.clone();
+        tunnel.update_peer(peer).await.map_err(ApiError::from)?;
+        self.store_peer_edit(&request.network, PeerEdit::Upsert(json)).await;
+
+        Ok(Some(serde_json::json!({
+            "network": request.network,
+            "peer": name,
+            "updated": true,
+        })))
+    }
+
+    /// Handle list_peers action
+    async fn handle_list_peers(
+        &self,
+        request: &ApiRequest,
     ) -> Result<Option<serde_json::Value>, ApiError> {
-        warn!("Key rotation not yet implemented");
-        Err(ApiError::InternalError(
-            "Key rotation not yet implemented".to_string(),
-        ))
+        let tunnel = self.get_tunnel(&request.network).await?;
+
+        let peers: Vec<serde_json::Value> = tunnel
+            .peer_info()
+            .await
+            .into_iter()
+            .map(|peer| {
+                let last_handshake = peer
+                    .stats
+                    .last_handshake
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs());
+                serde_json::json!({
+                    "name": peer.name,
+                    "public_key": peer.public_key.to_base64(),
+                    "endpoint": peer.endpoint,
+                    "allowed_ips": peer.allowed_ips,
+                    "active": peer.active,
+                    "last_handshake": last_handshake,
+                    "tx_bytes": peer.stats.tx_bytes,
+                    "rx_bytes": peer.stats.rx_bytes,
+                })
+            })
+            .collect();
+
+        Ok(Some(serde_json::json!({
+            "network": request.network,
+            "peers": peers,
+        })))
+    }
+
+    /// Parse and validate a `JsonPeerConfig` from the request's `config` payload
+    fn parse_peer_payload(request: &ApiRequest) -> Result<(PeerConfig, JsonPeerConfig), ApiError> {
+        let value = request.config.clone().ok_or_else(|| {
+            ApiError::ConfigError("Peer configuration required in config".to_string())
+        })?;
+        let json: JsonPeerConfig = serde_json::from_value(value)
+            .map_err(|e| ApiError::ConfigError(format!("Invalid peer config: {}", e)))?;
+
+        let config_peer = ConfigPeer::from(json.clone());
+        config_peer.validate().map_err(ApiError::from)?;
+        let peer = PeerConfig::from_config(config_peer).map_err(ApiError::from)?;
+
+        Ok((peer, json))
+    }
+
+    /// Mirror a peer change into the stored configuration so later reloads keep it
+    async fn store_peer_edit(&self, network: &str, edit: PeerEdit) {
+        if let Some(network_config) = self
+            .config
+            .write()
+            .await
+            .as_mut()
+            .and_then(|c| c.get_network_mut(network))
+        {
+            edit.apply(&mut network_config.peers, |p| &p.public_key, |j| j.clone().into());
+        }
+
+        let mut runtime_networks = self.runtime_networks.write().await;
+        if let Some(json) = runtime_networks.get_mut(network) {
+            edit.apply(&mut json.peers, |p| &p.public_key, |j| j.clone());
+            drop(runtime_networks);
+            self.save_state().await;
+        }
+    }
+
+    /// Get a registered tunnel by network name
+    async fn get_tunnel(&self, network: &str) -> Result<Arc<Tunnel>, ApiError> {
+        self.tunnels
+            .read()
+            .await
+            .get(network)
+            .cloned()
+            .ok_or_else(|| ApiError::NetworkNotFound(network.to_string()))
+    }
+
+    /// Parse and validate the request's inline `config` payload, if any
+    fn parse_inline_config(
+        request: &ApiRequest,
+    ) -> Result<Option<(NetworkConfig, JsonNetworkConfig)>, ApiError> {
+        let Some(ref value) = request.config else {
+            return Ok(None);
+        };
+
+        let json: JsonNetworkConfig = serde_json::from_value(value.clone())
+            .map_err(|e| ApiError::ConfigError(format!("Invalid inline config: {}", e)))?;
+        let network_config = NetworkConfig::from(json.clone());
+        network_config.validate().map_err(ApiError::from)?;
+
+        Ok(Some((network_config, json)))
+    }
+
+    /// Merge an inline network configuration into the loaded config and persist it
+    async fn store_inline_config(
+        &self,
+        network: &str,
+        network_config: NetworkConfig,
+        json: JsonNetworkConfig,
+    ) {
+        self.config
+            .write()
+            .await
+            .get_or_insert_with(Config::new)
+            .add_network(network.to_string(), network_config);
+
+        self.runtime_networks
+            .write()
+            .await
+            .insert(network.to_string(), json);
+        self.save_state().await;
+    }
+
+    /// Write inline networks to the state file, if one is configured
+    async fn save_state(&self) {
+        if let Some(ref state) = self.state {
+            let networks = self.runtime_networks.read().await;
+            if let Err(e) = state.save(&networks) {
+                warn!("Failed to save runtime state: {}", e);
+            }
+        }
     }
 
     /// Get network configuration
@@ -239,6 +626,207 @@ impl CommandHandler {
         tunnels.keys().cloned().collect()
     }
 
+    /// Spawn the task that restarts failed or stalled tunnels
+    pub fn spawn_supervisor(self: &Arc<Self>, policy: SupervisorPolicy) -> JoinHandle<()> {
+        let handler = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(policy.interval);
+            loop {
+                ticker.tick().await;
+                handler.supervise(&policy).await;
+            }
+        })
+    }
+
+    /// Check every registered tunnel once and restart those that need it
+    ///
+    /// Failed tunnels, and with a handshake timeout degraded ones, are restarted
+    /// after a backoff that grows with each failed attempt. Enabled networks
+    /// whose tunnel could not be created are retried on the same schedule.
+    pub async fn supervise(&self, policy: &SupervisorPolicy) {
+        for (name, tunnel) in self.tunnels().await {
+            match tunnel.check_health(policy.handshake_timeout).await {
+                TunnelState::Active => self.note_active(&name, &tunnel, policy).await,
+                TunnelState::Error | TunnelState::Reconnecting | TunnelState::Degraded => {
+                    self.reconnect(&name, &tunnel, policy).await;
+                }
+                _ => {}
+            }
+        }
+
+        for (name, network) in self.unregistered_networks().await {
+            self.create_tunnel(&name, &network, policy).await;
+        }
+    }
+
+    /// Enabled networks without a tunnel that no client disconnected
+    async fn unregistered_networks(&self) -> Vec<(String, NetworkConfig)> {
+        let config = self.config.read().await;
+        let Some(config) = config.as_ref() else {
+            return Vec::new();
+        };
+        let tunnels = self.tunnels.read().await;
+        let disconnected = self.disconnected.lock().unwrap();
+        config
+            .networks
+            .iter()
+            .filter(|(name, network)| {
+                network.enable_wireguard
+                    && !tunnels.contains_key(*name)
+                    && !disconnected.contains(*name)
+            })
+            .map(|(name, network)| (name.clone(), network.clone()))
+            .collect()
+    }
+
+    /// Schedule the next attempt for a network, returning its number once it is due
+    fn due_attempt(&self, name: &str, policy: &SupervisorPolicy) -> Option<u32> {
+        let now = Instant::now();
+        let mut reconnects = self.reconnects.lock().unwrap();
+        let reconnect = reconnects.entry(name.to_string()).or_default();
+        match reconnect.next_attempt {
+            Some(at) if at <= now => {
+                reconnect.attempts += 1;
+                reconnect.next_attempt = None;
+                Some(reconnect.attempts)
+            }
+            Some(_) => None,
+            None => {
+                let delay = policy.backoff(reconnect.attempts);
+                reconnect.next_attempt = Some(now + delay);
+                info!("Restarting tunnel '{}' in {:.1}s", name, delay.as_secs_f64());
+                None
+            }
+        }
+    }
+
+    /// Record the outcome of a network's restart attempt
+    fn finish_attempt(&self, name: &str, result: crate::error::Result<()>) {
+        let mut reconnects = self.reconnects.lock().unwrap();
+        let Some(reconnect) = reconnects.get_mut(name) else {
+            return;
+        };
+        match result {
+            Ok(()) => {
+                info!("Tunnel '{}' restarted", name);
+                reconnect.restarted_at = Some(Instant::now());
+                reconnect.last_error = None;
+            }
+            Err(e) => {
+                warn!("Failed to restart tunnel '{}': {}", name, e);
+                reconnect.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Create and start the tunnel of an enabled network once an attempt is due
+    ///
+    /// A tunnel that was created is registered even if it failed to start, so
+    /// later attempts restart it like any other failed tunnel.
+    async fn create_tunnel(&self, name: &str, network: &NetworkConfig, policy: &SupervisorPolicy) {
+        let Some(attempt) = self.due_attempt(name, policy) else {
+            return;
+        };
+        info!("Creating tunnel '{}' (attempt {})", name, attempt);
+        let tunnel = match Tunnel::from_network_config(network) {
+            Ok(tunnel) => Arc::new(tunnel),
+            Err(e) => {
+                self.finish_attempt(name, Err(e));
+                return;
+            }
+        };
+        let result = tunnel.start().await;
+
+        // A client may have connected or disconnected the network meanwhile
+        let mut tunnels = self.tunnels.write().await;
+        if tunnels.contains_key(name) || self.disconnected.lock().unwrap().contains(name) {
+            drop(tunnels);
+            if tunnel.state().await.is_running() {
+                info!("Network '{}' changed during restart, stopping new tunnel", name);
+                if let Err(e) = tunnel.stop().await {
+                    warn!("Failed to stop tunnel '{}': {}", name, e);
+                }
+            }
+            return;
+        }
+        tunnels.insert(name.to_string(), tunnel);
+        drop(tunnels);
+        self.finish_attempt(name, result);
+    }
+
+    /// Schedule a restart of a network's tunnel, or run it once it is due
+    async fn reconnect(&self, name: &str, tunnel: &Tunnel, policy: &SupervisorPolicy) {
+        let attempt = self.due_attempt(name, policy);
+        tunnel.set_reconnecting().await;
+
+        let Some(attempt) = attempt else {
+            return;
+        };
+        info!("Restarting tunnel '{}' (attempt {})", name, attempt);
+        let result = tunnel.restart().await;
+
+        // The network may have been disconnected meanwhile; its tunnel must not
+        // be left running without being registered
+        let registered = self
+            .tunnels
+            .read()
+            .await
+            .get(name)
+            .is_some_and(|registered| std::ptr::eq(registered.as_ref(), tunnel));
+        if !registered {
+            if tunnel.state().await.is_running() {
+                info!("Network '{}' was disconnected during restart, stopping tunnel", name);
+                if let Err(e) = tunnel.stop().await {
+                    warn!("Failed to stop tunnel '{}': {}", name, e);
+                }
+            }
+            return;
+        }
+
+        self.finish_attempt(name, result);
+    }
+
+    /// Forget a network's reconnect progress once its tunnel is back to normal
+    ///
+    /// A restarted tunnel has recovered when a peer completes a handshake or it
+    /// stays up for the longest backoff; a pending restart is cancelled.
+    async fn note_active(&self, name: &str, tunnel: &Tunnel, policy: &SupervisorPolicy) {
+        let restarted_at = match self.reconnects.lock().unwrap().get(name) {
+            Some(reconnect) => reconnect.restarted_at,
+            None => return,
+        };
+        let recovered = match restarted_at {
+            Some(at) => at.elapsed() >= policy.max_backoff || tunnel.stats().await.healthy_peers > 0,
+            None => true,
+        };
+
+        if recovered && self.reconnects.lock().unwrap().remove(name).is_some() {
+            info!("Tunnel '{}' recovered", name);
+        }
+    }
+
+    /// Reconnect progress of a network for status output
+    fn reconnect_status(&self, network: &str) -> serde_json::Value {
+        let reconnects = self.reconnects.lock().unwrap();
+        let reconnect = reconnects.get(network).cloned().unwrap_or_default();
+        serde_json::json!({
+            "attempts": reconnect.attempts,
+            "next_attempt_secs": reconnect
+                .next_attempt
+                .map(|at| at.saturating_duration_since(Instant::now()).as_secs()),
+            "last_error": reconnect.last_error,
+        })
+    }
+
+    /// Get all registered tunnels
+    pub async fn tunnels(&self) -> Vec<(String, Arc<Tunnel>)> {
+        let tunnels = self.tunnels.read().await;
+        tunnels
+            .iter()
+            .map(|(name, tunnel)| (name.clone(), tunnel.clone()))
+            .collect()
+    }
+
     /// Get all tunnel states
     pub async fn get_all_states(&self) -> HashMap<String, String> {
         let tunnels = self.tunnels.read().await;
@@ -278,11 +866,42 @@ impl CommandHandler {
         // Remove from active tunnels
         let mut tunnels = self.tunnels.write().await;
         tunnels.remove(network);
+        drop(tunnels);
+        self.reconnects.lock().unwrap().remove(network);
+        self.disconnected.lock().unwrap().insert(network.to_string());
         
         Ok(())
     }
 }
 
+/// A change to a network's stored peer list
+enum PeerEdit {
+    /// Add a peer or replace the one with the same public key
+    Upsert(JsonPeerConfig),
+    /// Remove the peer with this base64 public key
+    Remove(String),
+}
+
+impl PeerEdit {
+    /// Apply the edit to a list of peers in any representation
+    fn apply<T>(
+        &self,
+        peers: &mut Vec<T>,
+        key_of: impl Fn(&T) -> &String,
+        convert: impl Fn(&JsonPeerConfig) -> T,
+    ) {
+        match self {
+            PeerEdit::Upsert(json) => {
+                match peers.iter_mut().find(|p| key_of(p) == &json.public_key) {
+                    Some(existing) => *existing = convert(json),
+                    None => peers.push(convert(json)),
+                }
+            }
+            PeerEdit::Remove(public_key) => peers.retain(|p| key_of(p) != public_key),
+        }
+    }
+}
+
 impl Default for CommandHandler {
     fn default() -> Self {
         Self::new()
@@ -292,7 +911,13 @@ impl Default for CommandHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::ControlAction;
+    use crate::config::{ControlAction, WireguardBackend};
+    use crate::error::{Result, WgAgentError};
+    use crate::platform::{Platform, PlatformInfo};
+    use crate::wireguard::{
+        DeviceConfig, DeviceIo, MemoryNetwork, MemoryTun, TunIo, UdpIo, WgDevice,
+    };
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_handler_creation() {
@@ -315,6 +940,543 @@ mod tests {
         assert!(response.error.is_some());
     }
 
+    #[tokio::test]
+    async fn test_handler_rotate_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("private.key");
+        crate::wireguard::PrivateKey::generate()
+            .save_to_file(&key_path)
+            .unwrap();
+
+        let mut config = Config::new();
+        config.add_network(
+            "default".to_string(),
+            NetworkConfig {
+                enable_wireguard: true,
+                interface: "wg0".to_string(),
+                mtu: 1420,
+                private_key_path: key_path.to_string_lossy().to_string(),
+                dns: vec![],
+                addresses: vec![],
+                listen_port: 0,
+                bind_address: None,
+                backend: WireguardBackend::Auto,
+                queues: 0,
+                peers: vec![],
+                http: None,
+            },
+        );
+
+        let handler = CommandHandler::new();
+        handler.load_config(config).await;
+
+        // Rotating twice must leave the file holding the last returned key
+        let mut last_key = String::new();
+        for i in 0..2 {
+            let request = ApiRequest::new(
+                format!("rotate-{}", i),
+                ControlAction::RotateKeys,
+                "default".to_string(),
+            );
+            let response = handler.handle_request(request).await;
+            assert!(response.success);
+            let data = response.data.unwrap();
+            assert_eq!(data["applied"], false);
+            assert_eq!(data["sessions_restarted"], false);
+            assert_ne!(data["public_key"].as_str().unwrap(), last_key);
+            last_key = data["public_key"].as_str().unwrap().to_string();
+        }
+
+        let on_disk = KeyPair::from_file(&key_path).unwrap();
+        assert_eq!(on_disk.public.to_base64(), last_key);
+    }
+
+    #[tokio::test]
+    async fn test_handler_rotate_keys_rolls_back_unsaved_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join("missing").join("private.key");
+        let mut config = Config::new();
+        config.add_network(
+            "default".to_string(),
+            NetworkConfig {
+                enable_wireguard: true,
+                interface: "wgrot0".to_string(),
+                mtu: 1420,
+                private_key_path: key_path.to_string_lossy().to_string(),
+                dns: vec![],
+                addresses: vec![],
+                listen_port: 0,
+                bind_address: None,
+                backend: WireguardBackend::Userspace,
+                queues: 1,
+                peers: vec![],
+                http: None,
+            },
+        );
+
+        let handler = CommandHandler::new();
+        handler.load_config(config).await;
+        let tunnel = Tunnel::with_device_io(
+            supervised_config("wgrot0"),
+            FakePlatform::boxed(),
+            Arc::new(MemoryIo::new()),
+        )
+        .unwrap();
+        tunnel.start().await.unwrap();
+        let tunnel = Arc::new(tunnel);
+        handler.register_tunnel("default".to_string(), tunnel.clone()).await;
+        let public_key = tunnel.public_key().await;
+
+        // The key file cannot be written, so the tunnel goes back to its old key
+        let request = ApiRequest::new(
+            "rotate-1".to_string(),
+            ControlAction::RotateKeys,
+            "default".to_string(),
+        );
+        assert!(!handler.handle_request(request).await.success);
+        assert_eq!(tunnel.public_key().await, public_key);
+        assert!(!key_path.exists());
+        tunnel.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handler_rejects_invalid_inline_config() {
+        let handler = CommandHandler::new();
+        let mut request = ApiRequest::new(
+            "test-1".to_string(),
+            ControlAction::Connect,
+            "inline".to_string(),
+        );
+        request.config = Some(serde_json::json!({
+            "interface": "wg0",
+            "mtu": 9000,
+            "privateKeyPath": "/etc/harmony-agent/private.key",
+            "peers": []
+        }));
+
+        let response = handler.handle_request(request).await;
+        assert!(!response.success);
+        assert!(matches!(response.error, Some(ApiError::ConfigError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_handler_restore_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("state.json");
+        let json: JsonNetworkConfig = serde_json::from_value(serde_json::json!({
+            "interface": "wg7",
+            "privateKeyPath": "/etc/harmony-agent/private.key",
+            "peers": []
+        }))
+        .unwrap();
+        let mut saved = HashMap::new();
+        saved.insert("inline".to_string(), json);
+        RuntimeState::new(&state_path).save(&saved).unwrap();
+
+        let handler = CommandHandler::new().with_state_file(state_path);
+        let mut config = Config::new();
+        let restored = handler.restore_state(&mut config).await.unwrap();
+
+        assert_eq!(restored, vec!["inline".to_string()]);
+        assert_eq!(config.get_network("inline").unwrap().interface, "wg7");
+    }
+
+    #[tokio::test]
+    async fn test_handler_peer_actions() {
+        let handler = CommandHandler::new();
+        let tunnel = Tunnel::new(TunnelConfig {
+            interface: "wg0".to_string(),
+            mtu: 1420,
+            dns_servers: vec![],
+            addresses: vec![],
+            listen_port: 0,
+            bind_address: None,
+            backend: WireguardBackend::Auto,
+            queues: 0,
+            keypair: KeyPair::generate(),
+            peers: vec![],
+        })
+        .unwrap();
+        handler.register_tunnel("default".to_string(), Arc::new(tunnel)).await;
+
+        let public_key = KeyPair::generate().public.to_base64();
+        let mut request = ApiRequest::new(
+            "add-1".to_string(),
+            ControlAction::AddPeer,
+            "default".to_string(),
+        );
+        request.config = Some(serde_json::json!({
+            "name": "site-a",
+            "publicKey": public_key,
+            "allowedIps": ["10.1.0.0/16"]
+        }));
+        assert!(handler.handle_request(request).await.success);
+
+        let request = ApiRequest::new(
+            "list-1".to_string(),
+            ControlAction::ListPeers,
+            "default".to_string(),
+        );
+        let response = handler.handle_request(request).await;
+        let peers = response.data.unwrap()["peers"].clone();
+        assert_eq!(peers.as_array().unwrap().len(), 1);
+        assert_eq!(peers[0]["name"], "site-a");
+        assert_eq!(peers[0]["allowed_ips"][0], "10.1.0.0/16");
+
+        let mut request = ApiRequest::new(
+            "remove-1".to_string(),
+            ControlAction::RemovePeer,
+            "default".to_string(),
+        );
+        request.config = Some(serde_json::json!({ "publicKey": public_key }));
+        assert!(handler.handle_request(request).await.success);
+
+        // Peer actions need a known network
+        let request = ApiRequest::new(
+            "list-2".to_string(),
+            ControlAction::ListPeers,
+            "missing".to_string(),
+        );
+        let response = handler.handle_request(request).await;
+        assert!(matches!(response.error, Some(ApiError::NetworkNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_handler_agent_status_and_shutdown() {
+        let handler = CommandHandler::new();
+        let request = ApiRequest::new(
+            "test-1".to_string(),
+            ControlAction::AgentStatus,
+            "default".to_string(),
+        );
+        let response = handler.handle_request(request).await;
+        assert!(response.success);
+        let data = response.data.unwrap();
+        assert_eq!(data["version"], crate::VERSION);
+        assert!(data["networks"].as_object().unwrap().is_empty());
+
+        let request = ApiRequest::new(
+            "test-2".to_string(),
+            ControlAction::Shutdown,
+            "default".to_string(),
+        );
+        assert!(handler.handle_request(request).await.success);
+        tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            handler.shutdown_requested(),
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Platform that accepts every configuration change but has no TUN devices
+    struct FakePlatform {
+        info: PlatformInfo,
+    }
+
+    impl FakePlatform {
+        fn boxed() -> Box<dyn Platform> {
+            Box::new(Self {
+                info: PlatformInfo::new(),
+            })
+        }
+    }
+
+    impl Platform for FakePlatform {
+        fn info(&self) -> &PlatformInfo {
+            &self.info
+        }
+
+        fn create_interface(&self, _name: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn destroy_interface(&self, _name: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_mtu(&self, _interface: &str, _mtu: u16) -> Result<()> {
+            Ok(())
+        }
+
+        fn interface_up(&self, _interface: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn interface_down(&self, _interface: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_address(&self, _interface: &str, _address: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn configure_routes(&self, _interface: &str, _routes: &[String]) -> Result<()> {
+            Ok(())
+        }
+
+        fn remove_routes(&self, _interface: &str, _routes: &[String]) -> Result<()> {
+            Ok(())
+        }
+
+        fn configure_dns(&self, _interface: &str, _dns: &[String]) -> Result<()> {
+            Ok(())
+        }
+
+        fn remove_dns(&self, _interface: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn check_capabilities(&self) -> Result<Vec<String>> {
+            Ok(vec![])
+        }
+
+        fn create_tun_device(&self, name: &str, _mtu: u16) -> Result<tun::platform::Device> {
+            Err(WgAgentError::TunDevice(format!(
+                "Fake platform cannot create {}",
+                name
+            )))
+        }
+    }
+
+    /// Gives every device a fresh in-memory TUN device and a socket on the
+    /// given network
+    struct MemoryIo(MemoryNetwork);
+
+    impl MemoryIo {
+        fn new() -> Self {
+            Self(MemoryNetwork::new())
+        }
+    }
+
+    impl DeviceIo for MemoryIo {
+        fn open(&self, _config: &DeviceConfig) -> Result<(Arc<dyn TunIo>, Arc<dyn UdpIo>)> {
+            let (tun, _) = MemoryTun::pair();
+            let socket = self.0.bind("192.0.2.1:51820".parse().unwrap());
+            Ok((Arc::new(tun), Arc::new(socket)))
+        }
+    }
+
+    fn supervised_config(interface: &str) -> TunnelConfig {
+        TunnelConfig {
+            interface: interface.to_string(),
+            mtu: 1420,
+            dns_servers: vec![],
+            addresses: vec![],
+            listen_port: 0,
+            bind_address: None,
+            backend: WireguardBackend::Userspace,
+            queues: 1,
+            keypair: KeyPair::generate(),
+            peers: vec![],
+        }
+    }
+
+    fn test_policy() -> SupervisorPolicy {
+        SupervisorPolicy {
+            interval: Duration::from_millis(10),
+            handshake_timeout: None,
+            initial_backoff: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(20),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handler_supervisor_retries_failed_tunnel() {
+        let handler = CommandHandler::new();
+        // The platform cannot create TUN devices, so every start fails
+        let tunnel = Arc::new(
+            Tunnel::with_platform(supervised_config("wgsup0"), FakePlatform::boxed()).unwrap(),
+        );
+        assert!(tunnel.start().await.is_err());
+        handler.register_tunnel("flaky".to_string(), tunnel.clone()).await;
+        let policy = test_policy();
+
+        // The first pass only schedules the restart
+        handler.supervise(&policy).await;
+        assert_eq!(tunnel.state().await, TunnelState::Reconnecting);
+
+        // Once due, the restart runs and its failure is reported in status
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        handler.supervise(&policy).await;
+        assert_eq!(tunnel.state().await, TunnelState::Error);
+
+        let request = ApiRequest::new(
+            "status-1".to_string(),
+            ControlAction::Status,
+            "flaky".to_string(),
+        );
+        let data = handler.handle_request(request).await.data.unwrap();
+        assert_eq!(data["state"], "error");
+        assert_eq!(data["reconnect"]["attempts"], 1);
+        assert!(data["reconnect"]["last_error"].is_string());
+
+        handler.supervise(&policy).await;
+        assert_eq!(tunnel.state().await, TunnelState::Reconnecting);
+    }
+
+    #[tokio::test]
+    async fn test_handler_supervisor_retries_tunnel_creation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::new();
+        config.add_network(
+            "boot".to_string(),
+            NetworkConfig {
+                enable_wireguard: true,
+                interface: "wgsup2".to_string(),
+                mtu: 1420,
+                // The key file is missing, so the tunnel cannot be created
+                private_key_path: dir.path().join("private.key").to_string_lossy().to_string(),
+                dns: vec![],
+                addresses: vec![],
+                listen_port: 0,
+                bind_address: None,
+                backend: WireguardBackend::Userspace,
+                queues: 1,
+                peers: vec![],
+                http: None,
+            },
+        );
+        let handler = CommandHandler::new();
+        handler.load_config(config).await;
+        let policy = test_policy();
+        let attempts = |handler: &CommandHandler| {
+            handler.reconnects.lock().unwrap().get("boot").map(|r| (r.attempts, r.last_error.clone()))
+        };
+
+        // Creation is retried on the restart schedule and its failure recorded
+        handler.supervise(&policy).await;
+        assert_eq!(attempts(&handler), Some((0, None)));
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        handler.supervise(&policy).await;
+        let (count, last_error) = attempts(&handler).unwrap();
+        assert_eq!(count, 1);
+        assert!(last_error.unwrap().contains("private.key"));
+        assert!(handler.tunnels().await.is_empty());
+
+        // Disconnecting the network stops the retries
+        let request = ApiRequest::new(
+            "disconnect-1".to_string(),
+            ControlAction::Disconnect,
+            "boot".to_string(),
+        );
+        assert!(handler.handle_request(request).await.success);
+        handler.supervise(&policy).await;
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        handler.supervise(&policy).await;
+        assert_eq!(attempts(&handler), None);
+    }
+
+    #[tokio::test]
+    async fn test_handler_reconnect_after_disconnect_stops_tunnel() {
+        let handler = CommandHandler::new();
+        let mut config = supervised_config("wgsup1");
+        config.peers = vec![PeerConfig::new("peer".to_string(), KeyPair::generate().public)];
+        let tunnel = Tunnel::with_device_io(
+            config,
+            FakePlatform::boxed(),
+            Arc::new(MemoryIo::new()),
+        )
+        .unwrap();
+        let policy = test_policy();
+
+        // A restart that was due when the network got disconnected
+        handler.reconnects.lock().unwrap().insert(
+            "gone".to_string(),
+            Reconnect {
+                next_attempt: Some(Instant::now()),
+                ..Reconnect::default()
+            },
+        );
+        handler.reconnect("gone", &tunnel, &policy).await;
+
+        // A tunnel restarted after its network was disconnected is stopped
+        // again rather than left running unregistered
+        assert!(handler.tunnels().await.is_empty());
+        assert_eq!(tunnel.state().await, TunnelState::Stopped);
+    }
+
+    /// Start a device for `keys` at `addr` that sends one packet to the tunnel
+    async fn roaming_peer(
+        network: &MemoryNetwork,
+        keys: &KeyPair,
+        tunnel_keys: &KeyPair,
+        addr: &str,
+    ) -> WgDevice {
+        let mut tunnel_peer = PeerConfig::new("tunnel".to_string(), tunnel_keys.public.clone());
+        tunnel_peer.endpoint = Some("192.0.2.1:51820".parse().unwrap());
+        tunnel_peer.allowed_ips = vec!["10.0.0.1/32".to_string()];
+        let (tun, handle) = MemoryTun::pair();
+        let device = WgDevice::with_io(
+            DeviceConfig {
+                interface: "wg-roamer".to_string(),
+                mtu: 1420,
+                keypair: keys.clone(),
+                listen_port: 0,
+                bind_address: None,
+                queues: 1,
+                peers: vec![tunnel_peer],
+            },
+            Arc::new(tun),
+            Arc::new(network.bind(addr.parse().unwrap())),
+        )
+        .await
+        .unwrap();
+
+        // An IPv4 header from 10.0.0.2 to 10.0.0.1 is enough to start a handshake
+        let mut packet = vec![0x45, 0, 0, 20, 0, 0, 0, 0, 64, 17, 0, 0];
+        packet.extend_from_slice(&[10, 0, 0, 2, 10, 0, 0, 1]);
+        handle.send(&packet);
+        device
+    }
+
+    /// Wait for `list_peers` to report `endpoint` for the only peer
+    async fn wait_for_endpoint(handler: &CommandHandler, endpoint: &str) {
+        for _ in 0..50 {
+            let request = ApiRequest::new(
+                "peers-1".to_string(),
+                ControlAction::ListPeers,
+                "roaming".to_string(),
+            );
+            let data = handler.handle_request(request).await.data.unwrap();
+            if data["peers"][0]["endpoint"] == endpoint {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("list_peers never reported endpoint {}", endpoint);
+    }
+
+    #[tokio::test]
+    async fn test_handler_list_peers_reports_roamed_endpoint() {
+        let handler = CommandHandler::new();
+        let network = MemoryNetwork::new();
+        let roamer_keys = KeyPair::generate();
+        let mut config = supervised_config("wgroam0");
+        let mut roamer = PeerConfig::new("roamer".to_string(), roamer_keys.public.clone());
+        roamer.allowed_ips = vec!["10.0.0.2/32".to_string()];
+        config.peers = vec![roamer];
+        let tunnel_keys = config.keypair.clone();
+        let tunnel = Tunnel::with_device_io(
+            config,
+            FakePlatform::boxed(),
+            Arc::new(MemoryIo(network.clone())),
+        )
+        .unwrap();
+        tunnel.start().await.unwrap();
+        handler.register_tunnel("roaming".to_string(), Arc::new(tunnel)).await;
+
+        // The peer has no configured endpoint until it reaches the tunnel
+        let first = roaming_peer(&network, &roamer_keys, &tunnel_keys, "192.0.2.2:40000").await;
+        wait_for_endpoint(&handler, "192.0.2.2:40000").await;
+
+        // The same peer showing up from a new address is followed
+        first.stop().await.unwrap();
+        let second = roaming_peer(&network, &roamer_keys, &tunnel_keys, "192.0.2.3:40001").await;
+        wait_for_endpoint(&handler, "192.0.2.3:40001").await;
+        second.stop().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_handler_status_not_found() {
         let handler = CommandHandler::new();
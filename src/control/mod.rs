@@ -4,12 +4,18 @@
 //! main applications via Unix sockets (Linux/macOS) or Named Pipes (Windows).
 
 mod api;
+mod client;
 mod handler;
 mod server;
+mod state;
+mod supervisor;
 
 pub use api::{ApiRequest, ApiResponse, ApiError};
+pub use client::{ControlClient, DEFAULT_CLIENT_TIMEOUT};
 pub use handler::CommandHandler;
 pub use server::{ControlServer, DEFAULT_SOCKET_PATH};
+pub use state::{RuntimeState, DEFAULT_STATE_PATH};
+pub use supervisor::{SupervisorPolicy, DEFAULT_SUPERVISE_INTERVAL};
 
 #[cfg(windows)]
 pub use server::DEFAULT_PIPE_NAME;
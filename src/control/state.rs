@@ -0,0 +1,114 @@
+//! Runtime state persistence
+//!
+//! Networks supplied inline through the control API are not part of the
+//! TOML configuration. This module saves them to a state file so they can be
+//! restored when the agent restarts.
+
+use crate::config::JsonNetworkConfig;
+use crate::error::{Result, WgAgentError};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Default runtime state file path
+pub const DEFAULT_STATE_PATH: &str = "/var/lib/harmony-agent/state.json";
+
+/// Runtime state file holding inline network configurations
+#[derive(Debug, Clone)]
+pub struct RuntimeState {
+    path: PathBuf,
+}
+
+impl RuntimeState {
+    /// Create a state store backed by `path`
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Get the state file path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load saved networks (empty if the file does not exist)
+    pub fn load(&self) -> Result<HashMap<String, JsonNetworkConfig>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => {
+                return Err(WgAgentError::Config(format!(
+                    "Failed to read state file {:?}: {}",
+                    self.path, e
+                )))
+            }
+        };
+
+        serde_json::from_str(&content).map_err(|e| {
+            WgAgentError::Serialization(format!("Invalid state file {:?}: {}", self.path, e))
+        })
+    }
+
+    /// Atomically replace the state file (0600, it may hold preshared keys)
+    pub fn save(&self, networks: &HashMap<String, JsonNetworkConfig>) -> Result<()> {
+        let content = serde_json::to_string_pretty(networks).map_err(|e| {
+            WgAgentError::Serialization(format!("Failed to serialize state: {}", e))
+        })?;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| {
+                WgAgentError::Config(format!("Failed to create state directory {:?}: {}", dir, e))
+            })?;
+        }
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        let _ = fs::remove_file(&tmp_path);
+
+        let write = || -> std::io::Result<()> {
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&tmp_path)?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, &self.path)
+        };
+
+        write().map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+            WgAgentError::Config(format!("Failed to write state file {:?}: {}", self.path, e))
+        })?;
+
+        debug!("Saved {} runtime networks to {:?}", networks.len(), self.path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_state_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = RuntimeState::new(dir.path().join("nested").join("state.json"));
+        assert!(state.load().unwrap().is_empty());
+
+        let network: JsonNetworkConfig = serde_json::from_str(
+            r#"{"privateKeyPath": "/etc/harmony-agent/private.key", "peers": []}"#,
+        )
+        .unwrap();
+        let mut networks = HashMap::new();
+        networks.insert("site-a".to_string(), network);
+        state.save(&networks).unwrap();
+
+        let loaded = state.load().unwrap();
+        assert_eq!(loaded["site-a"].private_key_path, "/etc/harmony-agent/private.key");
+        let mode = fs::metadata(state.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,104 @@
+//! Tunnel supervision policy
+//!
+//! The `CommandHandler` supervisor restarts tunnels that failed or stopped
+//! completing handshakes. This module holds its settings, the backoff between
+//! restart attempts and the reconnect progress tracked per network.
+
+use crate::config::SupervisorConfig;
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+/// Default interval between supervision passes
+pub const DEFAULT_SUPERVISE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// When and how often the supervisor restarts tunnels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SupervisorPolicy {
+    /// Time between supervision passes
+    pub interval: Duration,
+    /// Restart tunnels without a handshake for this long (None = only failed tunnels)
+    pub handshake_timeout: Option<Duration>,
+    /// Delay before the first restart attempt
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between restart attempts
+    pub max_backoff: Duration,
+}
+
+impl SupervisorPolicy {
+    /// Delay before the restart attempt following `attempts` failed ones
+    ///
+    /// The delay doubles with every attempt up to `max_backoff`, then up to half
+    /// of it is taken off at random so agents that lost the same network do not
+    /// retry in step.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempts))
+            .min(self.max_backoff);
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+impl From<&SupervisorConfig> for SupervisorPolicy {
+    fn from(config: &SupervisorConfig) -> Self {
+        Self {
+            interval: DEFAULT_SUPERVISE_INTERVAL,
+            handshake_timeout: (config.handshake_timeout_secs > 0)
+                .then(|| Duration::from_secs(config.handshake_timeout_secs)),
+            initial_backoff: Duration::from_secs(config.initial_backoff_secs),
+            max_backoff: Duration::from_secs(config.max_backoff_secs),
+        }
+    }
+}
+
+impl Default for SupervisorPolicy {
+    fn default() -> Self {
+        Self::from(&SupervisorConfig::default())
+    }
+}
+
+/// Reconnect progress of one network
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Reconnect {
+    /// Restart attempts since the tunnel was last healthy
+    pub attempts: u32,
+    /// When the next attempt is due (None = not scheduled yet)
+    pub next_attempt: Option<Instant>,
+    /// When the last attempt brought the tunnel back up
+    pub restarted_at: Option<Instant>,
+    /// Error from the last failed attempt
+    pub last_error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = SupervisorPolicy {
+            interval: DEFAULT_SUPERVISE_INTERVAL,
+            handshake_timeout: None,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        };
+
+        for (attempts, full) in [(0, 1), (1, 2), (3, 8), (6, 60), (40, 60)] {
+            let full = Duration::from_secs(full);
+            let delay = policy.backoff(attempts);
+            assert!(delay >= full / 2 && delay <= full, "{:?} for {}", delay, attempts);
+        }
+    }
+
+    #[test]
+    fn test_policy_from_config() {
+        let mut config = SupervisorConfig::default();
+        assert_eq!(
+            SupervisorPolicy::from(&config).handshake_timeout,
+            Some(Duration::from_secs(300))
+        );
+
+        config.handshake_timeout_secs = 0;
+        assert_eq!(SupervisorPolicy::from(&config).handshake_timeout, None);
+    }
+}
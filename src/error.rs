@@ -17,6 +17,10 @@ pub enum WgAgentError {
     #[error("Platform error: {0}")]
     Platform(String),
 
+    /// Structured errors from interface, address and route operations
+    #[error("Platform error: {0}")]
+    PlatformOperation(#[from] PlatformError),
+
     /// WireGuard protocol errors
     #[error("WireGuard error: {0}")]
     WireGuard(String),
@@ -74,6 +78,32 @@ pub enum WgAgentError {
     Validation(String),
 }
 
+/// Failure of a link, address or route operation
+#[derive(Error, Debug)]
+pub enum PlatformError {
+    /// The network interface does not exist
+    #[error("interface {0} not found")]
+    InterfaceNotFound(String),
+
+    /// The address or route is already configured
+    #[error("{0}: already exists")]
+    AlreadyExists(String),
+
+    /// The caller lacks CAP_NET_ADMIN
+    #[error("{0}: permission denied (CAP_NET_ADMIN required)")]
+    PermissionDenied(String),
+
+    /// The kernel rejected the request
+    #[error("{operation}: {source}")]
+    Netlink {
+        /// Operation that failed (e.g. "add route 10.0.0.0/24 via wg0")
+        operation: String,
+        /// Error reported by the kernel
+        #[source]
+        source: std::io::Error,
+    },
+}
+
 /// Result type alias using WgAgentError
 pub type Result<T> = std::result::Result<T, WgAgentError>;
 
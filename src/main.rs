@@ -11,8 +11,11 @@ use harmony_agent::{
     APP_NAME, VERSION,
     config::Config,
     service::{create_service, ServiceMode},
-    monitoring::Monitor,
-    control::{CommandHandler, ControlServer, DEFAULT_SOCKET_PATH},
+    monitoring::{HealthStatus, Monitor, MonitorBridge},
+    control::{
+        CommandHandler, ControlClient, ControlServer, SupervisorPolicy, DEFAULT_SOCKET_PATH,
+        DEFAULT_STATE_PATH,
+    },
 };
 use std::sync::Arc;
 use std::path::PathBuf;
@@ -24,6 +27,10 @@ use axum::{
 };
 use tokio::signal;
 
+mod cli;
+
+use cli::{EXIT_ERROR, EXIT_OK};
+
 /// Cross-platform WireGuard network agent
 #[derive(Parser, Debug)]
 #[command(name = APP_NAME, version = VERSION, about, long_about = None)]
@@ -41,6 +48,14 @@ struct Cli {
     )]
     config: String,
 
+    /// Control socket path
+    #[arg(long, global = true, default_value = DEFAULT_SOCKET_PATH)]
+    socket: PathBuf,
+
+    /// Print machine-readable JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -51,51 +66,225 @@ enum Commands {
     Start,
 
     /// Stop the agent daemon
-    Stop,
+    Stop {
+        /// Seconds to wait for the agent to exit
+        #[arg(long, default_value_t = 30)]
+        timeout: u64,
+    },
 
     /// Check agent status
+    ///
+    /// Exits 0 when all networks are healthy, 2 when any network is down or
+    /// has unhealthy peers, and 3 when the agent cannot be reached.
     Status,
 
+    /// Connect a network
+    Connect {
+        /// Network name
+        network: String,
+
+        /// JSON network configuration to send inline (same fields as the API)
+        #[arg(long, value_name = "FILE")]
+        network_config: Option<PathBuf>,
+    },
+
+    /// Disconnect a network
+    Disconnect {
+        /// Network name
+        network: String,
+    },
+
+    /// Reload a network's configuration
+    Reload {
+        /// Network name
+        network: String,
+
+        /// JSON network configuration to send inline (same fields as the API)
+        #[arg(long, value_name = "FILE")]
+        network_config: Option<PathBuf>,
+    },
+
+    /// Generate a new key pair for a network
+    RotateKeys {
+        /// Network name
+        network: String,
+    },
+
+    /// Manage the peers of a connected network
+    Peers {
+        #[command(subcommand)]
+        command: PeersCommand,
+    },
+
+    /// Generate and derive WireGuard keys (compatible with `wg`)
+    Key {
+        #[command(subcommand)]
+        command: KeyCommand,
+    },
+
+    /// Convert between wg-quick files and agent configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
     /// Show version information
     Version,
 }
 
+#[derive(Subcommand, Debug)]
+enum PeersCommand {
+    /// List peers with their statistics
+    List {
+        /// Network name
+        network: String,
+    },
+
+    /// Add a peer
+    Add {
+        /// Network name
+        network: String,
+
+        /// Peer name
+        #[arg(long)]
+        name: String,
+
+        /// Base64 public key
+        #[arg(long)]
+        public_key: String,
+
+        /// Endpoint as host:port (omit to learn it from inbound packets)
+        #[arg(long)]
+        endpoint: Option<String>,
+
+        /// Allowed IP ranges, comma separated
+        #[arg(long, required = true, value_delimiter = ',')]
+        allowed_ips: Vec<String>,
+
+        /// Persistent keepalive in seconds
+        #[arg(long)]
+        keepalive: Option<u16>,
+
+        /// Preshared key file on the agent host
+        #[arg(long)]
+        preshared_key_path: Option<String>,
+    },
+
+    /// Remove a peer
+    Remove {
+        /// Network name
+        network: String,
+
+        /// Base64 public key of the peer
+        #[arg(long)]
+        public_key: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum KeyCommand {
+    /// Generate a private key
+    Genkey {
+        /// Write the key to this file (0600) instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+
+        /// Overwrite an existing key file
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Print the public key for a private key read from a file or stdin
+    Pubkey {
+        /// Private key file (defaults to stdin)
+        file: Option<PathBuf>,
+    },
+
+    /// Generate a preshared key
+    Genpsk {
+        /// Write the key to this file (0600) instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+
+        /// Overwrite an existing key file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Import a wg-quick .conf file and print it as a TOML network section
+    Import {
+        /// wg-quick configuration file
+        file: PathBuf,
+
+        /// Network name for the imported configuration
+        #[arg(long)]
+        network: String,
+
+        /// Directory for the private and preshared key files
+        #[arg(long, default_value = "/etc/harmony-agent")]
+        key_dir: PathBuf,
+
+        /// Overwrite existing key files
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Print a configured network as a wg-quick .conf file
+    Export {
+        /// Network name
+        network: String,
+    },
+}
+
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
 
-    // Initialize logging
-    init_logging(cli.verbose);
+    // Initialize logging (client commands only log warnings unless verbose)
+    let default_level = match (cli.verbose, &cli.command) {
+        (true, _) => "debug",
+        (false, Commands::Start) => "info",
+        (false, _) => "warn",
+    };
+    init_logging(default_level);
 
     info!("Starting {} v{}", APP_NAME, VERSION);
 
     // Execute command
-    if let Err(e) = run(cli).await {
-        error!("Error: {}", e);
-        std::process::exit(1);
+    match run(cli).await {
+        Ok(EXIT_OK) => {}
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            error!("Error: {}", e);
+            eprintln!("Error: {}", e);
+            std::process::exit(EXIT_ERROR);
+        }
     }
 }
 
-/// Initialize structured logging with tracing
-fn init_logging(verbose: bool) {
-    let filter = if verbose {
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("debug"))
-    } else {
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))
-    };
+/// Initialize structured logging with tracing (to stderr, keeping stdout for command output)
+fn init_logging(default_level: &str) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_level));
 
     tracing_subscriber::registry()
         .with(filter)
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .init();
 }
 
-/// Run the CLI command
-async fn run(cli: Cli) -> anyhow::Result<()> {
+/// Run the CLI command, returning the process exit code
+async fn run(cli: Cli) -> anyhow::Result<i32> {
+    let client = ControlClient::new(&cli.socket);
+
     match cli.command {
         Commands::Start => {
             info!("Starting agent with config: {}", cli.config);
-            let config = Config::from_file(&cli.config)?;
+            let mut config = Config::from_file(&cli.config)?;
+            harmony_agent::platform::configure(&config.platform);
             let mode = ServiceMode::detect();
             info!("Service mode: {:?}", mode);
             let mut service = create_service(mode);
@@ -104,8 +293,17 @@ async fn run(cli: Cli) -> anyhow::Result<()> {
             service.notify_ready()?;
             info!("Service started successfully");
             
-            // Create command handler and load configuration
-            let handler = Arc::new(CommandHandler::new());
+            // Create command handler, restore networks added through the API and load configuration
+            let handler = Arc::new(
+                CommandHandler::new().with_state_file(PathBuf::from(DEFAULT_STATE_PATH)),
+            );
+            match handler.restore_state(&mut config).await {
+                Ok(restored) if !restored.is_empty() => {
+                    info!("Restored runtime networks: {}", restored.join(", "));
+                }
+                Ok(_) => {}
+                Err(e) => error!("Failed to restore runtime state: {}", e),
+            }
             handler.load_config(config.clone()).await;
             
             // Auto-start tunnels for networks with enable_wireguard = true
@@ -120,13 +318,21 @@ async fn run(cli: Cli) -> anyhow::Result<()> {
                             match tunnel.start().await {
                                 Ok(()) => {
                                     info!("Tunnel '{}' started successfully", name);
-                                    // Register tunnel with handler
-                                    handler.register_tunnel(name.clone(), Arc::new(tunnel)).await;
+                                }
+                                Err(e) if config.supervisor.enabled => {
+                                    error!("Failed to start tunnel '{}', will retry: {}", name, e);
                                 }
                                 Err(e) => {
                                     error!("Failed to start tunnel '{}': {}", name, e);
+                                    continue;
                                 }
                             }
+                            // Register tunnel with handler; the supervisor retries failed ones
+                            handler.register_tunnel(name.clone(), Arc::new(tunnel)).await;
+                        }
+                        Err(e) if config.supervisor.enabled => {
+                            // The supervisor creates the tunnel once it can
+                            error!("Failed to create tunnel '{}', will retry: {}", name, e);
                         }
                         Err(e) => {
                             error!("Failed to create tunnel '{}': {}", name, e);
@@ -136,10 +342,15 @@ async fn run(cli: Cli) -> anyhow::Result<()> {
             }
             
             let active_count = handler.list_networks().await.len();
-            info!("Started {} WireGuard tunnel(s)", active_count);
+            info!("Managing {} WireGuard tunnel(s)", active_count);
+
+            // Restart tunnels that fail or stop completing handshakes
+            let supervisor_handle = config.supervisor.enabled.then(|| {
+                handler.spawn_supervisor(SupervisorPolicy::from(&config.supervisor))
+            });
             
             // Create control server
-            let socket_path = PathBuf::from(DEFAULT_SOCKET_PATH);
+            let socket_path = cli.socket.clone();
             let control_server = Arc::new(ControlServer::new(socket_path.clone(), handler.clone()));
             
             // Spawn control server task
@@ -155,8 +366,9 @@ async fn run(cli: Cli) -> anyhow::Result<()> {
                 })
             };
             
-            // Start HTTP server for metrics and health endpoints
+            // Start HTTP server for metrics and health endpoints, fed from the live tunnels
             let monitor = Arc::new(Monitor::new());
+            let bridge_handle = MonitorBridge::new(monitor.clone(), handler.clone()).spawn();
             let app = create_http_server(monitor);
             
             let addr = "127.0.0.1:9090";
@@ -167,18 +379,17 @@ async fn run(cli: Cli) -> anyhow::Result<()> {
             
             // Run HTTP server with graceful shutdown
             axum::serve(listener, app)
-                .with_graceful_shutdown(shutdown_signal())
+                .with_graceful_shutdown(shutdown_signal(handler.clone()))
                 .await?;
             
             info!("Shutting down agent");
             
-            // Shutdown control server
-            if let Err(e) = control_server.shutdown().await {
-                error!("Failed to shutdown control server: {}", e);
-            }
-            
-            // Abort control server task
+            // Stop accepting control requests and abort the monitoring task
             control_handle.abort();
+            bridge_handle.abort();
+            if let Some(handle) = supervisor_handle {
+                handle.abort();
+            }
             
             // Stop all tunnels
             for network in handler.list_networks().await {
@@ -188,37 +399,87 @@ async fn run(cli: Cli) -> anyhow::Result<()> {
                 }
             }
             
+            // Remove the socket last: `stop` clients wait for it to disappear
+            if let Err(e) = control_server.shutdown().await {
+                error!("Failed to shutdown control server: {}", e);
+            }
+            
             service.stop()?;
-            Ok(())
+            Ok(EXIT_OK)
         },
-        Commands::Stop => {
-            info!("Stopping agent");
-            // To be implemented with proper process management
-            Ok(())
+        Commands::Stop { timeout } => Ok(cli::stop_agent(&client, cli.json, timeout).await),
+        Commands::Status => Ok(cli::agent_status(&client, cli.json).await),
+        Commands::Connect { network, network_config } => {
+            Ok(cli::connect(&client, cli.json, &network, network_config.as_deref()).await)
         },
-        Commands::Status => {
-            info!("Checking agent status");
-            // To be implemented with health checks
-            println!("Agent status: Not yet implemented");
-            Ok(())
+        Commands::Disconnect { network } => Ok(cli::disconnect(&client, cli.json, &network).await),
+        Commands::Reload { network, network_config } => {
+            Ok(cli::reload(&client, cli.json, &network, network_config.as_deref()).await)
         },
+        Commands::RotateKeys { network } => Ok(cli::rotate_keys(&client, cli.json, &network).await),
+        Commands::Peers { command } => Ok(match command {
+            PeersCommand::List { network } => cli::list_peers(&client, cli.json, &network).await,
+            PeersCommand::Add {
+                network,
+                name,
+                public_key,
+                endpoint,
+                allowed_ips,
+                keepalive,
+                preshared_key_path,
+            } => {
+                let peer = cli::NewPeer {
+                    name: &name,
+                    public_key: &public_key,
+                    endpoint: endpoint.as_deref(),
+                    allowed_ips: &allowed_ips,
+                    keepalive,
+                    preshared_key_path: preshared_key_path.as_deref(),
+                };
+                cli::add_peer(&client, cli.json, &network, peer).await
+            },
+            PeersCommand::Remove { network, public_key } => {
+                cli::remove_peer(&client, cli.json, &network, &public_key).await
+            },
+        }),
+        Commands::Key { command } => Ok(match command {
+            KeyCommand::Genkey { out, force } => cli::genkey(out.as_deref(), force),
+            KeyCommand::Pubkey { file } => cli::pubkey(file.as_deref()),
+            KeyCommand::Genpsk { out, force } => cli::genpsk(out.as_deref(), force),
+        }),
+        Commands::Config { command } => Ok(match command {
+            ConfigCommand::Import { file, network, key_dir, force } => {
+                cli::import_wgquick(&file, &network, &key_dir, force)
+            },
+            ConfigCommand::Export { network } => cli::export_wgquick(&cli.config, &network),
+        }),
         Commands::Version => {
             println!("{} v{}", APP_NAME, VERSION);
-            Ok(())
+            Ok(EXIT_OK)
         },
     }
 }
 
 /// Create HTTP server with routes
 fn create_http_server(monitor: Arc<Monitor>) -> Router {
+    let health_monitor = monitor.clone();
     Router::new()
-        .route("/healthz", get(healthz))
+        .route("/healthz", get(move || healthz(health_monitor.clone())))
         .route("/metrics", get(move || metrics(monitor.clone())))
 }
 
-/// Health check endpoint
-async fn healthz() -> impl IntoResponse {
-    (StatusCode::OK, "OK")
+/// Health check endpoint (503 when any network is unhealthy)
+async fn healthz(monitor: Arc<Monitor>) -> impl IntoResponse {
+    match monitor.health_check() {
+        Ok(health) if health.status == HealthStatus::Unhealthy => {
+            (StatusCode::SERVICE_UNAVAILABLE, health.details)
+        }
+        Ok(health) if health.status == HealthStatus::Degraded => {
+            (StatusCode::OK, format!("DEGRADED: {}", health.details))
+        }
+        Ok(_) => (StatusCode::OK, "OK".to_string()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
 }
 
 /// Metrics endpoint (Prometheus format)
@@ -269,8 +530,8 @@ async fn metrics(monitor: Arc<Monitor>) -> impl IntoResponse {
     (StatusCode::OK, output)
 }
 
-/// Wait for shutdown signal (Ctrl+C or SIGTERM)
-async fn shutdown_signal() {
+/// Wait for shutdown signal (Ctrl+C, SIGTERM or a `shutdown` control request)
+async fn shutdown_signal(handler: Arc<CommandHandler>) {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -295,5 +556,8 @@ async fn shutdown_signal() {
         _ = terminate => {
             info!("Received SIGTERM signal");
         },
+        _ = handler.shutdown_requested() => {
+            info!("Received shutdown request");
+        },
     }
 }
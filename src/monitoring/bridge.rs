@@ -0,0 +1,209 @@
+//! Bridge between running tunnels and the monitor
+//!
+//! This module periodically polls the tunnels registered with the
+//! `CommandHandler` and feeds their state, traffic, peer and handshake
+//! statistics into a `Monitor`.
+
+use super::{ConnectionState, Monitor};
+use crate::control::CommandHandler;
+use crate::wireguard::TunnelState;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Default interval between tunnel polls
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Handshake counters last seen for a network
+#[derive(Debug, Clone, Copy, Default)]
+struct HandshakeCounts {
+    attempts: u64,
+    successes: u64,
+}
+
+/// Polls tunnels and updates the monitor
+pub struct MonitorBridge {
+    monitor: Arc<Monitor>,
+    handler: Arc<CommandHandler>,
+    interval: Duration,
+    handshakes: Mutex<HashMap<String, HandshakeCounts>>,
+}
+
+impl MonitorBridge {
+    /// Create a new bridge
+    pub fn new(monitor: Arc<Monitor>, handler: Arc<CommandHandler>) -> Self {
+        Self {
+            monitor,
+            handler,
+            interval: DEFAULT_POLL_INTERVAL,
+            handshakes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set the poll interval
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Spawn the polling task
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                self.poll().await;
+            }
+        })
+    }
+
+    /// Poll every tunnel once and update the monitor
+    pub async fn poll(&self) {
+        let tunnels = self.handler.tunnels().await;
+        let names: HashSet<&String> = tunnels.iter().map(|(name, _)| name).collect();
+
+        // Forget networks that were disconnected through the API
+        for name in self.monitor.get_all_stats().into_keys() {
+            if !names.contains(&name) {
+                self.monitor.unregister_network(&name);
+                self.handshakes.lock().unwrap().remove(&name);
+            }
+        }
+
+        for (name, tunnel) in &tunnels {
+            let previous = match self.monitor.get_stats(name) {
+                Some(stats) => stats.state,
+                None => {
+                    self.monitor.register_network(name.clone());
+                    ConnectionState::Disconnected
+                }
+            };
+
+            let stats = tunnel.stats().await;
+            let state = connection_state(stats.state);
+            if state != previous {
+                self.monitor.update_state(name, state);
+            }
+            self.monitor
+                .update_traffic(name, stats.total_tx_bytes, stats.total_rx_bytes);
+            self.monitor.update_peers(
+                name,
+                stats.total_peers,
+                stats.active_peers,
+                stats.healthy_peers,
+            );
+            self.monitor.update_batch_sizes(
+                name,
+                stats.udp_batches.recv_batch_size(),
+                stats.udp_batches.send_batch_size(),
+            );
+            self.monitor.update_rate_limited(name, stats.handshakes_rate_limited);
+
+            let current = stats
+                .peer_stats
+                .values()
+                .fold(HandshakeCounts::default(), |acc, peer| HandshakeCounts {
+                    attempts: acc.attempts + peer.handshake_attempts,
+                    successes: acc.successes + peer.successful_handshakes,
+                });
+            let last = self
+                .handshakes
+                .lock()
+                .unwrap()
+                .insert(name.clone(), current)
+                .unwrap_or_default();
+
+            // Counters restart when the device is recreated
+            let successes = current.successes.saturating_sub(last.successes);
+            let attempts = current.attempts.saturating_sub(last.attempts);
+            for _ in 0..successes {
+                self.monitor.record_handshake(name, true);
+            }
+            for _ in 0..attempts.saturating_sub(successes) {
+                self.monitor.record_handshake(name, false);
+            }
+
+            debug!("Updated monitor for network {}: {}", name, state);
+        }
+    }
+}
+
+/// Map a tunnel state to a monitoring connection state
+fn connection_state(state: TunnelState) -> ConnectionState {
+    match state {
+        TunnelState::Active => ConnectionState::Connected,
+        TunnelState::Degraded => ConnectionState::Degraded,
+        TunnelState::Starting | TunnelState::Reconnecting => ConnectionState::Connecting,
+        TunnelState::Error => ConnectionState::Failed,
+        TunnelState::Uninitialized | TunnelState::Stopping | TunnelState::Stopped => {
+            ConnectionState::Disconnected
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WireguardBackend;
+    use crate::monitoring::HealthStatus;
+    use crate::wireguard::{KeyPair, Tunnel, TunnelConfig};
+
+    fn test_tunnel() -> Arc<Tunnel> {
+        Arc::new(
+            Tunnel::new(TunnelConfig {
+                interface: "wg0".to_string(),
+                mtu: 1420,
+                addresses: vec!["10.0.0.1/24".to_string()],
+                listen_port: 0,
+                bind_address: None,
+                backend: WireguardBackend::Auto,
+                queues: 0,
+                dns_servers: vec![],
+                keypair: KeyPair::generate(),
+                peers: vec![],
+            })
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_bridge_tracks_registered_tunnels() {
+        let monitor = Arc::new(Monitor::new());
+        let handler = Arc::new(CommandHandler::new());
+        let bridge = MonitorBridge::new(monitor.clone(), handler.clone());
+
+        handler
+            .register_tunnel("office".to_string(), test_tunnel())
+            .await;
+        bridge.poll().await;
+
+        let stats = monitor.get_stats("office").unwrap();
+        assert_eq!(stats.state, ConnectionState::Disconnected);
+        assert_eq!(
+            monitor.health_check().unwrap().status,
+            HealthStatus::Unhealthy
+        );
+    }
+
+    #[test]
+    fn test_connection_state_mapping() {
+        assert_eq!(
+            connection_state(TunnelState::Active),
+            ConnectionState::Connected
+        );
+        assert_eq!(
+            connection_state(TunnelState::Error),
+            ConnectionState::Failed
+        );
+        assert_eq!(
+            connection_state(TunnelState::Reconnecting),
+            ConnectionState::Connecting
+        );
+        assert_eq!(
+            connection_state(TunnelState::Stopped),
+            ConnectionState::Disconnected
+        );
+    }
+}
@@ -25,6 +25,12 @@ pub enum MetricType {
     PeerLatency,
     /// Packet loss rate
     PacketLoss,
+    /// Average datagrams per UDP receive call
+    UdpRecvBatchSize,
+    /// Average datagrams per UDP send call
+    UdpSendBatchSize,
+    /// Handshakes rate limited under load
+    HandshakesRateLimited,
 }
 
 impl std::fmt::Display for MetricType {
@@ -38,6 +44,9 @@ impl std::fmt::Display for MetricType {
             Self::ConnectionUptime => write!(f, "harmony_agent_connection_uptime_seconds"),
             Self::PeerLatency => write!(f, "harmony_agent_peer_latency_milliseconds"),
             Self::PacketLoss => write!(f, "harmony_agent_packet_loss_rate"),
+            Self::UdpRecvBatchSize => write!(f, "harmony_agent_udp_recv_batch_size"),
+            Self::UdpSendBatchSize => write!(f, "harmony_agent_udp_send_batch_size"),
+            Self::HandshakesRateLimited => write!(f, "harmony_agent_handshakes_rate_limited_total"),
         }
     }
 }
@@ -54,6 +63,11 @@ impl MetricType {
             Self::ConnectionUptime => "Connection uptime in seconds",
             Self::PeerLatency => "Peer latency in milliseconds",
             Self::PacketLoss => "Packet loss rate percentage",
+            Self::UdpRecvBatchSize => "Average datagrams received per UDP system call",
+            Self::UdpSendBatchSize => "Average datagrams sent per UDP system call",
+            Self::HandshakesRateLimited => {
+                "Total handshake initiations answered with a cookie or dropped under load"
+            }
         }
     }
 
@@ -63,11 +77,14 @@ impl MetricType {
             Self::BytesTransmitted
             | Self::BytesReceived
             | Self::HandshakeSuccess
-            | Self::HandshakeFailure => "counter",
+            | Self::HandshakeFailure
+            | Self::HandshakesRateLimited => "counter",
             Self::ActivePeers
             | Self::ConnectionUptime
             | Self::PeerLatency
-            | Self::PacketLoss => "gauge",
+            | Self::PacketLoss
+            | Self::UdpRecvBatchSize
+            | Self::UdpSendBatchSize => "gauge",
         }
     }
 }
@@ -9,9 +9,11 @@ use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 use tracing::{debug, info};
 
+mod bridge;
 mod health;
 mod metrics;
 
+pub use bridge::{MonitorBridge, DEFAULT_POLL_INTERVAL};
 pub use health::{HealthCheck, HealthStatus, check_health};
 pub use metrics::{Metrics, MetricsCollector, MetricType};
 
@@ -65,6 +67,12 @@ pub struct NetworkStats {
     pub handshake_successes: u64,
     /// Handshake failures
     pub handshake_failures: u64,
+    /// Average datagrams per UDP receive call
+    pub recv_batch_size: f64,
+    /// Average datagrams per UDP send call
+    pub send_batch_size: f64,
+    /// Handshake initiations rate limited under load
+    pub handshakes_rate_limited: u64,
 }
 
 impl NetworkStats {
@@ -81,6 +89,9 @@ impl NetworkStats {
             healthy_peers: 0,
             handshake_successes: 0,
             handshake_failures: 0,
+            recv_batch_size: 0.0,
+            send_batch_size: 0.0,
+            handshakes_rate_limited: 0,
         }
     }
 
@@ -123,6 +134,14 @@ impl Monitor {
         info!("Registered network for monitoring");
     }
 
+    /// Stop tracking a network
+    pub fn unregister_network(&self, network: &str) {
+        let mut stats = self.stats.write().unwrap();
+        if stats.remove(network).is_some() {
+            info!("Unregistered network from monitoring");
+        }
+    }
+
     /// Update connection state
     pub fn update_state(&self, network: &str, state: ConnectionState) {
         let mut stats = self.stats.write().unwrap();
@@ -160,6 +179,29 @@ impl Monitor {
         }
     }
 
+    /// Update UDP batch sizes
+    pub fn update_batch_sizes(&self, network: &str, recv_batch_size: f64, send_batch_size: f64) {
+        let mut stats = self.stats.write().unwrap();
+        if let Some(net_stats) = stats.get_mut(network) {
+            net_stats.recv_batch_size = recv_batch_size;
+            net_stats.send_batch_size = send_batch_size;
+
+            self.metrics.record(MetricType::UdpRecvBatchSize, recv_batch_size);
+            self.metrics.record(MetricType::UdpSendBatchSize, send_batch_size);
+        }
+    }
+
+    /// Update the count of rate limited handshakes
+    pub fn update_rate_limited(&self, network: &str, handshakes_rate_limited: u64) {
+        let mut stats = self.stats.write().unwrap();
+        if let Some(net_stats) = stats.get_mut(network) {
+            net_stats.handshakes_rate_limited = handshakes_rate_limited;
+
+            self.metrics
+                .record(MetricType::HandshakesRateLimited, handshakes_rate_limited as f64);
+        }
+    }
+
     /// Record handshake result
     pub fn record_handshake(&self, network: &str, success: bool) {
         let mut stats = self.stats.write().unwrap();
@@ -227,8 +269,19 @@ mod tests {
         let monitor = Monitor::new();
         monitor.register_network("test".to_string());
         monitor.update_state("test", ConnectionState::Connected);
+        monitor.update_batch_sizes("test", 8.0, 2.5);
+        monitor.update_rate_limited("test", 3);
         
         let stats = monitor.get_stats("test").unwrap();
         assert_eq!(stats.state, ConnectionState::Connected);
+        assert_eq!(stats.recv_batch_size, 8.0);
+        assert_eq!(
+            monitor.metrics().get(MetricType::UdpSendBatchSize).unwrap().value,
+            2.5
+        );
+        assert_eq!(stats.handshakes_rate_limited, 3);
+
+        monitor.unregister_network("test");
+        assert!(monitor.get_stats("test").is_none());
     }
 }
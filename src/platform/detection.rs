@@ -5,6 +5,7 @@
 
 use std::fs;
 use std::path::Path;
+use std::sync::OnceLock;
 
 /// Container environment detection
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -165,6 +166,102 @@ fn is_privileged() -> bool {
     }
 }
 
+/// Check if the in-kernel WireGuard module is loaded, built in or loadable
+pub fn kernel_wireguard_available() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        if Path::new("/sys/module/wireguard").exists() {
+            return true;
+        }
+
+        let Ok(release) = fs::read_to_string("/proc/sys/kernel/osrelease") else {
+            return false;
+        };
+        let modules = Path::new("/lib/modules").join(release.trim());
+        ["modules.builtin", "modules.dep"].iter().any(|index| {
+            fs::read_to_string(modules.join(index)).is_ok_and(|contents| has_wireguard_module(&contents))
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        false
+    }
+}
+
+/// Batched UDP I/O features supported by the running kernel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UdpBatchSupport {
+    /// `recvmmsg`/`sendmmsg` are available
+    pub mmsg: bool,
+    /// UDP segmentation offload on send (`UDP_SEGMENT`)
+    pub gso: bool,
+    /// UDP receive coalescing (`UDP_GRO`)
+    pub gro: bool,
+}
+
+/// Detect batched UDP I/O support, probing the kernel once per process
+pub fn udp_batch_support() -> UdpBatchSupport {
+    static SUPPORT: OnceLock<UdpBatchSupport> = OnceLock::new();
+    *SUPPORT.get_or_init(probe_udp_batch_support)
+}
+
+#[cfg(target_os = "linux")]
+fn probe_udp_batch_support() -> UdpBatchSupport {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    // SAFETY: socket() has no preconditions
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return UdpBatchSupport::default();
+    }
+    // SAFETY: the descriptor was just created and nothing else owns it
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+    let fd = socket.as_raw_fd();
+
+    // Empty batches fail only if the system calls are missing or filtered
+    let unsupported = |result: libc::c_int| {
+        result < 0 && std::io::Error::last_os_error().raw_os_error() == Some(libc::ENOSYS)
+    };
+    // SAFETY: a zero-length batch never touches the message pointer
+    let mmsg = unsafe {
+        !unsupported(libc::recvmmsg(fd, std::ptr::null_mut(), 0, libc::MSG_DONTWAIT, std::ptr::null_mut()))
+            && !unsupported(libc::sendmmsg(fd, std::ptr::null_mut(), 0, libc::MSG_DONTWAIT))
+    };
+
+    // Kernels without the option reject it with ENOPROTOOPT
+    let has_option = |option: libc::c_int| {
+        let mut value: libc::c_int = 0;
+        let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+        // SAFETY: value and len describe a valid c_int buffer
+        unsafe {
+            libc::getsockopt(fd, libc::SOL_UDP, option, &mut value as *mut _ as *mut libc::c_void, &mut len) == 0
+        }
+    };
+
+    UdpBatchSupport {
+        mmsg,
+        gso: mmsg && has_option(libc::UDP_SEGMENT),
+        gro: mmsg && has_option(libc::UDP_GRO),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn probe_udp_batch_support() -> UdpBatchSupport {
+    UdpBatchSupport::default()
+}
+
+/// Check a modules.dep or modules.builtin listing for wireguard.ko
+#[cfg(target_os = "linux")]
+fn has_wireguard_module(listing: &str) -> bool {
+    listing.lines().any(|line| {
+        let path = line.split(':').next().unwrap_or_default();
+        path.rsplit('/')
+            .next()
+            .is_some_and(|file| file.starts_with("wireguard.ko"))
+    })
+}
+
 #[cfg(target_os = "linux")]
 fn detect_linux_version() -> String {
     // Try to read /etc/os-release
@@ -258,6 +355,26 @@ mod tests {
         // Environment-specific assertions would go here
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_has_wireguard_module() {
+        let listing = "kernel/drivers/net/dummy.ko.zst:\n\
+                       kernel/drivers/net/wireguard/wireguard.ko.zst: kernel/lib/crypto/libchacha.ko.zst\n";
+        assert!(has_wireguard_module(listing));
+        assert!(!has_wireguard_module("kernel/drivers/net/dummy.ko:\n"));
+        assert!(has_wireguard_module("kernel/drivers/net/wireguard/wireguard.ko\n"));
+    }
+
+    #[test]
+    fn test_udp_batch_support() {
+        let support = udp_batch_support();
+        assert_eq!(support, udp_batch_support());
+        // Offloads are only used through the batched system calls
+        assert!(support.mmsg || (!support.gso && !support.gro));
+        #[cfg(target_os = "linux")]
+        assert!(support.mmsg);
+    }
+
     #[test]
     fn test_container_environment_equality() {
         assert_eq!(ContainerEnvironment::None, ContainerEnvironment::None);
@@ -4,13 +4,14 @@
 //! management, routing, and DNS configuration.
 
 use crate::error::{Result, WgAgentError};
-use crate::platform::{detection, Platform, PlatformInfo};
+use crate::platform::{detection, AddressFamily, Platform, PlatformInfo};
 use std::process::Command;
 use tracing::{debug, info, warn};
 
-/// Linux platform implementation
+/// Linux platform implementation using the `ip` and `resolvconf` commands
 pub struct LinuxPlatform {
     info: PlatformInfo,
+    route_metric: Option<u32>,
 }
 
 impl LinuxPlatform {
@@ -18,9 +19,16 @@ impl LinuxPlatform {
     pub fn new() -> Self {
         Self {
             info: detection::detect_environment(),
+            route_metric: None,
         }
     }
 
+    /// Install peer routes with this metric
+    pub fn with_route_metric(mut self, metric: Option<u32>) -> Self {
+        self.route_metric = metric;
+        self
+    }
+
     /// Execute a system command
     fn run_command(&self, program: &str, args: &[&str]) -> Result<String> {
         debug!("Executing command: {} {:?}", program, args);
@@ -51,6 +59,14 @@ impl LinuxPlatform {
     }
 }
 
+/// `ip` option selecting the family of an address or route
+fn family_flag(address: &str) -> Result<&'static str> {
+    Ok(match AddressFamily::of(address)? {
+        AddressFamily::V4 => "-4",
+        AddressFamily::V6 => "-6",
+    })
+}
+
 impl Default for LinuxPlatform {
     fn default() -> Self {
         Self::new()
@@ -98,6 +114,12 @@ impl Platform for LinuxPlatform {
         Ok(())
     }
 
+    fn create_wireguard_interface(&self, name: &str) -> Result<()> {
+        info!("Creating kernel WireGuard interface: {}", name);
+        self.run_command("ip", &["link", "add", "dev", name, "type", "wireguard"])?;
+        Ok(())
+    }
+
     fn set_mtu(&self, interface: &str, mtu: u16) -> Result<()> {
         info!("Setting MTU for interface {}: {}", interface, mtu);
         self.run_command("ip", &["link", "set", interface, "mtu", &mtu.to_string()])?;
@@ -118,7 +140,8 @@ impl Platform for LinuxPlatform {
 
     fn set_address(&self, interface: &str, address: &str) -> Result<()> {
         info!("Setting address {} on interface {}", address, interface);
-        self.run_command("ip", &["addr", "add", address, "dev", interface])?;
+        let family = family_flag(address)?;
+        self.run_command("ip", &[family, "addr", "add", address, "dev", interface])?;
         Ok(())
     }
 
@@ -127,7 +150,13 @@ impl Platform for LinuxPlatform {
 
         for route in routes {
             debug!("Adding route: {} via {}", route, interface);
-            self.run_command("ip", &["route", "add", route, "dev", interface])?;
+            let family = family_flag(route)?;
+            let mut args = vec![family, "route", "add", route, "dev", interface];
+            let metric = self.route_metric.map(|metric| metric.to_string());
+            if let Some(ref metric) = metric {
+                args.extend(["metric", metric]);
+            }
+            self.run_command("ip", &args)?;
         }
 
         Ok(())
@@ -139,7 +168,9 @@ impl Platform for LinuxPlatform {
         for route in routes {
             debug!("Removing route: {} via {}", route, interface);
             // Ignore errors when removing routes (they might not exist)
-            let _ = self.run_command("ip", &["route", "del", route, "dev", interface]);
+            if let Ok(family) = family_flag(route) {
+                let _ = self.run_command("ip", &[family, "route", "del", route, "dev", interface]);
+            }
         }
 
         Ok(())
@@ -214,13 +245,24 @@ impl Platform for LinuxPlatform {
     }
 
     fn create_tun_device(&self, name: &str, mtu: u16) -> Result<tun::platform::Device> {
-        info!("Creating TUN device '{}' with MTU {}", name, mtu);
+        self.create_multiqueue_tun_device(name, mtu, 1)
+    }
+
+    /// Create the TUN device with `IFF_MULTI_QUEUE` when more than one queue is requested
+    fn create_multiqueue_tun_device(
+        &self,
+        name: &str,
+        mtu: u16,
+        queues: usize,
+    ) -> Result<tun::platform::Device> {
+        info!("Creating TUN device '{}' with MTU {} and {} queue(s)", name, mtu, queues);
 
         let mut config = tun::Configuration::default();
         
         config
             .name(name)
             .mtu(mtu as i32)
+            .queues(queues.max(1))
             .up();
 
         #[cfg(target_os = "linux")]
@@ -248,6 +290,13 @@ mod tests {
         assert_eq!(platform.info().os, "linux");
     }
 
+    #[test]
+    fn test_family_flag() {
+        assert_eq!(family_flag("10.0.0.0/24").unwrap(), "-4");
+        assert_eq!(family_flag("2001:db8::/32").unwrap(), "-6");
+        assert!(family_flag("not-an-ip").is_err());
+    }
+
     #[test]
     fn test_platform_info() {
         let platform = LinuxPlatform::new();
@@ -4,7 +4,7 @@
 //! management, routing, and DNS configuration using utun interfaces.
 
 use crate::error::{Result, WgAgentError};
-use crate::platform::{detection, Platform, PlatformInfo};
+use crate::platform::{detection, AddressFamily, Platform, PlatformInfo};
 use std::io::Write;
 use std::process::{Command, Stdio};
 use tun::Device;
@@ -53,6 +53,14 @@ impl MacOsPlatform {
     }
 }
 
+/// `route` option selecting the family of a route
+pub(crate) fn route_family(route: &str) -> Result<&'static str> {
+    Ok(match AddressFamily::of(route)? {
+        AddressFamily::V4 => "-inet",
+        AddressFamily::V6 => "-inet6",
+    })
+}
+
 impl Default for MacOsPlatform {
     fn default() -> Self {
         Self::new()
@@ -121,6 +129,15 @@ impl Platform for MacOsPlatform {
         let prefix_len: u8 = parts[1].parse().map_err(|_| {
             WgAgentError::Config(format!("Invalid prefix length: {}", parts[1]))
         })?;
+
+        // IPv6 addresses are added as aliases with a prefix length
+        if AddressFamily::of(ip)? == AddressFamily::V6 {
+            self.run_command(
+                "ifconfig",
+                &[interface, "inet6", ip, "prefixlen", parts[1], "alias"],
+            )?;
+            return Ok(());
+        }
         
         // macOS utun interfaces are point-to-point, so we need to specify both local and remote addresses
         // For WireGuard tunnels, we typically use the first IP in the subnet as the gateway/destination
@@ -153,7 +170,10 @@ impl Platform for MacOsPlatform {
         for route in routes {
             debug!("Adding route: {} via {}", route, interface);
             // macOS uses 'route add' command
-            self.run_command("route", &["add", "-net", route, "-interface", interface])?;
+            self.run_command(
+                "route",
+                &["add", route_family(route)?, "-net", route, "-interface", interface],
+            )?;
         }
 
         Ok(())
@@ -165,7 +185,9 @@ impl Platform for MacOsPlatform {
         for route in routes {
             debug!("Removing route: {} via {}", route, interface);
             // Ignore errors when removing routes (they might not exist)
-            let _ = self.run_command("route", &["delete", "-net", route]);
+            if let Ok(family) = route_family(route) {
+                let _ = self.run_command("route", &["delete", family, "-net", route]);
+            }
         }
 
         Ok(())
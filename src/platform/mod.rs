@@ -3,11 +3,17 @@
 //! This module provides platform-specific abstractions for TUN/TAP device
 //! management, routing, and DNS configuration.
 
-use crate::error::Result;
+use crate::config::{PlatformBackend, PlatformConfig};
+use crate::error::{Result, WgAgentError};
+use std::net::IpAddr;
+use std::sync::RwLock;
 
 #[cfg(target_os = "linux")]
 pub mod linux;
 
+#[cfg(target_os = "linux")]
+pub mod netlink;
+
 #[cfg(target_os = "macos")]
 pub mod macos;
 
@@ -16,7 +22,34 @@ pub mod windows;
 
 mod detection;
 
-pub use detection::{detect_environment, ContainerEnvironment, PlatformInfo};
+pub use detection::{
+    detect_environment, kernel_wireguard_available, udp_batch_support, ContainerEnvironment,
+    PlatformInfo, UdpBatchSupport,
+};
+
+/// IP address family of an address or route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// IPv4
+    V4,
+    /// IPv6
+    V6,
+}
+
+impl AddressFamily {
+    /// Determine the family of an IP address or CIDR (e.g. "fd00::/64")
+    pub fn of(address: &str) -> Result<Self> {
+        let ip = address.split('/').next().unwrap_or_default();
+        match ip.parse::<IpAddr>() {
+            Ok(IpAddr::V4(_)) => Ok(AddressFamily::V4),
+            Ok(IpAddr::V6(_)) => Ok(AddressFamily::V6),
+            Err(_) => Err(WgAgentError::Config(format!(
+                "Invalid IP address: {}",
+                address
+            ))),
+        }
+    }
+}
 
 /// Platform trait for cross-platform abstractions
 pub trait Platform: Send + Sync {
@@ -29,6 +62,14 @@ pub trait Platform: Send + Sync {
     /// Destroy a TUN/TAP device
     fn destroy_interface(&self, name: &str) -> Result<()>;
 
+    /// Create an in-kernel WireGuard interface
+    fn create_wireguard_interface(&self, name: &str) -> Result<()> {
+        Err(WgAgentError::Platform(format!(
+            "Cannot create {}: kernel WireGuard is not supported on this platform",
+            name
+        )))
+    }
+
     /// Set interface MTU
     fn set_mtu(&self, interface: &str, mtu: u16) -> Result<()>;
 
@@ -38,10 +79,18 @@ pub trait Platform: Send + Sync {
     /// Bring interface down
     fn interface_down(&self, interface: &str) -> Result<()>;
 
-    /// Set interface IP address
+    /// Set an interface IP address (IPv4 or IPv6 CIDR)
     fn set_address(&self, interface: &str, address: &str) -> Result<()>;
 
-    /// Configure routes for the interface
+    /// Set all interface IP addresses, stopping at the first failure
+    fn set_addresses(&self, interface: &str, addresses: &[String]) -> Result<()> {
+        for address in addresses {
+            self.set_address(interface, address)?;
+        }
+        Ok(())
+    }
+
+    /// Configure routes for the interface (IPv4 and IPv6 routes may be mixed)
     fn configure_routes(&self, interface: &str, routes: &[String]) -> Result<()>;
 
     /// Remove routes for the interface
@@ -59,13 +108,57 @@ pub trait Platform: Send + Sync {
     /// Create and configure a TUN device for WireGuard
     /// Returns a configured TUN device that can be used for packet I/O
     fn create_tun_device(&self, name: &str, mtu: u16) -> Result<tun::platform::Device>;
+
+    /// Create a TUN device with `queues` packet queues, so several workers can
+    /// read and write it in parallel
+    ///
+    /// Platforms without multi-queue TUN support create a single queue.
+    fn create_multiqueue_tun_device(
+        &self,
+        name: &str,
+        mtu: u16,
+        queues: usize,
+    ) -> Result<tun::platform::Device> {
+        let _ = queues;
+        self.create_tun_device(name, mtu)
+    }
+}
+
+/// Platform settings used by `get_platform`
+static SETTINGS: RwLock<PlatformConfig> = RwLock::new(PlatformConfig {
+    backend: PlatformBackend::Netlink,
+    route_metric: None,
+});
+
+/// Set the platform settings used for tunnels created from now on
+pub fn configure(settings: &PlatformConfig) {
+    *SETTINGS.write().unwrap() = settings.clone();
 }
 
 /// Get the platform implementation for the current OS
 pub fn get_platform() -> Box<dyn Platform> {
+    let settings = SETTINGS.read().unwrap().clone();
+    create_platform(&settings)
+}
+
+/// Create the platform implementation for the current OS with the given settings
+pub fn create_platform(settings: &PlatformConfig) -> Box<dyn Platform> {
     #[cfg(target_os = "linux")]
     {
-        Box::new(linux::LinuxPlatform::new())
+        let commands = || {
+            Box::new(linux::LinuxPlatform::new().with_route_metric(settings.route_metric))
+                as Box<dyn Platform>
+        };
+        match settings.backend {
+            PlatformBackend::Netlink => match netlink::NetlinkPlatform::new() {
+                Ok(platform) => Box::new(platform.with_route_metric(settings.route_metric)),
+                Err(e) => {
+                    tracing::warn!("Netlink unavailable ({}), using ip commands", e);
+                    commands()
+                }
+            },
+            PlatformBackend::Command => commands(),
+        }
     }
 
     #[cfg(target_os = "macos")]
@@ -78,3 +171,17 @@ pub fn get_platform() -> Box<dyn Platform> {
         Box::new(windows::WindowsPlatform::new())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_family() {
+        assert_eq!(AddressFamily::of("10.0.0.1/24").unwrap(), AddressFamily::V4);
+        assert_eq!(AddressFamily::of("10.0.0.1").unwrap(), AddressFamily::V4);
+        assert_eq!(AddressFamily::of("fd00::/64").unwrap(), AddressFamily::V6);
+        assert_eq!(AddressFamily::of("::/0").unwrap(), AddressFamily::V6);
+        assert!(AddressFamily::of("example.com/24").is_err());
+    }
+}
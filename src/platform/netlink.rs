@@ -0,0 +1,522 @@
+//! Linux rtnetlink platform implementation
+//!
+//! This module configures links, addresses and routes by talking rtnetlink
+//! directly instead of running `ip`, so it works in images without iproute2.
+//! DNS configuration and TUN creation are shared with the command backend.
+
+use crate::error::{PlatformError, Result, WgAgentError};
+use crate::platform::linux::LinuxPlatform;
+use crate::platform::{Platform, PlatformInfo};
+use netlink_packet_core::{
+    NetlinkDeserializable, NetlinkHeader, NetlinkMessage, NetlinkPayload, NetlinkSerializable,
+    NLM_F_ACK, NLM_F_CREATE, NLM_F_EXCL, NLM_F_REQUEST,
+};
+use netlink_packet_route::address::{AddressAttribute, AddressMessage};
+use netlink_packet_route::link::{InfoKind, LinkAttribute, LinkFlags, LinkInfo, LinkMessage};
+use netlink_packet_route::route::{
+    RouteAttribute, RouteHeader, RouteMessage, RouteProtocol, RouteScope, RouteType,
+};
+use netlink_packet_route::{AddressFamily, RouteNetlinkMessage};
+use netlink_sys::{protocols::NETLINK_ROUTE, Socket, SocketAddr};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// Blocking netlink request/acknowledge channel
+pub(crate) struct NetlinkSocket {
+    socket: Mutex<Socket>,
+    sequence: AtomicU32,
+}
+
+impl NetlinkSocket {
+    /// Open and connect a netlink socket for `protocol` (e.g. `NETLINK_ROUTE`)
+    pub(crate) fn open(protocol: isize) -> io::Result<Self> {
+        let mut socket = Socket::new(protocol)?;
+        socket.bind_auto()?;
+        socket.connect(&SocketAddr::new(0, 0))?;
+        Ok(Self {
+            socket: Mutex::new(socket),
+            sequence: AtomicU32::new(1),
+        })
+    }
+
+    /// Send a request and collect replies until the kernel acknowledges it
+    pub(crate) fn request<M>(&self, message: M, flags: u16) -> io::Result<Vec<M>>
+    where
+        M: NetlinkSerializable + NetlinkDeserializable,
+    {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let mut header = NetlinkHeader::default();
+        header.flags = NLM_F_REQUEST | NLM_F_ACK | flags;
+        header.sequence_number = sequence;
+
+        let mut packet = NetlinkMessage::new(header, NetlinkPayload::InnerMessage(message));
+        packet.finalize();
+        let mut buf = vec![0u8; packet.buffer_len()];
+        packet.serialize(&mut buf);
+
+        let socket = self.socket.lock().unwrap();
+        socket.send(&buf, 0)?;
+
+        let mut replies = Vec::new();
+        loop {
+            let (data, _) = socket.recv_from_full()?;
+            let mut offset = 0;
+            while offset < data.len() {
+                let reply = NetlinkMessage::<M>::deserialize(&data[offset..])
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+                let length = reply.header.length as usize;
+                if length == 0 {
+                    break;
+                }
+                // Messages are padded to four bytes
+                offset += (length + 3) & !3;
+
+                // Skip late replies to earlier requests
+                if reply.header.sequence_number != sequence {
+                    continue;
+                }
+                match reply.payload {
+                    NetlinkPayload::Error(error) if error.code.is_some() => {
+                        return Err(error.to_io());
+                    }
+                    NetlinkPayload::Error(_) | NetlinkPayload::Done(_) => return Ok(replies),
+                    NetlinkPayload::InnerMessage(message) => replies.push(message),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Linux platform using rtnetlink for links, addresses and routes
+pub struct NetlinkPlatform {
+    netlink: NetlinkSocket,
+    /// Command backend used for DNS and TUN creation
+    commands: LinuxPlatform,
+    route_metric: Option<u32>,
+}
+
+impl NetlinkPlatform {
+    /// Open a netlink socket for configuring interfaces
+    pub fn new() -> Result<Self> {
+        let netlink = NetlinkSocket::open(NETLINK_ROUTE).map_err(|e| PlatformError::Netlink {
+            operation: "open netlink socket".to_string(),
+            source: e,
+        })?;
+
+        Ok(Self {
+            netlink,
+            commands: LinuxPlatform::new(),
+            route_metric: None,
+        })
+    }
+
+    /// Install peer routes (and address prefix routes) with this metric
+    pub fn with_route_metric(mut self, metric: Option<u32>) -> Self {
+        self.route_metric = metric;
+        self
+    }
+
+    /// Send a request, mapping kernel errors to `PlatformError`
+    fn request(
+        &self,
+        interface: &str,
+        operation: String,
+        message: RouteNetlinkMessage,
+        flags: u16,
+    ) -> Result<Vec<RouteNetlinkMessage>> {
+        debug!("netlink: {}", operation);
+        self.netlink
+            .request(message, flags)
+            .map_err(|e| map_error(interface, operation, e).into())
+    }
+
+    /// Look up an interface index by name
+    fn link_index(&self, interface: &str) -> Result<u32> {
+        let mut message = LinkMessage::default();
+        message
+            .attributes
+            .push(LinkAttribute::IfName(interface.to_string()));
+
+        let replies = self.request(
+            interface,
+            format!("get link {}", interface),
+            RouteNetlinkMessage::GetLink(message),
+            0,
+        )?;
+        replies
+            .into_iter()
+            .find_map(|reply| match reply {
+                RouteNetlinkMessage::NewLink(link) => Some(link.header.index),
+                _ => None,
+            })
+            .ok_or_else(|| PlatformError::InterfaceNotFound(interface.to_string()).into())
+    }
+
+    /// Change link flags or attributes
+    fn set_link(
+        &self,
+        interface: &str,
+        operation: String,
+        update: impl FnOnce(&mut LinkMessage),
+    ) -> Result<()> {
+        let mut message = LinkMessage::default();
+        message.header.index = self.link_index(interface)?;
+        update(&mut message);
+        self.request(interface, operation, RouteNetlinkMessage::SetLink(message), 0)?;
+        Ok(())
+    }
+
+    /// Build the route message for `route` via interface `index`
+    fn route_message(&self, index: u32, route: &str) -> Result<RouteMessage> {
+        let (destination, prefix_len) = parse_cidr(route)?;
+        let destination = network_address(destination, prefix_len);
+        Ok(route_message(index, destination, prefix_len, self.route_metric))
+    }
+}
+
+impl Platform for NetlinkPlatform {
+    fn info(&self) -> &PlatformInfo {
+        self.commands.info()
+    }
+
+    fn create_interface(&self, name: &str) -> Result<()> {
+        info!("Creating Linux interface: {}", name);
+
+        match self.link_index(name) {
+            Ok(_) => warn!("Interface {} already exists", name),
+            Err(WgAgentError::PlatformOperation(PlatformError::InterfaceNotFound(_))) => {
+                debug!("Interface {} does not exist yet (will be created by WireGuard)", name);
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    fn destroy_interface(&self, name: &str) -> Result<()> {
+        info!("Destroying Linux interface: {}", name);
+
+        let index = match self.link_index(name) {
+            Ok(index) => index,
+            Err(WgAgentError::PlatformOperation(PlatformError::InterfaceNotFound(_))) => {
+                debug!("Interface {} does not exist", name);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        self.interface_down(name)?;
+
+        let mut message = LinkMessage::default();
+        message.header.index = index;
+        self.request(
+            name,
+            format!("delete link {}", name),
+            RouteNetlinkMessage::DelLink(message),
+            0,
+        )?;
+
+        info!("Interface {} destroyed", name);
+        Ok(())
+    }
+
+    fn create_wireguard_interface(&self, name: &str) -> Result<()> {
+        info!("Creating kernel WireGuard interface: {}", name);
+
+        let mut message = LinkMessage::default();
+        message.attributes.push(LinkAttribute::IfName(name.to_string()));
+        message
+            .attributes
+            .push(LinkAttribute::LinkInfo(vec![LinkInfo::Kind(InfoKind::Wireguard)]));
+
+        self.request(
+            name,
+            format!("add wireguard link {}", name),
+            RouteNetlinkMessage::NewLink(message),
+            NLM_F_CREATE | NLM_F_EXCL,
+        )?;
+        Ok(())
+    }
+
+    fn set_mtu(&self, interface: &str, mtu: u16) -> Result<()> {
+        info!("Setting MTU for interface {}: {}", interface, mtu);
+        self.set_link(interface, format!("set mtu {} on {}", mtu, interface), |link| {
+            link.attributes.push(LinkAttribute::Mtu(mtu as u32));
+        })
+    }
+
+    fn interface_up(&self, interface: &str) -> Result<()> {
+        info!("Bringing interface {} up", interface);
+        self.set_link(interface, format!("set link {} up", interface), |link| {
+            link.header.flags = LinkFlags::Up;
+            link.header.change_mask = LinkFlags::Up;
+        })
+    }
+
+    fn interface_down(&self, interface: &str) -> Result<()> {
+        info!("Bringing interface {} down", interface);
+        self.set_link(interface, format!("set link {} down", interface), |link| {
+            link.header.change_mask = LinkFlags::Up;
+        })
+    }
+
+    fn set_address(&self, interface: &str, address: &str) -> Result<()> {
+        info!("Setting address {} on interface {}", address, interface);
+        let (ip, prefix_len) = parse_cidr(address)?;
+        let message = address_message(self.link_index(interface)?, ip, prefix_len, self.route_metric);
+
+        let result = self.request(
+            interface,
+            format!("add address {} on {}", address, interface),
+            RouteNetlinkMessage::NewAddress(message),
+            NLM_F_CREATE | NLM_F_EXCL,
+        );
+        match result {
+            Err(WgAgentError::PlatformOperation(PlatformError::AlreadyExists(_))) => {
+                debug!("Address {} already assigned to {}", address, interface);
+                Ok(())
+            }
+            other => other.map(|_| ()),
+        }
+    }
+
+    fn configure_routes(&self, interface: &str, routes: &[String]) -> Result<()> {
+        info!("Configuring {} routes for interface {}", routes.len(), interface);
+        let index = self.link_index(interface)?;
+
+        for route in routes {
+            debug!("Adding route: {} via {}", route, interface);
+            let result = self.request(
+                interface,
+                format!("add route {} via {}", route, interface),
+                RouteNetlinkMessage::NewRoute(self.route_message(index, route)?),
+                NLM_F_CREATE | NLM_F_EXCL,
+            );
+            match result {
+                Err(WgAgentError::PlatformOperation(PlatformError::AlreadyExists(_))) => {
+                    debug!("Route {} via {} already exists", route, interface);
+                }
+                other => {
+                    other?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove_routes(&self, interface: &str, routes: &[String]) -> Result<()> {
+        info!("Removing {} routes from interface {}", routes.len(), interface);
+
+        // The routes disappear with the interface if it is already gone
+        let Ok(index) = self.link_index(interface) else {
+            return Ok(());
+        };
+
+        for route in routes {
+            debug!("Removing route: {} via {}", route, interface);
+            let Ok(message) = self.route_message(index, route) else {
+                continue;
+            };
+            // Ignore errors when removing routes (they might not exist)
+            let _ = self.request(
+                interface,
+                format!("delete route {} via {}", route, interface),
+                RouteNetlinkMessage::DelRoute(message),
+                0,
+            );
+        }
+
+        Ok(())
+    }
+
+    fn configure_dns(&self, interface: &str, dns_servers: &[String]) -> Result<()> {
+        self.commands.configure_dns(interface, dns_servers)
+    }
+
+    fn remove_dns(&self, interface: &str) -> Result<()> {
+        self.commands.remove_dns(interface)
+    }
+
+    fn check_capabilities(&self) -> Result<Vec<String>> {
+        let mut missing = Vec::new();
+
+        if !self.info().is_privileged {
+            missing.push("NET_ADMIN capability required (run as root or with CAP_NET_ADMIN)".to_string());
+        }
+        if !Path::new("/dev/net/tun").exists() {
+            missing.push("TUN device not available: /dev/net/tun".to_string());
+        }
+
+        Ok(missing)
+    }
+
+    fn create_tun_device(&self, name: &str, mtu: u16) -> Result<tun::platform::Device> {
+        self.commands.create_tun_device(name, mtu)
+    }
+
+    fn create_multiqueue_tun_device(
+        &self,
+        name: &str,
+        mtu: u16,
+        queues: usize,
+    ) -> Result<tun::platform::Device> {
+        self.commands.create_multiqueue_tun_device(name, mtu, queues)
+    }
+}
+
+/// Map a kernel error to a structured platform error
+pub(crate) fn map_error(interface: &str, operation: String, error: io::Error) -> PlatformError {
+    match error.raw_os_error() {
+        Some(libc::ENODEV) => PlatformError::InterfaceNotFound(interface.to_string()),
+        Some(libc::EEXIST) => PlatformError::AlreadyExists(operation),
+        Some(libc::EPERM) | Some(libc::EACCES) => PlatformError::PermissionDenied(operation),
+        _ => PlatformError::Netlink {
+            operation,
+            source: error,
+        },
+    }
+}
+
+/// Split "address/prefix" into its parts
+pub(crate) fn parse_cidr(cidr: &str) -> Result<(IpAddr, u8)> {
+    let invalid = || WgAgentError::Config(format!("Invalid CIDR notation: {}", cidr));
+
+    let (ip, prefix_len) = match cidr.split_once('/') {
+        Some((ip, prefix_len)) => {
+            let ip: IpAddr = ip.parse().map_err(|_| invalid())?;
+            (ip, prefix_len.parse::<u8>().map_err(|_| invalid())?)
+        }
+        None => {
+            let ip: IpAddr = cidr.parse().map_err(|_| invalid())?;
+            (ip, if ip.is_ipv4() { 32 } else { 128 })
+        }
+    };
+
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    if prefix_len > max {
+        return Err(invalid());
+    }
+    Ok((ip, prefix_len))
+}
+
+/// Clear the host bits of an address (the kernel rejects routes with them set)
+pub(crate) fn network_address(ip: IpAddr, prefix_len: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(v4) & mask))
+        }
+        IpAddr::V6(v6) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask))
+        }
+    }
+}
+
+fn address_family(ip: &IpAddr) -> AddressFamily {
+    match ip {
+        IpAddr::V4(_) => AddressFamily::Inet,
+        IpAddr::V6(_) => AddressFamily::Inet6,
+    }
+}
+
+/// Build an RTM_NEWADDR message
+fn address_message(index: u32, ip: IpAddr, prefix_len: u8, metric: Option<u32>) -> AddressMessage {
+    let mut message = AddressMessage::default();
+    message.header.family = address_family(&ip);
+    message.header.prefix_len = prefix_len;
+    message.header.index = index;
+    message.attributes.push(AddressAttribute::Local(ip));
+    message.attributes.push(AddressAttribute::Address(ip));
+    if let Some(metric) = metric {
+        message.attributes.push(AddressAttribute::RoutePriority(metric));
+    }
+    message
+}
+
+/// Build an RTM_NEWROUTE/RTM_DELROUTE message for a directly connected route
+fn route_message(index: u32, destination: IpAddr, prefix_len: u8, metric: Option<u32>) -> RouteMessage {
+    let mut message = RouteMessage::default();
+    message.header.address_family = address_family(&destination);
+    message.header.destination_prefix_length = prefix_len;
+    message.header.table = RouteHeader::RT_TABLE_MAIN;
+    message.header.protocol = RouteProtocol::Boot;
+    message.header.scope = RouteScope::Link;
+    message.header.kind = RouteType::Unicast;
+
+    // Default routes carry no destination attribute
+    if prefix_len > 0 {
+        message
+            .attributes
+            .push(RouteAttribute::Destination(destination.into()));
+    }
+    message.attributes.push(RouteAttribute::Oif(index));
+    if let Some(metric) = metric {
+        message.attributes.push(RouteAttribute::Priority(metric));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cidr() {
+        assert_eq!(
+            parse_cidr("10.0.0.1/24").unwrap(),
+            ("10.0.0.1".parse().unwrap(), 24)
+        );
+        assert_eq!(parse_cidr("fd00::1").unwrap(), ("fd00::1".parse().unwrap(), 128));
+        assert!(parse_cidr("10.0.0.1/33").is_err());
+        assert!(parse_cidr("example.com/24").is_err());
+    }
+
+    #[test]
+    fn test_network_address() {
+        let (ip, prefix_len) = parse_cidr("10.1.2.3/16").unwrap();
+        assert_eq!(network_address(ip, prefix_len), "10.1.0.0".parse::<IpAddr>().unwrap());
+        let (ip, prefix_len) = parse_cidr("fd00:1:2::3/48").unwrap();
+        assert_eq!(network_address(ip, prefix_len), "fd00:1:2::".parse::<IpAddr>().unwrap());
+        let (ip, prefix_len) = parse_cidr("::/0").unwrap();
+        assert_eq!(network_address(ip, prefix_len), "::".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_route_message() {
+        let message = route_message(7, "fd00::".parse().unwrap(), 64, Some(100));
+        assert_eq!(message.header.address_family, AddressFamily::Inet6);
+        assert_eq!(message.header.destination_prefix_length, 64);
+        assert!(message.attributes.contains(&RouteAttribute::Oif(7)));
+        assert!(message.attributes.contains(&RouteAttribute::Priority(100)));
+
+        let default_route = route_message(7, "0.0.0.0".parse().unwrap(), 0, None);
+        assert_eq!(default_route.header.address_family, AddressFamily::Inet);
+        assert_eq!(default_route.attributes, vec![RouteAttribute::Oif(7)]);
+    }
+
+    #[test]
+    fn test_map_error() {
+        let error = map_error("wg0", "add route".to_string(), io::Error::from_raw_os_error(libc::ENODEV));
+        assert!(matches!(error, PlatformError::InterfaceNotFound(name) if name == "wg0"));
+        let error = map_error("wg0", "add route".to_string(), io::Error::from_raw_os_error(libc::EPERM));
+        assert!(matches!(error, PlatformError::PermissionDenied(_)));
+        let error = map_error("wg0", "add route".to_string(), io::Error::from_raw_os_error(libc::EINVAL));
+        assert!(matches!(error, PlatformError::Netlink { .. }));
+    }
+
+    #[test]
+    fn test_link_lookup() {
+        let platform = NetlinkPlatform::new().unwrap();
+        assert!(platform.link_index("lo").unwrap() > 0);
+        assert!(matches!(
+            platform.link_index("wg-missing0"),
+            Err(WgAgentError::PlatformOperation(PlatformError::InterfaceNotFound(_)))
+        ));
+    }
+}
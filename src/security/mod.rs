@@ -14,7 +14,7 @@ mod permissions;
 mod privileges;
 mod validation;
 
-pub use permissions::{validate_file_permissions, SecureFileMode};
+pub use permissions::{validate_directory_security, validate_file_permissions, SecureFileMode};
 pub use privileges::{drop_privileges, lock_memory, PrivilegeLevel};
 pub use validation::{sanitize_path, validate_interface_name, validate_network_name};
 
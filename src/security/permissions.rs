@@ -121,7 +121,6 @@ pub fn set_secure_permissions(path: &Path, _mode: SecureFileMode) -> Result<(),
 
 /// Validate directory is not world-writable
 #[cfg(unix)]
-#[allow(dead_code)]
 pub fn validate_directory_security(path: &Path) -> Result<(), WgAgentError> {
     use std::os::unix::fs::PermissionsExt;
 
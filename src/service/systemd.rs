@@ -5,7 +5,7 @@
 
 use super::{Service, ServiceState, ServiceStatus};
 use crate::error::WgAgentError;
-use std::time::{Duration, Instant};
+use std::time::Instant;
 use tracing::{debug, info, warn};
 
 /// Systemd service implementation
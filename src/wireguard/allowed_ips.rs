@@ -0,0 +1,270 @@
+//! Allowed-IPs routing table
+//!
+//! This module implements the longest-prefix-match table used for WireGuard
+//! cryptokey routing. Each peer owns a set of CIDR ranges; outbound packets
+//! are sent to the peer owning the most specific range that contains the
+//! destination address, and inbound packets are only accepted if their
+//! source address maps back to the peer that sent them.
+
+use crate::error::{Result, WgAgentError};
+use std::net::IpAddr;
+
+/// A single node in a binary prefix trie
+#[derive(Debug, Clone)]
+struct Node<T> {
+    /// Child nodes for the next bit (0 and 1)
+    children: [Option<usize>; 2],
+    /// Value stored at this prefix, if any
+    value: Option<T>,
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Self {
+            children: [None, None],
+            value: None,
+        }
+    }
+}
+
+/// Binary trie keyed by address bits, with nodes stored in a flat vector
+#[derive(Debug, Clone)]
+struct Trie<T> {
+    nodes: Vec<Node<T>>,
+}
+
+impl<T: Clone + PartialEq> Trie<T> {
+    fn new() -> Self {
+        Self {
+            nodes: vec![Node::new()],
+        }
+    }
+
+    fn insert(&mut self, bytes: &[u8], prefix: u8, value: T) -> Option<T> {
+        let mut current = 0;
+
+        for i in 0..prefix as usize {
+            let bit = bit_at(bytes, i);
+            current = match self.nodes[current].children[bit] {
+                Some(next) => next,
+                None => {
+                    self.nodes.push(Node::new());
+                    let next = self.nodes.len() - 1;
+                    self.nodes[current].children[bit] = Some(next);
+                    next
+                }
+            };
+        }
+
+        self.nodes[current].value.replace(value)
+    }
+
+    fn lookup(&self, bytes: &[u8]) -> Option<&T> {
+        let mut current = 0;
+        let mut best = self.nodes[0].value.as_ref();
+
+        for i in 0..bytes.len() * 8 {
+            match self.nodes[current].children[bit_at(bytes, i)] {
+                Some(next) => {
+                    current = next;
+                    if let Some(value) = self.nodes[current].value.as_ref() {
+                        best = Some(value);
+                    }
+                }
+                None => break,
+            }
+        }
+
+        best
+    }
+
+    fn remove_value(&mut self, value: &T) -> usize {
+        let mut removed = 0;
+        for node in &mut self.nodes {
+            if node.value.as_ref() == Some(value) {
+                node.value = None;
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.iter().filter(|n| n.value.is_some()).count()
+    }
+}
+
+/// Get bit `index` (most significant first) of a big-endian byte string
+fn bit_at(bytes: &[u8], index: usize) -> usize {
+    ((bytes[index / 8] >> (7 - (index % 8))) & 1) as usize
+}
+
+/// Longest-prefix-match table for IPv4 and IPv6 allowed IPs
+#[derive(Debug, Clone)]
+pub struct AllowedIps<T> {
+    v4: Trie<T>,
+    v6: Trie<T>,
+}
+
+impl<T: Clone + PartialEq> AllowedIps<T> {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self {
+            v4: Trie::new(),
+            v6: Trie::new(),
+        }
+    }
+
+    /// Insert a prefix, returning the value it previously mapped to (if any)
+    pub fn insert(&mut self, addr: IpAddr, prefix: u8, value: T) -> Result<Option<T>> {
+        match addr {
+            IpAddr::V4(v4) => {
+                if prefix > 32 {
+                    return Err(WgAgentError::Config(format!(
+                        "Prefix length {} exceeds maximum 32 for IP address {}",
+                        prefix, addr
+                    )));
+                }
+                Ok(self.v4.insert(&v4.octets(), prefix, value))
+            }
+            IpAddr::V6(v6) => {
+                if prefix > 128 {
+                    return Err(WgAgentError::Config(format!(
+                        "Prefix length {} exceeds maximum 128 for IP address {}",
+                        prefix, addr
+                    )));
+                }
+                Ok(self.v6.insert(&v6.octets(), prefix, value))
+            }
+        }
+    }
+
+    /// Insert a prefix given in CIDR notation (e.g. "10.0.0.0/24")
+    pub fn insert_cidr(&mut self, cidr: &str, value: T) -> Result<Option<T>> {
+        let (addr, prefix) = parse_cidr(cidr)?;
+        self.insert(addr, prefix, value)
+    }
+
+    /// Find the value owning the most specific prefix containing `addr`
+    pub fn lookup(&self, addr: IpAddr) -> Option<&T> {
+        match addr {
+            IpAddr::V4(v4) => self.v4.lookup(&v4.octets()),
+            IpAddr::V6(v6) => self.v6.lookup(&v6.octets()),
+        }
+    }
+
+    /// Remove every prefix mapping to `value`, returning how many were removed
+    pub fn remove_value(&mut self, value: &T) -> usize {
+        self.v4.remove_value(value) + self.v6.remove_value(value)
+    }
+
+    /// Number of prefixes in the table
+    pub fn len(&self) -> usize {
+        self.v4.len() + self.v6.len()
+    }
+
+    /// Check if the table is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Clone + PartialEq> Default for AllowedIps<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parse CIDR notation into an address and prefix length
+fn parse_cidr(cidr: &str) -> Result<(IpAddr, u8)> {
+    let (addr, prefix) = cidr.trim().split_once('/').ok_or_else(|| {
+        WgAgentError::Config(format!(
+            "Invalid allowed IP format: {} (expected CIDR notation like 10.0.0.0/24)",
+            cidr
+        ))
+    })?;
+
+    let addr: IpAddr = addr.parse().map_err(|e| {
+        WgAgentError::Config(format!("Invalid IP address in '{}': {}", cidr, e))
+    })?;
+
+    let prefix: u8 = prefix.parse().map_err(|e| {
+        WgAgentError::Config(format!("Invalid prefix length in '{}': {}", cidr, e))
+    })?;
+
+    Ok((addr, prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_prefix_match_v4() {
+        let mut table = AllowedIps::new();
+        table.insert_cidr("10.0.0.0/8", "wide").unwrap();
+        table.insert_cidr("10.1.0.0/16", "narrow").unwrap();
+        table.insert_cidr("10.1.2.3/32", "host").unwrap();
+
+        assert_eq!(table.lookup("10.9.9.9".parse().unwrap()), Some(&"wide"));
+        assert_eq!(table.lookup("10.1.9.9".parse().unwrap()), Some(&"narrow"));
+        assert_eq!(table.lookup("10.1.2.3".parse().unwrap()), Some(&"host"));
+        assert_eq!(table.lookup("192.168.1.1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_longest_prefix_match_v6() {
+        let mut table = AllowedIps::new();
+        table.insert_cidr("fd42::/48", 1).unwrap();
+        table.insert_cidr("fd42:0:0:1::/64", 2).unwrap();
+
+        assert_eq!(table.lookup("fd42::1".parse().unwrap()), Some(&1));
+        assert_eq!(table.lookup("fd42:0:0:1::1".parse().unwrap()), Some(&2));
+        assert_eq!(table.lookup("fd43::1".parse().unwrap()), None);
+        // IPv4 and IPv6 tables are independent
+        assert_eq!(table.lookup("10.0.0.1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_default_route() {
+        let mut table = AllowedIps::new();
+        table.insert_cidr("0.0.0.0/0", "default").unwrap();
+        table.insert_cidr("10.0.0.0/24", "site").unwrap();
+
+        assert_eq!(table.lookup("8.8.8.8".parse().unwrap()), Some(&"default"));
+        assert_eq!(table.lookup("10.0.0.7".parse().unwrap()), Some(&"site"));
+        assert_eq!(table.lookup("::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_insert_replaces_owner() {
+        let mut table = AllowedIps::new();
+        assert_eq!(table.insert_cidr("10.0.0.0/24", "a").unwrap(), None);
+        assert_eq!(table.insert_cidr("10.0.0.0/24", "b").unwrap(), Some("a"));
+        assert_eq!(table.lookup("10.0.0.1".parse().unwrap()), Some(&"b"));
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_value() {
+        let mut table = AllowedIps::new();
+        table.insert_cidr("10.0.0.0/24", "a").unwrap();
+        table.insert_cidr("fd00::/64", "a").unwrap();
+        table.insert_cidr("10.0.1.0/24", "b").unwrap();
+
+        assert_eq!(table.remove_value(&"a"), 2);
+        assert_eq!(table.lookup("10.0.0.1".parse().unwrap()), None);
+        assert_eq!(table.lookup("10.0.1.1".parse().unwrap()), Some(&"b"));
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_cidr() {
+        let mut table = AllowedIps::new();
+        assert!(table.insert_cidr("10.0.0.0", 1).is_err());
+        assert!(table.insert_cidr("10.0.0.0/33", 1).is_err());
+        assert!(table.insert_cidr("fd00::/129", 1).is_err());
+        assert!(table.insert_cidr("invalid/24", 1).is_err());
+        assert!(table.is_empty());
+    }
+}
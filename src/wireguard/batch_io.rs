@@ -0,0 +1,369 @@
+//! Batched UDP socket I/O for Linux
+//!
+//! `recvmmsg`/`sendmmsg` move many datagrams per system call. Where the kernel
+//! supports it, runs of equal-sized datagrams to one destination are sent as a
+//! single buffer the kernel segments (UDP GSO), and received runs may arrive
+//! coalesced into one buffer with their segment size attached (UDP GRO).
+
+use crate::wireguard::io::RecvMeta;
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::Range;
+use std::os::fd::RawFd;
+use std::ptr;
+
+/// Most datagrams sent as one GSO buffer
+const GSO_MAX_SEGMENTS: usize = 64;
+
+/// Largest UDP payload of an IPv4 datagram, the limit for one GSO buffer
+const GSO_MAX_BYTES: usize = 65507;
+
+/// Control message space for one `int`-sized option
+// SAFETY: CMSG_SPACE only does arithmetic
+const CONTROL_LEN: usize = unsafe { libc::CMSG_SPACE(mem::size_of::<libc::c_int>() as u32) } as usize;
+
+/// Control message buffer, aligned for `cmsghdr`
+#[repr(C, align(8))]
+#[derive(Clone, Copy)]
+struct ControlBuffer([u8; CONTROL_LEN]);
+
+impl ControlBuffer {
+    const EMPTY: Self = Self([0; CONTROL_LEN]);
+}
+
+/// Ask the kernel to coalesce received datagrams
+pub(crate) fn enable_gro(fd: RawFd) -> io::Result<()> {
+    let enable: libc::c_int = 1;
+    // SAFETY: the option value points to a live c_int of the given size
+    let result = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_UDP,
+            libc::UDP_GRO,
+            &enable as *const _ as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Receive waiting datagrams into `bufs` without blocking
+///
+/// Returns the number of buffers filled; fails with `WouldBlock` if nothing
+/// is waiting.
+pub(crate) fn recv(fd: RawFd, bufs: &mut [Vec<u8>], meta: &mut [RecvMeta]) -> io::Result<usize> {
+    let count = bufs.len().min(meta.len());
+    let mut iovecs: Vec<libc::iovec> = bufs[..count]
+        .iter_mut()
+        .map(|buf| libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        })
+        .collect();
+    // SAFETY: all-zero bytes are a valid sockaddr_storage
+    let mut addrs = vec![unsafe { mem::zeroed::<libc::sockaddr_storage>() }; count];
+    let mut controls = vec![ControlBuffer::EMPTY; count];
+
+    let mut messages: Vec<libc::mmsghdr> = iovecs
+        .iter_mut()
+        .zip(addrs.iter_mut())
+        .zip(controls.iter_mut())
+        .map(|((iovec, addr), control)| {
+            // SAFETY: all-zero bytes are a valid mmsghdr
+            let mut message: libc::mmsghdr = unsafe { mem::zeroed() };
+            message.msg_hdr.msg_name = addr as *mut _ as *mut libc::c_void;
+            message.msg_hdr.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            message.msg_hdr.msg_iov = iovec;
+            message.msg_hdr.msg_iovlen = 1;
+            message.msg_hdr.msg_control = control.0.as_mut_ptr() as *mut libc::c_void;
+            message.msg_hdr.msg_controllen = CONTROL_LEN as _;
+            message
+        })
+        .collect();
+
+    // SAFETY: every message points at buffers that outlive the call
+    let received = unsafe {
+        libc::recvmmsg(
+            fd,
+            messages.as_mut_ptr(),
+            count as libc::c_uint,
+            libc::MSG_DONTWAIT,
+            ptr::null_mut(),
+        )
+    };
+    if received < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let received = received as usize;
+    for (message, (addr, meta)) in messages.iter().zip(addrs.iter().zip(meta.iter_mut())).take(received) {
+        let len = message.msg_len as usize;
+        let Some(src) = to_socket_addr(addr) else {
+            // Not an IP datagram; leave nothing to process
+            *meta = RecvMeta { len: 0, src: meta.src, stride: 0 };
+            continue;
+        };
+        *meta = RecvMeta {
+            len,
+            src,
+            stride: gro_segment_size(&message.msg_hdr).unwrap_or(len),
+        };
+    }
+    Ok(received)
+}
+
+/// Send `datagrams` in order without blocking, returning how many were sent
+///
+/// With `gso`, consecutive datagrams to the same destination go out as one
+/// segmented buffer. IPv4 targets are mapped to IPv6 when `ipv6` is set, for
+/// dual-stack sockets. Fails with `WouldBlock` if nothing could be sent.
+pub(crate) fn send(
+    fd: RawFd,
+    datagrams: &[(&[u8], SocketAddr)],
+    gso: bool,
+    ipv6: bool,
+) -> io::Result<usize> {
+    let groups = gso_groups(datagrams, gso);
+
+    let iovecs: Vec<libc::iovec> = datagrams
+        .iter()
+        .map(|(data, _)| libc::iovec {
+            iov_base: data.as_ptr() as *mut libc::c_void,
+            iov_len: data.len(),
+        })
+        .collect();
+    let mut addrs: Vec<(libc::sockaddr_storage, libc::socklen_t)> = groups
+        .iter()
+        .map(|group| {
+            let target = datagrams[group.start].1;
+            let target = match target {
+                SocketAddr::V4(v4) if ipv6 => SocketAddr::new(IpAddr::V6(v4.ip().to_ipv6_mapped()), v4.port()),
+                _ => target,
+            };
+            from_socket_addr(target)
+        })
+        .collect();
+    let mut controls = vec![ControlBuffer::EMPTY; groups.len()];
+
+    let mut messages: Vec<libc::mmsghdr> = groups
+        .iter()
+        .zip(addrs.iter_mut())
+        .zip(controls.iter_mut())
+        .map(|((group, (addr, addr_len)), control)| {
+            // SAFETY: all-zero bytes are a valid mmsghdr
+            let mut message: libc::mmsghdr = unsafe { mem::zeroed() };
+            message.msg_hdr.msg_name = addr as *mut _ as *mut libc::c_void;
+            message.msg_hdr.msg_namelen = *addr_len;
+            message.msg_hdr.msg_iov = iovecs[group.clone()].as_ptr() as *mut libc::iovec;
+            message.msg_hdr.msg_iovlen = group.len() as _;
+            if group.len() > 1 {
+                let segment_size = datagrams[group.start].0.len() as u16;
+                set_segment_size(&mut message.msg_hdr, control, segment_size);
+            }
+            message
+        })
+        .collect();
+
+    // SAFETY: every message points at buffers that outlive the call
+    let sent = unsafe {
+        libc::sendmmsg(
+            fd,
+            messages.as_mut_ptr(),
+            messages.len() as libc::c_uint,
+            libc::MSG_DONTWAIT,
+        )
+    };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(groups[..sent as usize].iter().map(|group| group.len()).sum())
+}
+
+/// Split datagrams into the runs sent as one message each
+///
+/// A run shares a destination and a segment size; only its last datagram may
+/// be shorter. Without `gso` every datagram is its own run.
+fn gso_groups(datagrams: &[(&[u8], SocketAddr)], gso: bool) -> Vec<Range<usize>> {
+    let mut groups: Vec<Range<usize>> = Vec::new();
+    let mut bytes = 0;
+
+    for (index, (data, target)) in datagrams.iter().enumerate() {
+        if let Some(group) = groups.last_mut().filter(|_| gso) {
+            let (first, first_target) = datagrams[group.start];
+            let last = datagrams[group.end - 1].0;
+            let fits = *target == first_target
+                && !data.is_empty()
+                && data.len() <= first.len()
+                && last.len() == first.len()
+                && group.len() < GSO_MAX_SEGMENTS
+                && bytes + data.len() <= GSO_MAX_BYTES;
+            if fits {
+                group.end = index + 1;
+                bytes += data.len();
+                continue;
+            }
+        }
+        groups.push(index..index + 1);
+        bytes = data.len();
+    }
+    groups
+}
+
+/// Attach a `UDP_SEGMENT` control message to an outgoing message
+fn set_segment_size(header: &mut libc::msghdr, control: &mut ControlBuffer, segment_size: u16) {
+    header.msg_control = control.0.as_mut_ptr() as *mut libc::c_void;
+    header.msg_controllen = CONTROL_LEN as _;
+    // SAFETY: the control buffer has room for a header and an int, and is aligned for cmsghdr
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(header);
+        (*cmsg).cmsg_level = libc::SOL_UDP;
+        (*cmsg).cmsg_type = libc::UDP_SEGMENT;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<u16>() as u32) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut u16, segment_size);
+    }
+}
+
+/// Read the `UDP_GRO` segment size from a received message, if it was coalesced
+fn gro_segment_size(header: &libc::msghdr) -> Option<usize> {
+    // SAFETY: the kernel filled in msg_controllen for the control buffer we supplied
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(header);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_UDP && (*cmsg).cmsg_type == libc::UDP_GRO {
+                let size = ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int);
+                return usize::try_from(size).ok().filter(|size| *size > 0);
+            }
+            cmsg = libc::CMSG_NXTHDR(header, cmsg);
+        }
+    }
+    None
+}
+
+/// Convert a kernel socket address, reporting IPv4-mapped addresses as IPv4
+fn to_socket_addr(addr: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    match addr.ss_family as libc::c_int {
+        libc::AF_INET => {
+            // SAFETY: the family says this is a sockaddr_in
+            let addr = unsafe { &*(addr as *const _ as *const libc::sockaddr_in) };
+            let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+            Some(SocketAddr::new(IpAddr::V4(ip), u16::from_be(addr.sin_port)))
+        }
+        libc::AF_INET6 => {
+            // SAFETY: the family says this is a sockaddr_in6
+            let addr = unsafe { &*(addr as *const _ as *const libc::sockaddr_in6) };
+            let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr).to_canonical();
+            Some(SocketAddr::new(ip, u16::from_be(addr.sin6_port)))
+        }
+        _ => None,
+    }
+}
+
+/// Convert a socket address for the kernel
+fn from_socket_addr(addr: SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    // SAFETY: all-zero bytes are a valid sockaddr_storage
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(v4) => {
+            let sin = libc::sockaddr_in {
+                sin_family: libc::AF_INET as libc::sa_family_t,
+                sin_port: v4.port().to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from(*v4.ip()).to_be(),
+                },
+                sin_zero: [0; 8],
+            };
+            // SAFETY: sockaddr_storage is large enough and aligned for any address
+            unsafe { ptr::write(&mut storage as *mut _ as *mut libc::sockaddr_in, sin) };
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(v6) => {
+            let sin6 = libc::sockaddr_in6 {
+                sin6_family: libc::AF_INET6 as libc::sa_family_t,
+                sin6_port: v6.port().to_be(),
+                sin6_flowinfo: v6.flowinfo(),
+                sin6_addr: libc::in6_addr {
+                    s6_addr: v6.ip().octets(),
+                },
+                sin6_scope_id: v6.scope_id(),
+            };
+            // SAFETY: sockaddr_storage is large enough and aligned for any address
+            unsafe { ptr::write(&mut storage as *mut _ as *mut libc::sockaddr_in6, sin6) };
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, len as libc::socklen_t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+    use std::os::fd::AsRawFd;
+
+    #[test]
+    fn test_gso_groups() {
+        let a: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+        let b: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+        let (full, short) = ([0u8; 100], [0u8; 60]);
+        let datagrams = [
+            (&full[..], a),
+            (&full[..], a),
+            (&short[..], a),
+            // A shorter datagram ends the run
+            (&full[..], a),
+            (&full[..], b),
+            (&short[..], b),
+            (&short[..], b),
+        ];
+
+        assert_eq!(gso_groups(&datagrams, true), vec![0..3, 3..4, 4..6, 6..7]);
+        assert_eq!(gso_groups(&datagrams, false).len(), datagrams.len());
+
+        let many = vec![(&full[..], a); GSO_MAX_SEGMENTS + 1];
+        assert_eq!(gso_groups(&many, true), vec![0..GSO_MAX_SEGMENTS, GSO_MAX_SEGMENTS..GSO_MAX_SEGMENTS + 1]);
+    }
+
+    #[test]
+    fn test_socket_addr_round_trip() {
+        for addr in ["192.0.2.1:51820", "[2001:db8::1]:443"] {
+            let addr: SocketAddr = addr.parse().unwrap();
+            assert_eq!(to_socket_addr(&from_socket_addr(addr).0), Some(addr));
+        }
+
+        let mapped: SocketAddr = "[::ffff:192.0.2.1]:51820".parse().unwrap();
+        let canonical: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+        assert_eq!(to_socket_addr(&from_socket_addr(mapped).0), Some(canonical));
+    }
+
+    #[test]
+    fn test_batch_send_and_recv() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = receiver.local_addr().unwrap();
+
+        // GSO may be unavailable on loopback here; segmenting is the kernel's job
+        let payloads: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i; 100]).collect();
+        let datagrams: Vec<(&[u8], SocketAddr)> =
+            payloads.iter().map(|p| (p.as_slice(), target)).collect();
+        assert_eq!(send(sender.as_raw_fd(), &datagrams, false, false).unwrap(), 8);
+
+        let mut bufs = vec![vec![0u8; 2048]; 16];
+        let mut meta = vec![RecvMeta::default(); 16];
+        let mut received = Vec::new();
+        while received.len() < payloads.len() {
+            let n = recv(receiver.as_raw_fd(), &mut bufs, &mut meta).unwrap();
+            for (buf, meta) in bufs.iter().zip(&meta).take(n) {
+                assert_eq!(meta.src, sender.local_addr().unwrap());
+                received.extend(meta.datagrams(buf).map(<[u8]>::to_vec));
+            }
+        }
+        assert_eq!(received, payloads);
+
+        let error = recv(receiver.as_raw_fd(), &mut bufs, &mut meta).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+    }
+}
@@ -1,24 +1,35 @@
 //! WireGuard device implementation
 //!
 //! This module manages the core WireGuard device, integrating boringtun for
-//! the WireGuard protocol, TUN device for packet I/O, and UDP socket for
-//! network communication.
+//! the WireGuard protocol with packet I/O through [`TunIo`] (normally a TUN
+//! device) and [`UdpIo`] (normally a UDP socket).
 //!
 //! Architecture: Each WireGuard peer requires its own boringtun Tunn instance,
 //! as Tunn represents a single pairwise tunnel. We manage multiple peers by
-//! maintaining a collection of Tunn instances keyed by peer endpoint.
+//! maintaining a collection of Tunn instances keyed by peer public key, and
+//! route outbound packets to them with an allowed-IPs table (cryptokey routing).
 
+use crate::config::validation::MAX_TUN_QUEUES;
 use crate::error::{Result, WgAgentError};
-use crate::platform::Platform;
-use crate::wireguard::{KeyPair, PeerConfig};
-use boringtun::noise::{Tunn, TunnResult};
+use crate::platform::{Platform, UdpBatchSupport};
+#[cfg(target_os = "linux")]
+use crate::wireguard::batch_io;
+use crate::wireguard::io::{IoFuture, RecvMeta, TunDeviceIo, TunIo, UdpIo};
+use crate::wireguard::{AllowedIps, KeyPair, PeerConfig, PeerStats, PresharedKey};
+use boringtun::noise::errors::WireGuardError;
+use boringtun::noise::handshake::parse_handshake_anon;
+use boringtun::noise::rate_limiter::RateLimiter;
+use boringtun::noise::{Packet, Tunn, TunnResult};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-use std::io::{Read, Write};
-use std::net::{SocketAddr, UdpSocket};
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::Interest;
 use tokio::net::UdpSocket as TokioUdpSocket;
-use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
 use tokio::task::JoinHandle;
 use tokio::time;
 use tracing::{debug, error, info, warn};
@@ -31,9 +42,25 @@ const MAX_PACKET_SIZE: usize = 65535;
 /// Buffer size for TUN device reads
 const TUN_BUFFER_SIZE: usize = 2048;
 
+/// Buffer size for encrypted TUN packets: room for the 32 bytes of WireGuard
+/// data message framing, and for any handshake message
+const WG_BUFFER_SIZE: usize = TUN_BUFFER_SIZE + 32;
+
+/// Most packets read from the TUN device or the UDP socket in one batch
+const BATCH_SIZE: usize = 32;
+
 /// Timer tick interval for WireGuard operations
 const TIMER_TICK_INTERVAL: Duration = Duration::from_millis(250);
 
+/// Capacity of the device event broadcast channel
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// Sessions older than this are rejected by the protocol (WireGuard REJECT_AFTER_TIME)
+const REJECT_AFTER_TIME: Duration = Duration::from_secs(180);
+
+/// Handshake initiations per second the device handles before requiring cookies
+const HANDSHAKE_RATE_LIMIT: u64 = 100;
+
 /// WireGuard device statistics
 #[derive(Debug, Clone, Default)]
 pub struct DeviceStats {
@@ -47,8 +74,54 @@ pub struct DeviceStats {
     pub rx_packets: u64,
     /// Total errors encountered
     pub errors: u64,
+    /// Outbound packets dropped because no peer's allowed IPs matched
+    pub no_route_drops: u64,
+    /// Inbound packets dropped because the source was outside the peer's allowed IPs
+    pub source_filter_drops: u64,
+    /// Number of times a peer's endpoint was updated from an inbound packet
+    pub endpoint_updates: u64,
+    /// Handshake initiations answered with a cookie reply or dropped while under load
+    pub handshakes_rate_limited: u64,
+    /// Inbound datagrams dropped because their queue's worker was too far behind
+    pub inbound_queue_drops: u64,
+    /// Batching of the data path's UDP sends and receives
+    pub udp_batches: UdpBatchStats,
     /// Last handshake time per peer
     pub peer_handshakes: HashMap<String, Instant>,
+    /// Per-peer statistics, refreshed on every timer tick
+    pub peers: HashMap<crate::wireguard::PublicKey, PeerStats>,
+}
+
+/// Counters for the batches the data path sends and receives on the UDP socket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UdpBatchStats {
+    /// Receive calls that returned datagrams
+    pub recv_batches: u64,
+    /// Datagrams received, counting each segment of a coalesced buffer
+    pub recv_datagrams: u64,
+    /// Send calls that sent datagrams
+    pub send_batches: u64,
+    /// Datagrams sent
+    pub send_datagrams: u64,
+}
+
+impl UdpBatchStats {
+    /// Average number of datagrams per receive call
+    pub fn recv_batch_size(&self) -> f64 {
+        average(self.recv_datagrams, self.recv_batches)
+    }
+
+    /// Average number of datagrams per send call
+    pub fn send_batch_size(&self) -> f64 {
+        average(self.send_datagrams, self.send_batches)
+    }
+}
+
+fn average(total: u64, count: u64) -> f64 {
+    if count == 0 {
+        return 0.0;
+    }
+    total as f64 / count as f64
 }
 
 /// WireGuard device configuration
@@ -62,19 +135,56 @@ pub struct DeviceConfig {
     pub keypair: KeyPair,
     /// Listen port for UDP (0 = random)
     pub listen_port: u16,
+    /// Address to bind the UDP socket to (None = dual-stack on all addresses)
+    pub bind_address: Option<IpAddr>,
+    /// TUN queues, each with its own packet workers (0 = one per CPU; Linux only)
+    pub queues: usize,
     /// Peers configuration
     pub peers: Vec<PeerConfig>,
 }
 
+/// Events emitted by the device while running
+#[derive(Debug, Clone)]
+pub enum DeviceEvent {
+    /// A peer's endpoint changed after an authenticated packet arrived from a new address
+    EndpointChanged {
+        /// Peer name
+        peer: String,
+        /// Peer public key
+        public_key: crate::wireguard::PublicKey,
+        /// Previous endpoint (None if the peer had no endpoint configured)
+        old: Option<SocketAddr>,
+        /// New endpoint
+        new: SocketAddr,
+    },
+}
+
+/// Sender for the outcome of a device command
+type Reply = oneshot::Sender<Result<()>>;
+
 /// Commands for controlling the device
 #[derive(Debug)]
 enum DeviceCommand {
     /// Stop the device
     Stop,
     /// Add a new peer
-    AddPeer(PeerConfig),
+    AddPeer(PeerConfig, Reply),
     /// Remove a peer by public key
-    RemovePeer(X25519PublicKey),
+    RemovePeer(X25519PublicKey, Reply),
+    /// Point a peer at a new endpoint (e.g. after DNS re-resolution)
+    UpdateEndpoint(X25519PublicKey, SocketAddr, Reply),
+    /// Give a peer a new name
+    RenamePeer(X25519PublicKey, String, Reply),
+    /// Switch to a new static key pair
+    RotateKeys(KeyPair),
+}
+
+/// Tunnel built with our previous static key, kept until its sessions are replaced
+struct PreviousTunn {
+    /// Index the previous tunnel was created with
+    index: u32,
+    /// Boringtun tunnel instance holding the old sessions
+    tunn: Tunn,
 }
 
 /// Per-peer tunnel state
@@ -83,12 +193,22 @@ struct PeerTunnel {
     name: String,
     /// Peer's public key
     public_key: X25519PublicKey,
+    /// Local index assigned to this peer (upper 24 bits of boringtun session indices)
+    index: u32,
     /// Boringtun tunnel instance for this peer
     tunn: Tunn,
+    /// Tunnel from before the last key rotation, if its sessions are still in use
+    previous: Option<PreviousTunn>,
+    /// Preshared key, kept to rebuild the tunnel on key rotation
+    preshared_key: Option<PresharedKey>,
+    /// Persistent keepalive interval in seconds
+    keepalive: Option<u16>,
     /// Peer endpoint
     endpoint: Option<SocketAddr>,
-    /// Last activity timestamp
-    last_activity: Instant,
+    /// Byte counts (tx, rx) from tunnels discarded by key rotation
+    retired_bytes: (u64, u64),
+    /// Handshake counters and last handshake time
+    stats: PeerStats,
 }
 
 impl PeerTunnel {
@@ -100,25 +220,672 @@ impl PeerTunnel {
         index: u32,
     ) -> Result<Self> {
         let peer_public = X25519PublicKey::from(*peer_config.public_key.as_bytes());
-        
-        let tunn = Tunn::new(
+        let preshared_key = peer_config.preshared_key.clone();
+        let keepalive = peer_config.keepalive_interval.map(|d| d.as_secs() as u16);
+
+        let tunn = Self::build_tunn(
+            &name,
             local_private,
             peer_public,
-            peer_config.preshared_key,
-            peer_config.keepalive_interval.map(|d| d.as_secs() as u16),
+            &preshared_key,
+            keepalive,
             index,
-            None, // No rate limiter for now
-        )
-        .map_err(|e| WgAgentError::WireGuard(format!("Failed to create Tunn for peer '{}': {}", name, e)))?;
+        )?;
 
         Ok(Self {
             name,
             public_key: peer_public,
+            index,
             tunn,
+            previous: None,
+            preshared_key,
+            keepalive,
             endpoint: peer_config.endpoint,
-            last_activity: Instant::now(),
+            retired_bytes: (0, 0),
+            stats: PeerStats::default(),
         })
     }
+
+    /// Record an outgoing datagram, counting handshake messages we send
+    fn note_sent(&mut self, data: &[u8]) {
+        // Message types 1 (initiation) and 2 (response), little-endian u32
+        if matches!(data.first(), Some(1) | Some(2)) && data.get(1..4) == Some(&[0, 0, 0]) {
+            self.stats.handshake_attempts += 1;
+        }
+    }
+
+    /// Keep the byte counts of a tunnel that is being discarded
+    fn retire_counts(&mut self, tunn: &Tunn) {
+        let (_, tx, rx, _, _) = tunn.stats();
+        self.retired_bytes.0 += tx as u64;
+        self.retired_bytes.1 += rx as u64;
+    }
+
+    /// Read boringtun's counters and return up-to-date statistics
+    fn refresh_stats(&mut self) -> PeerStats {
+        let (since, tx, rx, _, _) = self.tunn.stats();
+        let (mut tx, mut rx) = (tx as u64 + self.retired_bytes.0, rx as u64 + self.retired_bytes.1);
+        let mut since = since;
+
+        if let Some(ref previous) = self.previous {
+            let (previous_since, previous_tx, previous_rx, _, _) = previous.tunn.stats();
+            tx += previous_tx as u64;
+            rx += previous_rx as u64;
+            since = since.or(previous_since);
+        }
+
+        if let Some(handshake_at) = since.and_then(|d| SystemTime::now().checked_sub(d)) {
+            // Sub-second drift between ticks is not a new handshake
+            let is_new = self
+                .stats
+                .last_handshake
+                .is_none_or(|last| handshake_at > last + Duration::from_secs(1));
+            if is_new {
+                self.stats.successful_handshakes += 1;
+            }
+            self.stats.last_handshake = Some(handshake_at);
+        }
+
+        self.stats.tx_bytes = tx;
+        self.stats.rx_bytes = rx;
+        self.stats.endpoint = self.endpoint;
+        self.stats.clone()
+    }
+
+    /// Create a boringtun tunnel instance
+    ///
+    /// Initiations reaching the tunnel have already passed the device's
+    /// limiter, so the tunnel gets a limiter of its own rather than counting
+    /// them against the device's budget a second time. It also covers
+    /// responses, whose MACs may be keyed by a key from before a rotation.
+    fn build_tunn(
+        name: &str,
+        local_private: StaticSecret,
+        peer_public: X25519PublicKey,
+        preshared_key: &Option<PresharedKey>,
+        keepalive: Option<u16>,
+        index: u32,
+    ) -> Result<Tunn> {
+        Tunn::new(
+            local_private,
+            peer_public,
+            preshared_key.as_ref().map(|k| *k.as_bytes()),
+            keepalive,
+            index,
+            None,
+        )
+        .map_err(|e| WgAgentError::WireGuard(format!("Failed to create Tunn for peer '{}': {}", name, e)))
+    }
+
+    /// Replace the tunnel with one using a new static key under a new index.
+    ///
+    /// The old tunnel is kept so established sessions keep carrying traffic
+    /// until a handshake with the new key completes. Returns the indices of
+    /// tunnels that are no longer in use.
+    fn rotate(
+        &mut self,
+        local_private: StaticSecret,
+        index: u32,
+    ) -> Result<Vec<u32>> {
+        let tunn = Self::build_tunn(
+            &self.name,
+            local_private,
+            self.public_key,
+            &self.preshared_key,
+            self.keepalive,
+            index,
+        )?;
+
+        let old_tunn = std::mem::replace(&mut self.tunn, tunn);
+        let old_index = std::mem::replace(&mut self.index, index);
+        let mut retired = Vec::new();
+
+        // A tunnel left over from an earlier rotation is superseded either way
+        if let Some(previous) = self.previous.take() {
+            self.retire_counts(&previous.tunn);
+            retired.push(previous.index);
+        }
+
+        // Only keep the old tunnel if it actually has a live session
+        let live = old_tunn
+            .time_since_last_handshake()
+            .is_some_and(|t| t < REJECT_AFTER_TIME);
+        if live {
+            self.previous = Some(PreviousTunn {
+                index: old_index,
+                tunn: old_tunn,
+            });
+        } else {
+            self.retire_counts(&old_tunn);
+            retired.push(old_index);
+        }
+
+        Ok(retired)
+    }
+
+    /// Tunnel to encapsulate outbound packets with: the previous tunnel while
+    /// the current one has not completed a handshake yet
+    fn outbound_tunn(&mut self) -> &mut Tunn {
+        match self.previous {
+            Some(ref mut previous) if self.tunn.time_since_last_handshake().is_none() => {
+                &mut previous.tunn
+            }
+            _ => &mut self.tunn,
+        }
+    }
+
+    /// Tunnel that owns the session an inbound datagram is addressed to
+    fn inbound_tunn(&mut self, datagram: &[u8]) -> &mut Tunn {
+        let receiver_idx = match Tunn::parse_incoming_packet(datagram) {
+            Ok(Packet::HandshakeResponse(p)) => Some(p.receiver_idx),
+            Ok(Packet::PacketCookieReply(p)) => Some(p.receiver_idx),
+            Ok(Packet::PacketData(p)) => Some(p.receiver_idx),
+            _ => None,
+        };
+
+        match self.previous {
+            Some(ref mut previous) if receiver_idx.map(|i| i >> 8) == Some(previous.index) => {
+                &mut previous.tunn
+            }
+            _ => &mut self.tunn,
+        }
+    }
+
+    /// Drop the previous tunnel once the new one has a session or the old
+    /// sessions have expired, returning its index
+    fn retire_previous(&mut self) -> Option<u32> {
+        let previous = self.previous.as_ref()?;
+        let replaced = self.tunn.time_since_last_handshake().is_some();
+        let expired = previous
+            .tunn
+            .time_since_last_handshake()
+            .is_none_or(|t| t >= REJECT_AFTER_TIME);
+
+        if replaced || expired {
+            let previous = self.previous.take()?;
+            self.retire_counts(&previous.tunn);
+            Some(previous.index)
+        } else {
+            None
+        }
+    }
+}
+
+/// Peer tunnel shared between the device tasks, locked per packet
+type SharedPeer = Arc<Mutex<PeerTunnel>>;
+
+/// Peers and the lookup tables used to route packets to them
+#[derive(Clone, Default)]
+struct PeerTable {
+    /// Peer tunnels keyed by peer public key
+    peers: HashMap<X25519PublicKey, SharedPeer>,
+    /// Peer index to public key mapping for identifying inbound packets
+    indices: HashMap<u32, X25519PublicKey>,
+    /// Allowed IPs routing table mapping prefixes to peer public keys
+    allowed_ips: AllowedIps<X25519PublicKey>,
+}
+
+impl PeerTable {
+    /// Add a peer tunnel, replacing any peer with the same key.
+    ///
+    /// Fails if an allowed IP is invalid or already routed to another peer;
+    /// the table is then partly changed and must be discarded.
+    fn insert(&mut self, peer_tunnel: PeerTunnel, allowed_ips: &[String]) -> Result<()> {
+        let public_key = peer_tunnel.public_key;
+        self.remove(&public_key);
+
+        for allowed_ip in allowed_ips {
+            let Some(owner) = self.allowed_ips.insert_cidr(allowed_ip, public_key)? else {
+                continue;
+            };
+            if owner != public_key {
+                let owner = self.peers.get(&owner).map(|p| p.lock().unwrap().name.clone());
+                return Err(WgAgentError::Config(format!(
+                    "Allowed IP {} of peer '{}' is already routed to peer '{}'",
+                    allowed_ip,
+                    peer_tunnel.name,
+                    owner.unwrap_or_default()
+                )));
+            }
+        }
+        self.indices.insert(peer_tunnel.index, public_key);
+        self.peers.insert(public_key, Arc::new(Mutex::new(peer_tunnel)));
+        Ok(())
+    }
+
+    /// Remove a peer and every route and index pointing at it
+    fn remove(&mut self, public_key: &X25519PublicKey) -> Option<SharedPeer> {
+        let removed = self.peers.remove(public_key)?;
+        self.indices.retain(|_, key| key != public_key);
+        self.allowed_ips.remove_value(public_key);
+        Some(removed)
+    }
+
+    /// Peer owning a session index (the upper 24 bits of a receiver index)
+    fn by_index(&self, index: u32) -> Option<X25519PublicKey> {
+        self.indices.get(&index).copied()
+    }
+}
+
+/// Peer table shared by the device tasks
+///
+/// Packet tasks work on a snapshot of the table and only lock the peer they
+/// are handling, so a busy peer does not hold up the others. Adding, removing
+/// or re-keying peers builds a new table and swaps it in.
+#[derive(Default)]
+struct SharedPeerTable {
+    current: std::sync::RwLock<Arc<PeerTable>>,
+}
+
+impl SharedPeerTable {
+    fn new(table: PeerTable) -> Self {
+        Self {
+            current: std::sync::RwLock::new(Arc::new(table)),
+        }
+    }
+
+    /// Current table; later changes are not visible through it
+    fn snapshot(&self) -> Arc<PeerTable> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    /// Apply a change to a copy of the table and publish it
+    fn update<R>(&self, change: impl FnOnce(&mut PeerTable) -> R) -> R {
+        let mut current = self.current.write().unwrap();
+        let mut table = PeerTable::clone(&current);
+        let result = change(&mut table);
+        *current = Arc::new(table);
+        result
+    }
+
+    /// Apply a change to a copy of the table and publish it unless it fails
+    fn try_update<R>(&self, change: impl FnOnce(&mut PeerTable) -> Result<R>) -> Result<R> {
+        let mut current = self.current.write().unwrap();
+        let mut table = PeerTable::clone(&current);
+        let result = change(&mut table)?;
+        *current = Arc::new(table);
+        Ok(result)
+    }
+}
+
+/// Device counters, updated from the packet tasks without locking
+#[derive(Debug, Default)]
+struct DeviceCounters {
+    tx_bytes: AtomicU64,
+    rx_bytes: AtomicU64,
+    tx_packets: AtomicU64,
+    rx_packets: AtomicU64,
+    errors: AtomicU64,
+    no_route_drops: AtomicU64,
+    source_filter_drops: AtomicU64,
+    endpoint_updates: AtomicU64,
+    handshakes_rate_limited: AtomicU64,
+    inbound_queue_drops: AtomicU64,
+    recv_batches: AtomicU64,
+    recv_datagrams: AtomicU64,
+    send_batches: AtomicU64,
+    send_datagrams: AtomicU64,
+}
+
+impl DeviceCounters {
+    fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a datagram sent to a peer
+    fn sent(&self, bytes: usize) {
+        self.tx_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        Self::increment(&self.tx_packets);
+    }
+
+    /// Count a packet delivered to the TUN device
+    fn delivered(&self, bytes: usize) {
+        self.rx_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        Self::increment(&self.rx_packets);
+    }
+
+    /// Count a batch of datagrams received from the UDP socket
+    fn recv_batch(&self, datagrams: usize) {
+        self.recv_datagrams.fetch_add(datagrams as u64, Ordering::Relaxed);
+        Self::increment(&self.recv_batches);
+    }
+
+    /// Count a batch of datagrams sent on the UDP socket
+    fn send_batch(&self, datagrams: usize) {
+        self.send_datagrams.fetch_add(datagrams as u64, Ordering::Relaxed);
+        Self::increment(&self.send_batches);
+    }
+
+    /// Read the counters into a statistics snapshot without per-peer data
+    fn snapshot(&self) -> DeviceStats {
+        DeviceStats {
+            tx_bytes: self.tx_bytes.load(Ordering::Relaxed),
+            rx_bytes: self.rx_bytes.load(Ordering::Relaxed),
+            tx_packets: self.tx_packets.load(Ordering::Relaxed),
+            rx_packets: self.rx_packets.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            no_route_drops: self.no_route_drops.load(Ordering::Relaxed),
+            source_filter_drops: self.source_filter_drops.load(Ordering::Relaxed),
+            endpoint_updates: self.endpoint_updates.load(Ordering::Relaxed),
+            handshakes_rate_limited: self.handshakes_rate_limited.load(Ordering::Relaxed),
+            inbound_queue_drops: self.inbound_queue_drops.load(Ordering::Relaxed),
+            udp_batches: UdpBatchStats {
+                recv_batches: self.recv_batches.load(Ordering::Relaxed),
+                recv_datagrams: self.recv_datagrams.load(Ordering::Relaxed),
+                send_batches: self.send_batches.load(Ordering::Relaxed),
+                send_datagrams: self.send_datagrams.load(Ordering::Relaxed),
+            },
+            ..DeviceStats::default()
+        }
+    }
+}
+
+/// Datagrams handed from the receiving task to an inbound worker
+type InboundBatch = Vec<(Vec<u8>, SocketAddr)>;
+
+/// Batches an inbound worker may have waiting before further ones are dropped
+const INBOUND_QUEUE_DEPTH: usize = 64;
+
+/// Device-wide handshake rate limiter, replaced on key rotation
+type SharedRateLimiter = Arc<RwLock<Arc<RateLimiter>>>;
+
+/// Create the handshake rate limiter for a static key
+///
+/// MAC1 is keyed by our public key, so the limiter is replaced on key rotation.
+fn handshake_rate_limiter(local_private: &StaticSecret) -> Arc<RateLimiter> {
+    Arc::new(RateLimiter::new(
+        &X25519PublicKey::from(local_private),
+        HANDSHAKE_RATE_LIMIT,
+    ))
+}
+
+/// State used to identify peers and route inbound packets
+struct InboundRouting {
+    /// Peer table
+    peers: Arc<SharedPeerTable>,
+    /// Our static private key, used to identify handshake initiators
+    local_private: Arc<RwLock<StaticSecret>>,
+    /// Verifies handshake MACs and issues cookies under load
+    rate_limiter: SharedRateLimiter,
+    /// Device event sender
+    event_tx: broadcast::Sender<DeviceEvent>,
+}
+
+impl InboundRouting {
+    /// Identify the peer that sent a datagram.
+    ///
+    /// Data, handshake response and cookie reply packets carry our receiver index;
+    /// handshake initiations are identified by decrypting the initiator's static key.
+    /// Returns the peer key and whether the packet may update the peer's endpoint.
+    async fn identify_peer(
+        &self,
+        table: &PeerTable,
+        datagram: &[u8],
+    ) -> Option<(X25519PublicKey, bool)> {
+        let receiver_idx = match Tunn::parse_incoming_packet(datagram).ok()? {
+            Packet::HandshakeInit(init) => {
+                let local_private = self.local_private.read().await;
+                let local_public = X25519PublicKey::from(&*local_private);
+                let half = parse_handshake_anon(&local_private, &local_public, &init).ok()?;
+                return Some((X25519PublicKey::from(half.peer_static_public), true));
+            }
+            Packet::HandshakeResponse(response) => response.receiver_idx,
+            // Cookie replies are not authenticated by the peer's static key
+            Packet::PacketCookieReply(cookie) => {
+                return table.by_index(cookie.receiver_idx >> 8).map(|key| (key, false));
+            }
+            Packet::PacketData(data) => data.receiver_idx,
+        };
+
+        table.by_index(receiver_idx >> 8).map(|key| (key, true))
+    }
+
+    /// Record a new endpoint for a peer after an authenticated packet from `src`
+    fn update_endpoint(
+        &self,
+        peer_tunnel: &mut PeerTunnel,
+        src: SocketAddr,
+        counters: &DeviceCounters,
+    ) {
+        if peer_tunnel.endpoint == Some(src) {
+            return;
+        }
+
+        let old = peer_tunnel.endpoint.replace(src);
+        DeviceCounters::increment(&counters.endpoint_updates);
+        info!(
+            "Peer '{}' endpoint changed: {} -> {}",
+            peer_tunnel.name,
+            old.map(|e| e.to_string()).unwrap_or_else(|| "none".to_string()),
+            src
+        );
+
+        // No subscribers is not an error
+        let _ = self.event_tx.send(DeviceEvent::EndpointChanged {
+            peer: peer_tunnel.name.clone(),
+            public_key: crate::wireguard::PublicKey::from_bytes(peer_tunnel.public_key.to_bytes()),
+            old,
+            new: src,
+        });
+    }
+}
+
+/// UDP socket for WireGuard traffic
+///
+/// A socket bound to `[::]` also serves IPv4 peers; their addresses are
+/// mapped to and from `::ffff:a.b.c.d` so the rest of the device only sees
+/// plain IPv4 endpoints.
+///
+/// On Linux, datagrams are moved in batches with `recvmmsg`/`sendmmsg`, using
+/// UDP GSO and GRO when the kernel supports them.
+pub(crate) struct DeviceSocket {
+    socket: TokioUdpSocket,
+    ipv6: bool,
+    /// Batched I/O features in use on this socket
+    batch: UdpBatchSupport,
+    /// Cleared if the kernel rejects a segmented send, e.g. for lack of
+    /// checksum offload on the outgoing interface
+    gso: AtomicBool,
+}
+
+impl DeviceSocket {
+    /// Bind to `bind_address:port`, or dual-stack `[::]:port` when no address is given
+    pub(crate) fn bind(bind_address: Option<IpAddr>, port: u16) -> Result<Self> {
+        let std_socket = match bind_address {
+            Some(ip) => Self::bind_std(ip, port)?,
+            None => Self::bind_std(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port).or_else(|e| {
+                warn!("IPv6 UDP socket unavailable ({}), using IPv4 only", e);
+                Self::bind_std(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port)
+            })?,
+        };
+
+        let local_addr = std_socket.local_addr().map_err(|e| {
+            WgAgentError::Platform(format!("Failed to get UDP socket local address: {}", e))
+        })?;
+        let socket = TokioUdpSocket::from_std(std_socket).map_err(|e| {
+            WgAgentError::Platform(format!("Failed to create tokio UdpSocket: {}", e))
+        })?;
+
+        let batch = Self::enable_batching(&socket);
+        debug!("UDP batching on {}: {:?}", local_addr, batch);
+
+        Ok(Self {
+            socket,
+            ipv6: local_addr.is_ipv6(),
+            batch,
+            gso: AtomicBool::new(batch.gso),
+        })
+    }
+
+    /// Turn on the batched I/O features the kernel supports
+    #[cfg(target_os = "linux")]
+    fn enable_batching(socket: &TokioUdpSocket) -> UdpBatchSupport {
+        use std::os::fd::AsRawFd;
+
+        let mut batch = crate::platform::udp_batch_support();
+        if batch.gro {
+            if let Err(e) = batch_io::enable_gro(socket.as_raw_fd()) {
+                debug!("UDP GRO unavailable: {}", e);
+                batch.gro = false;
+            }
+        }
+        batch
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn enable_batching(_socket: &TokioUdpSocket) -> UdpBatchSupport {
+        UdpBatchSupport::default()
+    }
+
+    /// Map an IPv4 destination on an IPv6 socket
+    fn map_target(&self, target: SocketAddr) -> SocketAddr {
+        match target {
+            SocketAddr::V4(v4) if self.ipv6 => {
+                SocketAddr::new(IpAddr::V6(v4.ip().to_ipv6_mapped()), v4.port())
+            }
+            _ => target,
+        }
+    }
+
+    fn bind_std(ip: IpAddr, port: u16) -> Result<UdpSocket> {
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        let addr = SocketAddr::new(ip, port);
+        let bind_error = |e: std::io::Error| {
+            WgAgentError::Platform(format!("Failed to bind UDP socket to {}: {}", addr, e))
+        };
+
+        let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))
+            .map_err(bind_error)?;
+        if ip == IpAddr::V6(Ipv6Addr::UNSPECIFIED) {
+            // Accept IPv4 as well, regardless of net.ipv6.bindv6only
+            socket.set_only_v6(false).map_err(bind_error)?;
+        }
+        socket.set_nonblocking(true).map_err(bind_error)?;
+        socket.bind(&addr.into()).map_err(bind_error)?;
+
+        Ok(socket.into())
+    }
+}
+
+impl UdpIo for DeviceSocket {
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Send a datagram, mapping IPv4 destinations on an IPv6 socket
+    fn send_to<'a>(&'a self, data: &'a [u8], target: SocketAddr) -> IoFuture<'a, usize> {
+        Box::pin(self.socket.send_to(data, self.map_target(target)))
+    }
+
+    /// Receive a datagram, reporting IPv4-mapped sources as IPv4
+    ///
+    /// With GRO enabled, only the first datagram of a coalesced buffer is
+    /// returned; use [`UdpIo::recv_batch`] to receive every datagram.
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> IoFuture<'a, (usize, SocketAddr)> {
+        Box::pin(async move {
+            let (n, src) = self.socket.recv_from(buf).await?;
+            Ok((n, SocketAddr::new(src.ip().to_canonical(), src.port())))
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn recv_batch<'a>(
+        &'a self,
+        bufs: &'a mut [Vec<u8>],
+        meta: &'a mut [RecvMeta],
+    ) -> IoFuture<'a, usize> {
+        use std::os::fd::AsRawFd;
+
+        Box::pin(async move {
+            if !self.batch.mmsg {
+                let (len, src) = self.recv_from(&mut bufs[0]).await?;
+                meta[0] = RecvMeta { len, src, stride: len };
+                return Ok(1);
+            }
+
+            let fd = self.socket.as_raw_fd();
+            self.socket
+                .async_io(Interest::READABLE, || batch_io::recv(fd, bufs, meta))
+                .await
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn send_batch<'a>(&'a self, datagrams: &'a [(&'a [u8], SocketAddr)]) -> IoFuture<'a, usize> {
+        use std::os::fd::AsRawFd;
+
+        Box::pin(async move {
+            if !self.batch.mmsg {
+                let (data, target) = datagrams[0];
+                self.send_to(data, target).await?;
+                return Ok(1);
+            }
+
+            let fd = self.socket.as_raw_fd();
+            let send = |gso| {
+                self.socket.async_io(Interest::WRITABLE, move || {
+                    batch_io::send(fd, datagrams, gso, self.ipv6)
+                })
+            };
+            match send(self.gso.load(Ordering::Relaxed)).await {
+                Err(e) if e.raw_os_error() == Some(libc::EIO) && self.gso.load(Ordering::Relaxed) => {
+                    warn!("UDP GSO send failed ({}), sending datagrams individually", e);
+                    self.gso.store(false, Ordering::Relaxed);
+                    send(false).await
+                }
+                result => result,
+            }
+        })
+    }
+}
+
+/// Inbound worker that handles datagrams from `src`, out of `workers`
+///
+/// Hashing the outer source keeps each peer's datagrams on one worker, in the
+/// order they arrived.
+fn endpoint_worker(src: SocketAddr, workers: usize) -> usize {
+    if workers == 1 {
+        return 0;
+    }
+    let mut hasher = DefaultHasher::new();
+    src.hash(&mut hasher);
+    (hasher.finish() % workers as u64) as usize
+}
+
+/// TUN queue to write a decrypted packet to, out of `queues`
+///
+/// Hashes the packet's addresses and protocol, plus the ports of
+/// unfragmented TCP and UDP, so one peer's flows are spread over the queues
+/// while each flow stays on one of them.
+fn packet_queue(packet: &[u8], queues: usize) -> usize {
+    const TCP: u8 = 6;
+    const UDP: u8 = 17;
+
+    if queues == 1 {
+        return 0;
+    }
+    let mut hasher = DefaultHasher::new();
+    let (protocol, transport) = match packet.first().map(|b| b >> 4) {
+        Some(4) if packet.len() >= 20 => {
+            packet[12..20].hash(&mut hasher);
+            // More fragments flag and fragment offset; only a whole
+            // datagram carries its ports
+            let fragmented = u16::from_be_bytes([packet[6], packet[7]]) & 0x3fff != 0;
+            let header_len = usize::from(packet[0] & 0x0f) * 4;
+            (packet[9], packet.get(header_len..).filter(|_| !fragmented))
+        }
+        Some(6) if packet.len() >= 40 => {
+            packet[8..40].hash(&mut hasher);
+            (packet[6], packet.get(40..))
+        }
+        _ => return 0,
+    };
+    protocol.hash(&mut hasher);
+    if let (TCP | UDP, Some(ports)) = (protocol, transport.and_then(|t| t.get(..4))) {
+        ports.hash(&mut hasher);
+    }
+    (hasher.finish() % queues as u64) as usize
 }
 
 /// WireGuard device managing the tunnel
@@ -127,18 +894,18 @@ pub struct WgDevice {
     config: DeviceConfig,
     /// Actual interface name (may differ from config.interface on macOS)
     actual_interface: String,
-    /// TUN device for packet I/O (wrapped in Mutex for mut access)
-    tun_device: Arc<Mutex<tun::platform::Device>>,
-    /// UDP socket for network communication
-    udp_socket: Arc<TokioUdpSocket>,
-    /// Per-peer tunnel instances, keyed by peer public key
-    peer_tunnels: Arc<RwLock<HashMap<X25519PublicKey, PeerTunnel>>>,
-    /// Endpoint to public key mapping for fast lookup
-    endpoint_map: Arc<RwLock<HashMap<SocketAddr, X25519PublicKey>>>,
-    /// Device statistics
-    stats: Arc<RwLock<DeviceStats>>,
+    /// IP packet side (normally the TUN device), one per queue
+    tun_queues: Vec<Arc<dyn TunIo>>,
+    /// Encrypted datagram side (normally the UDP socket)
+    udp_socket: Arc<dyn UdpIo>,
+    /// Peers and routing tables
+    peers: Arc<SharedPeerTable>,
+    /// Device counters
+    counters: Arc<DeviceCounters>,
     /// Command channel sender
     cmd_tx: mpsc::UnboundedSender<DeviceCommand>,
+    /// Device event sender
+    event_tx: broadcast::Sender<DeviceEvent>,
     /// Task handles for cleanup
     task_handles: Vec<JoinHandle<()>>,
 }
@@ -151,15 +918,10 @@ impl WgDevice {
     ) -> Result<Self> {
         info!("Creating WireGuard device for interface: {}", config.interface);
 
-        // Validate that we have at least one peer
-        if config.peers.is_empty() {
-            return Err(WgAgentError::Config(
-                "At least one peer must be configured".to_string()
-            ));
-        }
-
         // Create TUN device using platform-specific implementation
-        let tun_device = platform.create_tun_device(&config.interface, config.mtu)?;
+        let queues = Self::queue_count(config.queues);
+        let tun_device =
+            platform.create_multiqueue_tun_device(&config.interface, config.mtu, queues)?;
         
         // Get the actual interface name (may differ on macOS)
         let actual_interface = tun_device.name().map_err(|e| {
@@ -171,42 +933,85 @@ impl WgDevice {
             WgAgentError::TunDevice(format!("Failed to set TUN device to non-blocking: {}", e))
         })?;
 
-        // Wrap in Arc<Mutex> for concurrent access
-        let tun_device = Arc::new(Mutex::new(tun_device));
-
         // Create UDP socket for WireGuard communication
-        let listen_addr: SocketAddr = format!("0.0.0.0:{}", config.listen_port)
-            .parse()
-            .map_err(|e| WgAgentError::Config(format!("Invalid listen port: {}", e)))?;
+        let udp_socket = DeviceSocket::bind(config.bind_address, config.listen_port)?;
 
-        let std_socket = UdpSocket::bind(listen_addr).map_err(|e| {
-            WgAgentError::Platform(format!("Failed to bind UDP socket to {}: {}", listen_addr, e))
+        let local_addr = udp_socket.local_addr().map_err(|e| {
+            WgAgentError::Platform(format!("Failed to get UDP socket local address: {}", e))
         })?;
 
-        std_socket.set_nonblocking(true).map_err(|e| {
-            WgAgentError::Platform(format!("Failed to set UDP socket to non-blocking: {}", e))
+        info!(
+            "UDP socket listening on {} (requested port: {})",
+            local_addr, config.listen_port
+        );
+
+        #[cfg(target_os = "linux")]
+        let tun_queues = TunDeviceIo::queues(tun_device);
+        #[cfg(not(target_os = "linux"))]
+        let tun_queues = TunDeviceIo::new(tun_device).map(|queue| vec![queue]);
+        let tun_queues = tun_queues.map_err(|e| {
+            WgAgentError::TunDevice(format!("Failed to register TUN device for async I/O: {}", e))
         })?;
+        info!("Using {} TUN queue(s)", tun_queues.len());
 
-        let udp_socket = Arc::new(TokioUdpSocket::from_std(std_socket).map_err(|e| {
-            WgAgentError::Platform(format!("Failed to create tokio UdpSocket: {}", e))
-        })?);
+        let tun_queues = tun_queues
+            .into_iter()
+            .map(|queue| Arc::new(queue) as Arc<dyn TunIo>)
+            .collect();
+        Self::from_parts(config, actual_interface, tun_queues, Arc::new(udp_socket)).await
+    }
 
-        let actual_port = udp_socket.local_addr().map_err(|e| {
-            WgAgentError::Platform(format!("Failed to get UDP socket local address: {}", e))
-        })?.port();
+    /// Create a device that exchanges packets through the given I/O
+    /// implementations instead of a TUN device and UDP socket
+    pub async fn with_io(
+        config: DeviceConfig,
+        tun_device: Arc<dyn TunIo>,
+        udp_socket: Arc<dyn UdpIo>,
+    ) -> Result<Self> {
+        Self::with_queues(config, vec![tun_device], udp_socket).await
+    }
 
-        info!(
-            "UDP socket listening on port {} (requested: {})",
-            actual_port, config.listen_port
-        );
+    /// Like [`WgDevice::with_io`], with one packet worker per TUN queue
+    pub async fn with_queues(
+        config: DeviceConfig,
+        tun_queues: Vec<Arc<dyn TunIo>>,
+        udp_socket: Arc<dyn UdpIo>,
+    ) -> Result<Self> {
+        info!("Creating WireGuard device for interface: {}", config.interface);
+        if tun_queues.is_empty() {
+            return Err(WgAgentError::Config("At least one TUN queue is required".to_string()));
+        }
+
+        let actual_interface = config.interface.clone();
+        Self::from_parts(config, actual_interface, tun_queues, udp_socket).await
+    }
 
+    /// Number of TUN queues to open: as configured, or one per CPU for 0
+    ///
+    /// Only Linux supports multi-queue TUN devices.
+    fn queue_count(configured: usize) -> usize {
+        if !cfg!(target_os = "linux") {
+            return 1;
+        }
+        match configured {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        }
+        .min(MAX_TUN_QUEUES)
+    }
+
+    async fn from_parts(
+        config: DeviceConfig,
+        actual_interface: String,
+        tun_queues: Vec<Arc<dyn TunIo>>,
+        udp_socket: Arc<dyn UdpIo>,
+    ) -> Result<Self> {
         // Convert our private key to x25519 StaticSecret
         let local_private = StaticSecret::from(*config.keypair.private.as_bytes());
+        let rate_limiter = handshake_rate_limiter(&local_private);
 
         // Create peer tunnels
-        let mut peer_tunnels = HashMap::new();
-        let mut endpoint_map = HashMap::new();
-
+        let mut table = PeerTable::default();
         for (index, peer_config) in config.peers.iter().enumerate() {
             let peer_tunnel = PeerTunnel::new(
                 peer_config.name.clone(),
@@ -214,93 +1019,124 @@ impl WgDevice {
                 peer_config,
                 index as u32,
             )?;
-
-            if let Some(endpoint) = peer_config.endpoint {
-                endpoint_map.insert(endpoint, peer_tunnel.public_key);
-            }
-
+            table.insert(peer_tunnel, &peer_config.allowed_ips)?;
             info!("Created tunnel for peer: {}", peer_config.name);
-            peer_tunnels.insert(peer_tunnel.public_key, peer_tunnel);
         }
 
-        let peer_tunnels = Arc::new(RwLock::new(peer_tunnels));
-        let endpoint_map = Arc::new(RwLock::new(endpoint_map));
-
-        // Create command channel
+        // Create command and event channels
         let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
-
-        let stats = Arc::new(RwLock::new(DeviceStats::default()));
+        let (event_tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
 
         info!("WireGuard device created successfully with {} peers", config.peers.len());
 
         let mut device = Self {
             config,
             actual_interface,
-            tun_device,
+            tun_queues,
             udp_socket,
-            peer_tunnels,
-            endpoint_map,
-            stats,
+            peers: Arc::new(SharedPeerTable::new(table)),
+            counters: Arc::new(DeviceCounters::default()),
             cmd_tx,
+            event_tx,
             task_handles: Vec::new(),
         };
 
         // Start packet processing tasks
-        device.start_tasks(cmd_rx).await;
+        device
+            .start_tasks(cmd_rx, Arc::new(RwLock::new(rate_limiter)))
+            .await;
 
         Ok(device)
     }
 
     /// Start all packet processing tasks
-    async fn start_tasks(&mut self, cmd_rx: mpsc::UnboundedReceiver<DeviceCommand>) {
-        // Spawn outbound task (TUN -> encrypt -> UDP)
-        let outbound_handle = {
-            let tun_device = Arc::clone(&self.tun_device);
+    async fn start_tasks(
+        &mut self,
+        cmd_rx: mpsc::UnboundedReceiver<DeviceCommand>,
+        rate_limiter: SharedRateLimiter,
+    ) {
+        // Shared so key rotation is seen by handshake identification
+        let local_private = Arc::new(RwLock::new(StaticSecret::from(
+            *self.config.keypair.private.as_bytes(),
+        )));
+
+        // Spawn an outbound task per TUN queue (TUN -> encrypt -> UDP); the
+        // kernel keeps each flow on one queue
+        for tun_device in &self.tun_queues {
+            let tun_device = Arc::clone(tun_device);
             let udp_socket = Arc::clone(&self.udp_socket);
-            let peer_tunnels = Arc::clone(&self.peer_tunnels);
-            let stats = Arc::clone(&self.stats);
+            let peers = Arc::clone(&self.peers);
+            let counters = Arc::clone(&self.counters);
 
-            tokio::spawn(async move {
-                Self::outbound_task(tun_device, udp_socket, peer_tunnels, stats).await;
-            })
-        };
+            self.task_handles.push(tokio::spawn(async move {
+                Self::outbound_task(tun_device, udp_socket, peers, counters).await;
+            }));
+        }
+
+        // Spawn an inbound worker per TUN queue (decrypt -> TUN); each writes
+        // to whichever queue a packet's flow hashes to
+        let routing = Arc::new(InboundRouting {
+            peers: Arc::clone(&self.peers),
+            local_private: Arc::clone(&local_private),
+            rate_limiter: Arc::clone(&rate_limiter),
+            event_tx: self.event_tx.clone(),
+        });
+        let mut workers = Vec::new();
+        for _ in &self.tun_queues {
+            let (worker_tx, worker_rx) = mpsc::channel(INBOUND_QUEUE_DEPTH);
+            workers.push(worker_tx);
+
+            let tun_queues = self.tun_queues.clone();
+            let udp_socket = Arc::clone(&self.udp_socket);
+            let routing = Arc::clone(&routing);
+            let counters = Arc::clone(&self.counters);
+
+            self.task_handles.push(tokio::spawn(async move {
+                Self::inbound_worker_task(worker_rx, tun_queues, udp_socket, routing, counters)
+                    .await;
+            }));
+        }
 
-        // Spawn inbound task (UDP -> decrypt -> TUN)
+        // Spawn inbound task (UDP -> workers), which only receives datagrams
+        // and hands them to the workers
         let inbound_handle = {
-            let tun_device = Arc::clone(&self.tun_device);
             let udp_socket = Arc::clone(&self.udp_socket);
-            let peer_tunnels = Arc::clone(&self.peer_tunnels);
-            let endpoint_map = Arc::clone(&self.endpoint_map);
-            let stats = Arc::clone(&self.stats);
+            let counters = Arc::clone(&self.counters);
 
             tokio::spawn(async move {
-                Self::inbound_task(tun_device, udp_socket, peer_tunnels, endpoint_map, stats).await;
+                Self::inbound_task(udp_socket, counters, workers).await;
             })
         };
 
         // Spawn timer task for keepalive and rekey
         let timer_handle = {
             let udp_socket = Arc::clone(&self.udp_socket);
-            let peer_tunnels = Arc::clone(&self.peer_tunnels);
-            let stats = Arc::clone(&self.stats);
+            let peers = Arc::clone(&self.peers);
+            let rate_limiter = Arc::clone(&rate_limiter);
+            let counters = Arc::clone(&self.counters);
 
             tokio::spawn(async move {
-                Self::timer_task(udp_socket, peer_tunnels, stats).await;
+                Self::timer_task(udp_socket, peers, rate_limiter, counters).await;
             })
         };
 
         // Spawn command handler task
         let command_handle = {
-            let peer_tunnels = Arc::clone(&self.peer_tunnels);
-            let endpoint_map = Arc::clone(&self.endpoint_map);
-            let local_private = StaticSecret::from(*self.config.keypair.private.as_bytes());
+            let udp_socket = Arc::clone(&self.udp_socket);
+            let routing = InboundRouting {
+                peers: Arc::clone(&self.peers),
+                local_private,
+                rate_limiter,
+                event_tx: self.event_tx.clone(),
+            };
+            let next_index = self.config.peers.len() as u32;
+            let counters = Arc::clone(&self.counters);
 
             tokio::spawn(async move {
-                Self::command_task(cmd_rx, peer_tunnels, endpoint_map, local_private).await;
+                Self::command_task(cmd_rx, udp_socket, routing, counters, next_index).await;
             })
         };
 
-        self.task_handles.push(outbound_handle);
         self.task_handles.push(inbound_handle);
         self.task_handles.push(timer_handle);
         self.task_handles.push(command_handle);
@@ -310,191 +1146,412 @@ impl WgDevice {
 
     /// Outbound packet processing: TUN -> encrypt -> UDP
     async fn outbound_task(
-        tun_device: Arc<Mutex<tun::platform::Device>>,
-        udp_socket: Arc<TokioUdpSocket>,
-        peer_tunnels: Arc<RwLock<HashMap<X25519PublicKey, PeerTunnel>>>,
-        stats: Arc<RwLock<DeviceStats>>,
+        tun_device: Arc<dyn TunIo>,
+        udp_socket: Arc<dyn UdpIo>,
+        peers: Arc<SharedPeerTable>,
+        counters: Arc<DeviceCounters>,
     ) {
         info!("Outbound task started");
-        let mut tun_buffer = vec![0u8; TUN_BUFFER_SIZE];
-        let mut wg_buffer = vec![0u8; MAX_PACKET_SIZE];
+        let mut tun_buffers = vec![vec![0u8; TUN_BUFFER_SIZE]; BATCH_SIZE];
+        let mut sizes = [0usize; BATCH_SIZE];
+        let mut wg_buffers = vec![vec![0u8; WG_BUFFER_SIZE]; BATCH_SIZE];
 
         loop {
-            // Read from TUN device
-            let n = {
-                let mut device_guard = tun_device.lock().await;
-                match device_guard.read(&mut tun_buffer) {
-                    Ok(n) => n,
-                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                        drop(device_guard);
-                        time::sleep(Duration::from_millis(10)).await;
-                        continue;
-                    }
-                    Err(e) => {
-                        error!("TUN read error: {}", e);
-                        stats.write().await.errors += 1;
-                        continue;
-                    }
+            // Read as many packets from the TUN device as are ready
+            let count = match tun_device.read_batch(&mut tun_buffers, &mut sizes).await {
+                Ok(count) => count,
+                Err(e) => {
+                    error!("TUN read error: {}", e);
+                    DeviceCounters::increment(&counters.errors);
+                    time::sleep(Duration::from_millis(100)).await;
+                    continue;
                 }
             };
 
-            if n == 0 {
-                continue;
-            }
-
-            debug!("Read {} bytes from TUN device", n);
+            debug!("Read {} packets from TUN device", count);
+
+            // Encrypt the whole batch, then send it together
+            let table = peers.snapshot();
+            let datagrams: Vec<(&[u8], SocketAddr)> = tun_buffers
+                .iter()
+                .zip(&sizes)
+                .take(count)
+                .zip(wg_buffers.iter_mut())
+                .filter_map(|((packet, &n), wg_buffer)| {
+                    Self::encapsulate(&table, &packet[..n], wg_buffer, &counters)
+                })
+                .collect();
+
+            Self::send_datagrams(udp_socket.as_ref(), &datagrams, &counters).await;
+        }
+    }
 
-            // Try to encapsulate with each peer until one succeeds
-            let mut peer_tunnels_guard = peer_tunnels.write().await;
-            let mut sent = false;
+    /// Encrypt an outbound packet for the peer owning its destination address
+    ///
+    /// Returns the datagram and the endpoint to send it to, or None if the
+    /// packet was dropped or queued until a handshake completes.
+    fn encapsulate<'a>(
+        table: &PeerTable,
+        packet: &[u8],
+        wg_buffer: &'a mut [u8],
+        counters: &DeviceCounters,
+    ) -> Option<(&'a [u8], SocketAddr)> {
+        if packet.is_empty() {
+            return None;
+        }
 
-            for peer_tunnel in peer_tunnels_guard.values_mut() {
-                match peer_tunnel.tunn.encapsulate(&tun_buffer[..n], &mut wg_buffer) {
-                    TunnResult::Done => {
-                        debug!("Packet encapsulated (no output) for peer {}", peer_tunnel.name);
-                    }
-                    TunnResult::Err(e) => {
-                        debug!("Encapsulation error for peer {}: {:?}", peer_tunnel.name, e);
-                    }
-                    TunnResult::WriteToNetwork(data) => {
-                        // Send encrypted packet over UDP
-                        if let Some(endpoint) = peer_tunnel.endpoint {
-                            match udp_socket.send_to(data, endpoint).await {
-                                Ok(sent_bytes) => {
-                                    debug!("Sent {} bytes to {} (peer: {})", sent_bytes, endpoint, peer_tunnel.name);
-                                    let mut stats_guard = stats.write().await;
-                                    stats_guard.tx_bytes += sent_bytes as u64;
-                                    stats_guard.tx_packets += 1;
-                                    peer_tunnel.last_activity = Instant::now();
-                                    sent = true;
-                                    break;
-                                }
-                                Err(e) => {
-                                    warn!("UDP send error to {}: {}", endpoint, e);
-                                    stats.write().await.errors += 1;
-                                }
-                            }
-                        }
-                    }
-                    TunnResult::WriteToTunnelV4(_, _) | TunnResult::WriteToTunnelV6(_, _) => {
-                        debug!("Unexpected WriteToTunnel result in outbound path");
-                    }
+        // Cryptokey routing: find the peer owning the destination address
+        let peer = match Tunn::dst_address(packet) {
+            Some(dst) => match table.allowed_ips.lookup(dst).and_then(|key| table.peers.get(key)) {
+                Some(peer) => peer,
+                None => {
+                    debug!("No peer for destination {}, dropping packet", dst);
+                    DeviceCounters::increment(&counters.no_route_drops);
+                    return None;
                 }
+            },
+            None => {
+                debug!("Dropping packet with unparseable destination address");
+                DeviceCounters::increment(&counters.no_route_drops);
+                return None;
+            }
+        };
+
+        let mut peer_tunnel = peer.lock().unwrap();
+        match peer_tunnel.outbound_tunn().encapsulate(packet, wg_buffer) {
+            TunnResult::Done => {
+                debug!("Packet encapsulated (no output) for peer {}", peer_tunnel.name);
+                None
             }
+            TunnResult::Err(e) => {
+                debug!("Encapsulation error for peer {}: {:?}", peer_tunnel.name, e);
+                DeviceCounters::increment(&counters.errors);
+                None
+            }
+            TunnResult::WriteToNetwork(data) => {
+                let Some(endpoint) = peer_tunnel.endpoint else {
+                    debug!("Peer {} has no endpoint, dropping packet", peer_tunnel.name);
+                    return None;
+                };
+                peer_tunnel.note_sent(data);
+                Some((data, endpoint))
+            }
+            TunnResult::WriteToTunnelV4(_, _) | TunnResult::WriteToTunnelV6(_, _) => {
+                debug!("Unexpected WriteToTunnel result in outbound path");
+                None
+            }
+        }
+    }
 
-            if !sent {
-                debug!("Packet not sent - no peer could encapsulate");
+    /// Send datagrams in as few calls as the socket allows
+    ///
+    /// A datagram that fails to send is counted as an error and skipped.
+    async fn send_datagrams(
+        udp_socket: &dyn UdpIo,
+        datagrams: &[(&[u8], SocketAddr)],
+        counters: &DeviceCounters,
+    ) {
+        let mut next = 0;
+        while next < datagrams.len() {
+            match udp_socket.send_batch(&datagrams[next..]).await {
+                Ok(sent) => {
+                    debug!("Sent {} datagrams in one batch", sent);
+                    for (data, _) in &datagrams[next..next + sent] {
+                        counters.sent(data.len());
+                    }
+                    counters.send_batch(sent);
+                    next += sent;
+                }
+                Err(e) => {
+                    warn!("UDP send error to {}: {}", datagrams[next].1, e);
+                    DeviceCounters::increment(&counters.errors);
+                    next += 1;
+                }
             }
         }
     }
 
-    /// Inbound packet processing: UDP -> decrypt -> TUN
+    /// Inbound packet processing: UDP -> workers
+    ///
+    /// Datagrams are spread over the inbound workers by hashing their source
+    /// endpoint, so each peer's datagrams stay in order. Decryption happens
+    /// on the workers, leaving this task free to keep receiving; the workers
+    /// pick a TUN queue from each decrypted packet's own flow.
     async fn inbound_task(
-        tun_device: Arc<Mutex<tun::platform::Device>>,
-        udp_socket: Arc<TokioUdpSocket>,
-        peer_tunnels: Arc<RwLock<HashMap<X25519PublicKey, PeerTunnel>>>,
-        endpoint_map: Arc<RwLock<HashMap<SocketAddr, X25519PublicKey>>>,
-        stats: Arc<RwLock<DeviceStats>>,
+        udp_socket: Arc<dyn UdpIo>,
+        counters: Arc<DeviceCounters>,
+        workers: Vec<mpsc::Sender<InboundBatch>>,
     ) {
         info!("Inbound task started");
-        let mut udp_buffer = vec![0u8; MAX_PACKET_SIZE];
-        let mut tun_buffer = vec![0u8; MAX_PACKET_SIZE];
+        let mut udp_buffers = vec![vec![0u8; MAX_PACKET_SIZE]; BATCH_SIZE];
+        let mut meta = [RecvMeta::default(); BATCH_SIZE];
+        let mut batches: Vec<InboundBatch> = vec![Vec::new(); workers.len()];
 
         loop {
-            // Read from UDP socket
-            let (n, src) = match udp_socket.recv_from(&mut udp_buffer).await {
-                Ok((n, src)) => (n, src),
+            // Receive as many datagrams from the UDP socket as are waiting
+            let count = match udp_socket.recv_batch(&mut udp_buffers, &mut meta).await {
+                Ok(count) => count,
                 Err(e) => {
                     error!("UDP recv error: {}", e);
-                    stats.write().await.errors += 1;
+                    DeviceCounters::increment(&counters.errors);
                     time::sleep(Duration::from_millis(100)).await;
                     continue;
                 }
             };
 
-            debug!("Received {} bytes from {}", n, src);
+            let mut total = 0;
+            for (buf, meta) in udp_buffers.iter().zip(&meta).take(count) {
+                let batch = &mut batches[endpoint_worker(meta.src, workers.len())];
+                for datagram in meta.datagrams(buf) {
+                    total += 1;
+                    batch.push((datagram.to_vec(), meta.src));
+                }
+            }
+            counters.recv_batch(total);
+            for (worker, batch) in workers.iter().zip(&mut batches) {
+                if !batch.is_empty() {
+                    Self::hand_off(worker, std::mem::take(batch), &counters);
+                }
+            }
+        }
+    }
 
-            // Look up which peer this is from
-            let endpoint_map_guard = endpoint_map.read().await;
-            let peer_key = endpoint_map_guard.get(&src).copied();
-            drop(endpoint_map_guard);
+    /// Queue a batch for an inbound worker without waiting
+    ///
+    /// A worker that has fallen behind must not stall the receiving task and
+    /// with it every other queue, so its datagrams are dropped instead, as a
+    /// full socket buffer would; WireGuard and the tunneled protocols recover.
+    fn hand_off(
+        worker: &mpsc::Sender<InboundBatch>,
+        batch: InboundBatch,
+        counters: &DeviceCounters,
+    ) {
+        match worker.try_send(batch) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(batch)) => {
+                debug!("Inbound worker queue full, dropping {} datagrams", batch.len());
+                counters
+                    .inbound_queue_drops
+                    .fetch_add(batch.len() as u64, Ordering::Relaxed);
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                warn!("Inbound worker stopped, dropping datagrams");
+            }
+        }
+    }
 
-            let peer_key = match peer_key {
-                Some(key) => key,
-                None => {
-                    debug!("Received packet from unknown endpoint: {}", src);
-                    continue;
-                }
-            };
+    /// Inbound worker: decrypt -> TUN
+    async fn inbound_worker_task(
+        mut rx: mpsc::Receiver<InboundBatch>,
+        tun_queues: Vec<Arc<dyn TunIo>>,
+        udp_socket: Arc<dyn UdpIo>,
+        routing: Arc<InboundRouting>,
+        counters: Arc<DeviceCounters>,
+    ) {
+        let mut tun_buffer = vec![0u8; MAX_PACKET_SIZE];
 
-            // Decrypt packet with the peer's tunnel
-            let mut peer_tunnels_guard = peer_tunnels.write().await;
-            let peer_tunnel = match peer_tunnels_guard.get_mut(&peer_key) {
-                Some(pt) => pt,
-                None => {
-                    debug!("Peer tunnel not found for endpoint: {}", src);
-                    continue;
-                }
-            };
+        while let Some(batch) = rx.recv().await {
+            for (datagram, src) in batch {
+                debug!("Received {} bytes from {}", datagram.len(), src);
+                Self::handle_datagram(
+                    &datagram,
+                    src,
+                    &tun_queues,
+                    udp_socket.as_ref(),
+                    &routing,
+                    &mut tun_buffer,
+                    &counters,
+                )
+                .await;
+            }
+        }
+    }
 
-            match peer_tunnel.tunn.decapsulate(Some(src.ip()), &udp_buffer[..n], &mut tun_buffer) {
-                TunnResult::Done => {
-                    debug!("Packet decapsulated (no output)");
-                }
-                TunnResult::Err(e) => {
-                    warn!("Decapsulation error from {}: {:?}", src, e);
-                    stats.write().await.errors += 1;
-                }
-                TunnResult::WriteToNetwork(data) => {
-                    // Response packet to send back
-                    match udp_socket.send_to(data, src).await {
-                        Ok(sent) => {
-                            debug!("Sent response {} bytes to {}", sent, src);
-                            let mut stats_guard = stats.write().await;
-                            stats_guard.tx_bytes += sent as u64;
-                            stats_guard.tx_packets += 1;
-                            peer_tunnel.last_activity = Instant::now();
-                        }
+    /// Decrypt a datagram from `src` and deliver or answer it
+    async fn handle_datagram(
+        datagram: &[u8],
+        src: SocketAddr,
+        tun_queues: &[Arc<dyn TunIo>],
+        udp_socket: &dyn UdpIo,
+        routing: &InboundRouting,
+        tun_buffer: &mut [u8],
+        counters: &DeviceCounters,
+    ) {
+        // Check MACs and load before the DH that identifies an initiator.
+        // Responses are left to the peer's tunnel, which after a key rotation
+        // may still expect MACs keyed by the previous key.
+        if let Ok(Packet::HandshakeInit(_)) = Tunn::parse_incoming_packet(datagram) {
+            let rate_limiter = Arc::clone(&*routing.rate_limiter.read().await);
+            match rate_limiter.verify_packet(Some(src.ip()), datagram, tun_buffer) {
+                Ok(_) => {}
+                Err(TunnResult::WriteToNetwork(cookie)) => {
+                    debug!("Under load, sending cookie reply to {}", src);
+                    DeviceCounters::increment(&counters.handshakes_rate_limited);
+                    match udp_socket.send_to(cookie, src).await {
+                        Ok(sent) => counters.sent(sent),
                         Err(e) => {
                             warn!("UDP send error to {}: {}", src, e);
-                            stats.write().await.errors += 1;
+                            DeviceCounters::increment(&counters.errors);
                         }
                     }
+                    return;
                 }
-                TunnResult::WriteToTunnelV4(data, _) | TunnResult::WriteToTunnelV6(data, _) => {
-                    // Write decrypted packet to TUN device
-                    drop(peer_tunnels_guard); // Release lock before waiting for TUN
-
-                    let mut device_guard = tun_device.lock().await;
-                    match device_guard.write(data) {
-                        Ok(written) => {
-                            debug!("Wrote {} bytes to TUN device", written);
-                            let mut stats_guard = stats.write().await;
-                            stats_guard.rx_bytes += written as u64;
-                            stats_guard.rx_packets += 1;
-                        }
-                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                            debug!("TUN write would block");
-                        }
-                        Err(e) => {
-                            error!("TUN write error: {}", e);
-                            stats.write().await.errors += 1;
-                        }
+                Err(TunnResult::Err(WireGuardError::UnderLoad)) => {
+                    DeviceCounters::increment(&counters.handshakes_rate_limited);
+                    return;
+                }
+                Err(e) => {
+                    debug!("Dropping handshake initiation from {}: {:?}", src, e);
+                    return;
+                }
+            }
+        }
+
+        // Identify the peer from the packet itself, not the source address,
+        // so peers can roam between endpoints
+        let table = routing.peers.snapshot();
+        let (peer_key, roamable) = match routing.identify_peer(&table, datagram).await {
+            Some(found) => found,
+            None => {
+                debug!("Received packet from unknown peer at {}", src);
+                return;
+            }
+        };
+        let Some(peer) = table.peers.get(&peer_key) else {
+            debug!("Peer tunnel not found for packet from {}", src);
+            return;
+        };
+
+        // Decrypt under the peer's lock, send or deliver after releasing it
+        let result = {
+            let mut peer_tunnel = peer.lock().unwrap();
+            let result = peer_tunnel
+                .inbound_tunn(datagram)
+                .decapsulate(Some(src.ip()), datagram, tun_buffer);
+
+            // The packet authenticated, so its source is the peer's current endpoint
+            if roamable && !matches!(result, TunnResult::Err(_)) {
+                routing.update_endpoint(&mut peer_tunnel, src, counters);
+            }
+            if let TunnResult::WriteToNetwork(ref data) = result {
+                peer_tunnel.note_sent(data);
+            }
+            result
+        };
+
+        match result {
+            TunnResult::Done => {
+                debug!("Packet decapsulated (no output)");
+            }
+            TunnResult::Err(e) => {
+                warn!("Decapsulation error from {}: {:?}", src, e);
+                DeviceCounters::increment(&counters.errors);
+            }
+            TunnResult::WriteToNetwork(data) => {
+                // Response packet to send back
+                match udp_socket.send_to(data, src).await {
+                    Ok(sent) => {
+                        debug!("Sent response {} bytes to {}", sent, src);
+                        counters.sent(sent);
+                    }
+                    Err(e) => {
+                        warn!("UDP send error to {}: {}", src, e);
+                        DeviceCounters::increment(&counters.errors);
                     }
-                    drop(device_guard);
+                }
 
-                    // Continue to next iteration
-                    continue;
+                Self::flush_queued(peer, datagram, src, udp_socket, tun_buffer, counters).await;
+            }
+            TunnResult::WriteToTunnelV4(data, src_ip) => {
+                if Self::source_allowed(&table, &peer_key, src_ip.into(), counters) {
+                    Self::write_to_tun(tun_queues, data, counters).await;
+                }
+            }
+            TunnResult::WriteToTunnelV6(data, src_ip) => {
+                if Self::source_allowed(&table, &peer_key, src_ip.into(), counters) {
+                    Self::write_to_tun(tun_queues, data, counters).await;
                 }
             }
         }
     }
 
+    /// Send the packets boringtun queued while the handshake was in progress
+    ///
+    /// After a handshake message produces a response, boringtun expects to be
+    /// called again with an empty datagram until it has nothing left to send.
+    async fn flush_queued(
+        peer: &SharedPeer,
+        datagram: &[u8],
+        dst: SocketAddr,
+        udp_socket: &dyn UdpIo,
+        buf: &mut [u8],
+        counters: &DeviceCounters,
+    ) {
+        loop {
+            let data = {
+                let mut peer_tunnel = peer.lock().unwrap();
+                match peer_tunnel.inbound_tunn(datagram).decapsulate(None, &[], buf) {
+                    TunnResult::WriteToNetwork(data) => {
+                        peer_tunnel.note_sent(data);
+                        data
+                    }
+                    _ => break,
+                }
+            };
+
+            match udp_socket.send_to(data, dst).await {
+                Ok(sent) => {
+                    debug!("Sent queued {} bytes to {}", sent, dst);
+                    counters.sent(sent);
+                }
+                Err(e) => {
+                    warn!("UDP send error to {}: {}", dst, e);
+                    DeviceCounters::increment(&counters.errors);
+                }
+            }
+        }
+    }
+
+    /// Check that a decrypted packet's source address belongs to the peer it came from
+    fn source_allowed(
+        table: &PeerTable,
+        peer_key: &X25519PublicKey,
+        src_ip: IpAddr,
+        counters: &DeviceCounters,
+    ) -> bool {
+        if table.allowed_ips.lookup(src_ip) == Some(peer_key) {
+            return true;
+        }
+
+        debug!("Dropping inbound packet from {}: not in peer's allowed IPs", src_ip);
+        DeviceCounters::increment(&counters.source_filter_drops);
+        false
+    }
+
+    /// Write a decrypted packet to the TUN queue its flow hashes to
+    async fn write_to_tun(tun_queues: &[Arc<dyn TunIo>], data: &[u8], counters: &DeviceCounters) {
+        let tun_device = &tun_queues[packet_queue(data, tun_queues.len())];
+        match tun_device.write(data).await {
+            Ok(written) => {
+                debug!("Wrote {} bytes to TUN device", written);
+                counters.delivered(written);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                debug!("TUN write would block");
+            }
+            Err(e) => {
+                error!("TUN write error: {}", e);
+                DeviceCounters::increment(&counters.errors);
+            }
+        }
+    }
+
     /// Timer task for keepalive and rekey operations
     async fn timer_task(
-        udp_socket: Arc<TokioUdpSocket>,
-        peer_tunnels: Arc<RwLock<HashMap<X25519PublicKey, PeerTunnel>>>,
-        stats: Arc<RwLock<DeviceStats>>,
+        udp_socket: Arc<dyn UdpIo>,
+        peers: Arc<SharedPeerTable>,
+        rate_limiter: SharedRateLimiter,
+        counters: Arc<DeviceCounters>,
     ) {
         info!("Timer task started");
         let mut interval = time::interval(TIMER_TICK_INTERVAL);
@@ -503,38 +1560,88 @@ impl WgDevice {
         loop {
             interval.tick().await;
 
-            let mut peer_tunnels_guard = peer_tunnels.write().await;
-            
-            for peer_tunnel in peer_tunnels_guard.values_mut() {
-                match peer_tunnel.tunn.update_timers(&mut wg_buffer) {
-                    TunnResult::Done => {
-                        // No action needed
-                    }
-                    TunnResult::Err(e) => {
-                        debug!("Timer update error for peer {}: {:?}", peer_tunnel.name, e);
+            // The limiter only resets once a second however often it is asked
+            rate_limiter.read().await.reset_count();
+
+            let table = peers.snapshot();
+            let mut retired = Vec::new();
+
+            for peer in table.peers.values() {
+                // Keep sessions from before a key rotation alive until replaced
+                let previous_packet = {
+                    let mut peer_tunnel = peer.lock().unwrap();
+                    if let Some(old_index) = peer_tunnel.retire_previous() {
+                        debug!("Retiring pre-rotation tunnel for peer {}", peer_tunnel.name);
+                        retired.push(old_index);
                     }
-                    TunnResult::WriteToNetwork(data) => {
-                        // Send keepalive or rekey packet
-                        if let Some(endpoint) = peer_tunnel.endpoint {
-                            match udp_socket.send_to(data, endpoint).await {
-                                Ok(sent) => {
-                                    debug!("Sent timer packet {} bytes to {} (peer: {})", sent, endpoint, peer_tunnel.name);
-                                    let mut stats_guard = stats.write().await;
-                                    stats_guard.tx_bytes += sent as u64;
-                                    drop(stats_guard);
-                                    peer_tunnel.last_activity = Instant::now();
-                                }
-                                Err(e) => {
-                                    warn!("UDP send error in timer task: {}", e);
-                                    stats.write().await.errors += 1;
-                                }
+                    let endpoint = peer_tunnel.endpoint;
+                    let packet = match (peer_tunnel.previous.as_mut(), endpoint) {
+                        (Some(previous), Some(endpoint)) => {
+                            match previous.tunn.update_timers(&mut wg_buffer) {
+                                TunnResult::WriteToNetwork(data) => Some((data, endpoint)),
+                                _ => None,
                             }
                         }
+                        _ => None,
+                    };
+                    if let Some((data, _)) = &packet {
+                        peer_tunnel.note_sent(data);
                     }
-                    TunnResult::WriteToTunnelV4(_, _) | TunnResult::WriteToTunnelV6(_, _) => {
-                        debug!("Unexpected WriteToTunnel result in timer task");
+                    packet
+                };
+                if let Some((data, endpoint)) = previous_packet {
+                    match udp_socket.send_to(data, endpoint).await {
+                        Ok(sent) => counters.sent(sent),
+                        Err(e) => {
+                            warn!("UDP send error in timer task: {}", e);
+                            DeviceCounters::increment(&counters.errors);
+                        }
                     }
                 }
+
+                let packet = {
+                    let mut peer_tunnel = peer.lock().unwrap();
+                    let packet = match peer_tunnel.tunn.update_timers(&mut wg_buffer) {
+                        TunnResult::Done => None,
+                        TunnResult::Err(e) => {
+                            debug!("Timer update error for peer {}: {:?}", peer_tunnel.name, e);
+                            None
+                        }
+                        TunnResult::WriteToNetwork(data) => {
+                            // Keepalive or rekey packet
+                            peer_tunnel.note_sent(data);
+                            peer_tunnel.endpoint.map(|endpoint| (data, endpoint))
+                        }
+                        TunnResult::WriteToTunnelV4(_, _) | TunnResult::WriteToTunnelV6(_, _) => {
+                            debug!("Unexpected WriteToTunnel result in timer task");
+                            None
+                        }
+                    };
+
+                    // Publish per-peer statistics for `stats()`
+                    peer_tunnel.refresh_stats();
+                    packet
+                };
+                if let Some((data, endpoint)) = packet {
+                    match udp_socket.send_to(data, endpoint).await {
+                        Ok(sent) => {
+                            debug!("Sent timer packet {} bytes to {}", sent, endpoint);
+                            counters.sent(sent);
+                        }
+                        Err(e) => {
+                            warn!("UDP send error in timer task: {}", e);
+                            DeviceCounters::increment(&counters.errors);
+                        }
+                    }
+                }
+            }
+
+            if !retired.is_empty() {
+                peers.update(|table| {
+                    for index in &retired {
+                        table.indices.remove(index);
+                    }
+                });
             }
         }
     }
@@ -542,12 +1649,12 @@ impl WgDevice {
     /// Command processing task
     async fn command_task(
         mut cmd_rx: mpsc::UnboundedReceiver<DeviceCommand>,
-        peer_tunnels: Arc<RwLock<HashMap<X25519PublicKey, PeerTunnel>>>,
-        endpoint_map: Arc<RwLock<HashMap<SocketAddr, X25519PublicKey>>>,
-        local_private: StaticSecret,
+        udp_socket: Arc<dyn UdpIo>,
+        routing: InboundRouting,
+        counters: Arc<DeviceCounters>,
+        mut next_index: u32,
     ) {
         info!("Command task started");
-        let mut next_index = 1000u32; // Start peer indices at 1000
 
         while let Some(cmd) = cmd_rx.recv().await {
             match cmd {
@@ -555,42 +1662,124 @@ impl WgDevice {
                     info!("Received stop command");
                     break;
                 }
-                DeviceCommand::AddPeer(peer_config) => {
+                DeviceCommand::AddPeer(peer_config, reply) => {
                     info!("Adding peer: {}", peer_config.name);
-                    
-                    match PeerTunnel::new(
+
+                    let local_private = routing.local_private.read().await.clone();
+                    let result = PeerTunnel::new(
                         peer_config.name.clone(),
-                        local_private.clone(),
+                        local_private,
                         &peer_config,
                         next_index,
-                    ) {
-                        Ok(peer_tunnel) => {
-                            let public_key = peer_tunnel.public_key;
-                            
-                            if let Some(endpoint) = peer_config.endpoint {
-                                endpoint_map.write().await.insert(endpoint, public_key);
-                            }
-
-                            peer_tunnels.write().await.insert(public_key, peer_tunnel);
+                    )
+                    .and_then(|peer_tunnel| {
+                        routing
+                            .peers
+                            .try_update(|table| table.insert(peer_tunnel, &peer_config.allowed_ips))
+                    });
+                    match result {
+                        Ok(()) => {
                             next_index += 1;
                             info!("Peer '{}' added successfully", peer_config.name);
                         }
-                        Err(e) => {
-                            error!("Failed to add peer '{}': {}", peer_config.name, e);
-                        }
+                        Err(ref e) => error!("Failed to add peer '{}': {}", peer_config.name, e),
                     }
+                    // The caller may have stopped waiting
+                    let _ = reply.send(result);
                 }
-                DeviceCommand::RemovePeer(public_key) => {
+                DeviceCommand::RemovePeer(public_key, reply) => {
                     info!("Removing peer with public key");
-                    
-                    if let Some(removed) = peer_tunnels.write().await.remove(&public_key) {
-                        if let Some(endpoint) = removed.endpoint {
-                            endpoint_map.write().await.remove(&endpoint);
+
+                    let result = match routing.peers.update(|table| table.remove(&public_key)) {
+                        Some(removed) => {
+                            info!("Peer '{}' removed successfully", removed.lock().unwrap().name);
+                            Ok(())
+                        }
+                        None => Err(WgAgentError::NotFound("Peer not found for removal".to_string())),
+                    };
+                    let _ = reply.send(result);
+                }
+                DeviceCommand::UpdateEndpoint(public_key, endpoint, reply) => {
+                    let result = match routing.peers.snapshot().peers.get(&public_key) {
+                        Some(peer) => {
+                            let mut peer_tunnel = peer.lock().unwrap();
+                            routing.update_endpoint(&mut peer_tunnel, endpoint, &counters);
+                            Ok(())
+                        }
+                        None => Err(WgAgentError::NotFound(
+                            "Peer not found for endpoint update".to_string(),
+                        )),
+                    };
+                    let _ = reply.send(result);
+                }
+                DeviceCommand::RenamePeer(public_key, name, reply) => {
+                    let result = match routing.peers.snapshot().peers.get(&public_key) {
+                        Some(peer) => {
+                            let mut peer_tunnel = peer.lock().unwrap();
+                            info!("Peer '{}' renamed to '{}'", peer_tunnel.name, name);
+                            peer_tunnel.name = name;
+                            Ok(())
+                        }
+                        None => Err(WgAgentError::NotFound("Peer not found for rename".to_string())),
+                    };
+                    let _ = reply.send(result);
+                }
+                DeviceCommand::RotateKeys(keypair) => {
+                    info!("Rotating device static key");
+                    let local_private = StaticSecret::from(*keypair.private.as_bytes());
+                    *routing.rate_limiter.write().await = handshake_rate_limiter(&local_private);
+                    *routing.local_private.write().await = local_private.clone();
+
+                    // Re-key every peer, then publish the new indices before any
+                    // handshake that could be answered on them is sent
+                    let table = routing.peers.snapshot();
+                    let mut retired = Vec::new();
+                    let mut assigned = Vec::new();
+                    for (public_key, peer) in &table.peers {
+                        let mut peer_tunnel = peer.lock().unwrap();
+                        let rotated = peer_tunnel.rotate(local_private.clone(), next_index);
+                        match rotated {
+                            Ok(indices) => {
+                                retired.extend(indices);
+                                assigned.push((next_index, *public_key));
+                                next_index += 1;
+                            }
+                            Err(e) => {
+                                error!("Failed to rotate key for peer '{}': {}", peer_tunnel.name, e);
+                            }
                         }
-                        info!("Peer '{}' removed successfully", removed.name);
-                    } else {
-                        warn!("Peer not found for removal");
                     }
+                    routing.peers.update(|table| {
+                        for index in &retired {
+                            table.indices.remove(index);
+                        }
+                        table.indices.extend(assigned.iter().copied());
+                    });
+
+                    // Start the new handshakes right away rather than on the next packet
+                    let mut wg_buffer = vec![0u8; MAX_PACKET_SIZE];
+                    for peer in table.peers.values() {
+                        let initiation = {
+                            let mut peer_tunnel = peer.lock().unwrap();
+                            match (peer_tunnel.endpoint, peer_tunnel.tunn.format_handshake_initiation(&mut wg_buffer, false)) {
+                                (Some(endpoint), TunnResult::WriteToNetwork(data)) => {
+                                    peer_tunnel.note_sent(data);
+                                    Some((data, endpoint, peer_tunnel.name.clone()))
+                                }
+                                _ => None,
+                            }
+                        };
+                        if let Some((data, endpoint, name)) = initiation {
+                            match udp_socket.send_to(data, endpoint).await {
+                                Ok(sent) => counters.sent(sent),
+                                Err(e) => {
+                                    warn!("Failed to send handshake to peer '{}': {}", name, e);
+                                    DeviceCounters::increment(&counters.errors);
+                                }
+                            }
+                        }
+                    }
+                    info!("Static key rotated for {} peers", table.peers.len());
                 }
             }
         }
@@ -603,25 +1792,99 @@ impl WgDevice {
         &self.actual_interface
     }
 
+    /// Get the address the UDP socket is bound to
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.udp_socket.local_addr().ok()
+    }
+
+    /// Check that every packet processing task is still running
+    pub fn is_alive(&self) -> bool {
+        self.task_handles.iter().all(|handle| !handle.is_finished())
+    }
+
     /// Get device statistics
     pub async fn stats(&self) -> DeviceStats {
-        self.stats.read().await.clone()
+        let mut stats = self.counters.snapshot();
+        let now = Instant::now();
+
+        for peer in self.peers.snapshot().peers.values() {
+            let peer_tunnel = peer.lock().unwrap();
+            let mut peer_stats = peer_tunnel.stats.clone();
+            // The endpoint may have roamed since the last timer tick
+            peer_stats.endpoint = peer_tunnel.endpoint;
+            if let Some(elapsed) = peer_stats.last_handshake.and_then(|t| t.elapsed().ok()) {
+                if let Some(at) = now.checked_sub(elapsed) {
+                    stats.peer_handshakes.insert(peer_tunnel.name.clone(), at);
+                }
+            }
+            stats.peers.insert(
+                crate::wireguard::PublicKey::from_bytes(peer_tunnel.public_key.to_bytes()),
+                peer_stats,
+            );
+        }
+        stats
+    }
+
+    /// Subscribe to device events (e.g. endpoint changes)
+    pub fn subscribe(&self) -> broadcast::Receiver<DeviceEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Send a command and wait for the command task to carry it out
+    async fn request(&self, name: &str, command: impl FnOnce(Reply) -> DeviceCommand) -> Result<()> {
+        let (reply, outcome) = oneshot::channel();
+        self.cmd_tx.send(command(reply)).map_err(|e| {
+            WgAgentError::WireGuard(format!("Failed to send {} command: {}", name, e))
+        })?;
+        outcome.await.map_err(|_| {
+            WgAgentError::WireGuard(format!("Device stopped before handling {} command", name))
+        })?
     }
 
     /// Add a peer dynamically
+    ///
+    /// Fails, leaving the device unchanged, if an allowed IP is invalid or
+    /// already routed to another peer.
     pub async fn add_peer(&self, peer: PeerConfig) -> Result<()> {
-        self.cmd_tx
-            .send(DeviceCommand::AddPeer(peer))
-            .map_err(|e| WgAgentError::WireGuard(format!("Failed to send AddPeer command: {}", e)))
+        self.request("AddPeer", |reply| DeviceCommand::AddPeer(peer, reply))
+            .await
     }
 
     /// Remove a peer dynamically
     pub async fn remove_peer(&self, public_key: &crate::wireguard::PublicKey) -> Result<()> {
         let x25519_key = X25519PublicKey::from(*public_key.as_bytes());
+        self.request("RemovePeer", |reply| DeviceCommand::RemovePeer(x25519_key, reply))
+            .await
+    }
+
+    /// Set a peer's endpoint
+    pub async fn update_endpoint(
+        &self,
+        public_key: &crate::wireguard::PublicKey,
+        endpoint: SocketAddr,
+    ) -> Result<()> {
+        let x25519_key = X25519PublicKey::from(*public_key.as_bytes());
+        self.request("UpdateEndpoint", |reply| {
+            DeviceCommand::UpdateEndpoint(x25519_key, endpoint, reply)
+        })
+        .await
+    }
+
+    /// Change the name a peer is logged and reported under
+    pub async fn rename_peer(&self, public_key: &crate::wireguard::PublicKey, name: &str) -> Result<()> {
+        let x25519_key = X25519PublicKey::from(*public_key.as_bytes());
+        let name = name.to_string();
+        self.request("RenamePeer", |reply| DeviceCommand::RenamePeer(x25519_key, name, reply))
+            .await
+    }
+
+    /// Switch to a new static key pair, keeping existing sessions until
+    /// handshakes with the new key complete
+    pub async fn rotate_keys(&self, keypair: KeyPair) -> Result<()> {
         self.cmd_tx
-            .send(DeviceCommand::RemovePeer(x25519_key))
+            .send(DeviceCommand::RotateKeys(keypair))
             .map_err(|e| {
-                WgAgentError::WireGuard(format!("Failed to send RemovePeer command: {}", e))
+                WgAgentError::WireGuard(format!("Failed to send RotateKeys command: {}", e))
             })
     }
 
@@ -632,20 +1895,464 @@ impl WgDevice {
         // Send stop command
         let _ = self.cmd_tx.send(DeviceCommand::Stop);
 
-        // Wait for all tasks to complete (with timeout)
-        let timeout = Duration::from_secs(5);
-        let results = time::timeout(timeout, async {
-            for handle in self.task_handles.drain(..) {
-                let _ = handle.await;
+        // The command task is spawned last and exits on Stop once earlier
+        // commands are handled; give it a bounded time to do so
+        if let Some(mut command_handle) = self.task_handles.pop() {
+            let timeout = Duration::from_secs(5);
+            if time::timeout(timeout, &mut command_handle).await.is_err() {
+                warn!("Timeout waiting for command task to stop, aborting it");
+                command_handle.abort();
             }
-        })
-        .await;
+        }
 
-        if results.is_err() {
-            warn!("Timeout waiting for tasks to stop, aborting remaining tasks");
+        // The packet and timer tasks run until aborted
+        for handle in self.task_handles.drain(..) {
+            handle.abort();
+            let _ = handle.await;
         }
 
         info!("WireGuard device stopped");
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wireguard::{MemorySocket, MemoryTun};
+
+    /// Run a handshake between a peer tunnel and a remote boringtun instance
+    fn handshake(peer_tunnel: &mut PeerTunnel, remote: &mut Tunn) {
+        let mut buf = vec![0u8; MAX_PACKET_SIZE];
+        let mut remote_buf = vec![0u8; MAX_PACKET_SIZE];
+
+        let init = match peer_tunnel.tunn.format_handshake_initiation(&mut buf, false) {
+            TunnResult::WriteToNetwork(data) => data.to_vec(),
+            _ => panic!("expected handshake initiation"),
+        };
+        let response = match remote.decapsulate(None, &init, &mut remote_buf) {
+            TunnResult::WriteToNetwork(data) => data.to_vec(),
+            _ => panic!("expected handshake response"),
+        };
+        let _ = peer_tunnel.tunn.decapsulate(None, &response, &mut buf);
+        assert!(peer_tunnel.tunn.time_since_last_handshake().is_some());
+    }
+
+    fn test_peer(remote_public: X25519PublicKey, local: &KeyPair) -> PeerTunnel {
+        let mut config = PeerConfig::new(
+            "remote".to_string(),
+            crate::wireguard::PublicKey::from_bytes(remote_public.to_bytes()),
+        );
+        config.allowed_ips = vec!["10.0.0.2/32".to_string()];
+        let local_private = StaticSecret::from(*local.private.as_bytes());
+        PeerTunnel::new(config.name.clone(), local_private, &config, 0).unwrap()
+    }
+
+    #[test]
+    fn test_rotate_without_session_retires_old_index() {
+        let local = KeyPair::generate();
+        let remote = KeyPair::generate();
+        let mut peer_tunnel = test_peer(X25519PublicKey::from(*remote.public.as_bytes()), &local);
+
+        let new_local = KeyPair::generate();
+        let new_private = StaticSecret::from(*new_local.private.as_bytes());
+        let retired = peer_tunnel
+            .rotate(new_private.clone(), 1)
+            .unwrap();
+
+        assert_eq!(retired, vec![0]);
+        assert_eq!(peer_tunnel.index, 1);
+        assert!(peer_tunnel.previous.is_none());
+    }
+
+    #[test]
+    fn test_rotate_keeps_live_session() {
+        let local = KeyPair::generate();
+        let remote = KeyPair::generate();
+        let mut peer_tunnel = test_peer(X25519PublicKey::from(*remote.public.as_bytes()), &local);
+        let mut remote_tunn = Tunn::new(
+            StaticSecret::from(*remote.private.as_bytes()),
+            X25519PublicKey::from(*local.public.as_bytes()),
+            None,
+            None,
+            100,
+            None,
+        )
+        .unwrap();
+
+        handshake(&mut peer_tunnel, &mut remote_tunn);
+
+        let new_local = KeyPair::generate();
+        let new_private = StaticSecret::from(*new_local.private.as_bytes());
+        let retired = peer_tunnel
+            .rotate(new_private.clone(), 1)
+            .unwrap();
+        assert!(retired.is_empty());
+        assert_eq!(peer_tunnel.previous.as_ref().map(|p| p.index), Some(0));
+
+        // Traffic keeps flowing over the old session until the new key handshakes
+        let mut packet = vec![0u8; 20];
+        packet[0] = 0x45;
+        packet[3] = 20;
+        packet[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        packet[16..20].copy_from_slice(&[10, 0, 0, 2]);
+
+        let mut buf = vec![0u8; MAX_PACKET_SIZE];
+        let mut remote_buf = vec![0u8; MAX_PACKET_SIZE];
+        let encrypted = match peer_tunnel.outbound_tunn().encapsulate(&packet, &mut buf) {
+            TunnResult::WriteToNetwork(data) => data.to_vec(),
+            _ => panic!("expected encrypted packet"),
+        };
+        assert!(matches!(
+            remote_tunn.decapsulate(None, &encrypted, &mut remote_buf),
+            TunnResult::WriteToTunnelV4(_, _)
+        ));
+        assert_eq!(peer_tunnel.retire_previous(), None);
+    }
+
+    #[test]
+    fn test_refresh_stats_counts_handshake_once() {
+        let local = KeyPair::generate();
+        let remote = KeyPair::generate();
+        let mut peer_tunnel = test_peer(X25519PublicKey::from(*remote.public.as_bytes()), &local);
+        let mut remote_tunn = Tunn::new(
+            StaticSecret::from(*remote.private.as_bytes()),
+            X25519PublicKey::from(*local.public.as_bytes()),
+            None,
+            None,
+            100,
+            None,
+        )
+        .unwrap();
+
+        assert!(peer_tunnel.refresh_stats().last_handshake.is_none());
+
+        handshake(&mut peer_tunnel, &mut remote_tunn);
+        peer_tunnel.note_sent(&[1, 0, 0, 0]);
+
+        let stats = peer_tunnel.refresh_stats();
+        assert!(stats.last_handshake.is_some());
+        assert_eq!(stats.handshake_attempts, 1);
+        assert_eq!(stats.successful_handshakes, 1);
+
+        // Refreshing again without a new handshake keeps the count
+        assert_eq!(peer_tunnel.refresh_stats().successful_handshakes, 1);
+    }
+
+    fn test_routing(peer_tunnel: PeerTunnel, local: &KeyPair) -> InboundRouting {
+        let mut table = PeerTable::default();
+        table.insert(peer_tunnel, &["10.0.0.2/32".to_string()]).unwrap();
+        let local_private = StaticSecret::from(*local.private.as_bytes());
+        InboundRouting {
+            peers: Arc::new(SharedPeerTable::new(table)),
+            rate_limiter: Arc::new(RwLock::new(handshake_rate_limiter(&local_private))),
+            local_private: Arc::new(RwLock::new(local_private)),
+            event_tx: broadcast::channel(1).0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake_limit_counts_initiations_once() {
+        let local = KeyPair::generate();
+        let remote = KeyPair::generate();
+        let peer_tunnel = test_peer(X25519PublicKey::from(*remote.public.as_bytes()), &local);
+        let routing = test_routing(peer_tunnel, &local);
+        let mut remote_tunn = Tunn::new(
+            StaticSecret::from(*remote.private.as_bytes()),
+            X25519PublicKey::from(*local.public.as_bytes()),
+            None,
+            None,
+            100,
+            None,
+        )
+        .unwrap();
+
+        let remote_addr: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+        let (socket, _remote_socket) =
+            MemorySocket::pair("192.0.2.1:51820".parse().unwrap(), remote_addr);
+        let (tun, _tun_handle) = MemoryTun::pair();
+        let tun_queues: Vec<Arc<dyn TunIo>> = vec![Arc::new(tun)];
+        let counters = DeviceCounters::default();
+        let mut buf = vec![0u8; MAX_PACKET_SIZE];
+        let mut remote_buf = vec![0u8; MAX_PACKET_SIZE];
+
+        // The limiter is never reset here, so the whole budget is one window
+        for sent in 1..=HANDSHAKE_RATE_LIMIT + 1 {
+            let initiation = match remote_tunn.format_handshake_initiation(&mut remote_buf, true) {
+                TunnResult::WriteToNetwork(data) => data.to_vec(),
+                _ => panic!("expected handshake initiation"),
+            };
+            WgDevice::handle_datagram(
+                &initiation,
+                remote_addr,
+                &tun_queues,
+                &socket,
+                &routing,
+                &mut buf,
+                &counters,
+            )
+            .await;
+
+            let limited = counters.snapshot().handshakes_rate_limited;
+            assert_eq!(limited, sent.saturating_sub(HANDSHAKE_RATE_LIMIT), "after {}", sent);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inbound_source_filter() {
+        let local = KeyPair::generate();
+        let remote = KeyPair::generate();
+        let mut peer_tunnel = test_peer(X25519PublicKey::from(*remote.public.as_bytes()), &local);
+        let mut remote_tunn = Tunn::new(
+            StaticSecret::from(*remote.private.as_bytes()),
+            X25519PublicKey::from(*local.public.as_bytes()),
+            None,
+            None,
+            100,
+            None,
+        )
+        .unwrap();
+        handshake(&mut peer_tunnel, &mut remote_tunn);
+
+        let ipv4_packet = |src: [u8; 4]| {
+            let mut packet = vec![0u8; 20];
+            packet[0] = 0x45;
+            packet[3] = 20;
+            packet[12..16].copy_from_slice(&src);
+            packet[16..20].copy_from_slice(&[10, 0, 0, 1]);
+            packet
+        };
+
+        // The responder can send once it has seen data on the new session
+        let mut buf = vec![0u8; MAX_PACKET_SIZE];
+        let mut remote_buf = vec![0u8; MAX_PACKET_SIZE];
+        let confirm = match peer_tunnel.tunn.encapsulate(&ipv4_packet([10, 0, 0, 1]), &mut buf) {
+            TunnResult::WriteToNetwork(data) => data.to_vec(),
+            _ => panic!("expected encrypted packet"),
+        };
+        let _ = remote_tunn.decapsulate(None, &confirm, &mut remote_buf);
+
+        let routing = test_routing(peer_tunnel, &local);
+        let remote_addr: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+        let (socket, _remote_socket) = MemorySocket::pair("192.0.2.1:51820".parse().unwrap(), remote_addr);
+        let (tun, tun_handle) = MemoryTun::pair();
+        let tun_queues: Vec<Arc<dyn TunIo>> = vec![Arc::new(tun)];
+        let counters = DeviceCounters::default();
+
+        // Only the source inside the peer's allowed IPs reaches the TUN device
+        for (src, delivered) in [([10, 0, 0, 2], true), ([10, 0, 0, 9], false)] {
+            let datagram = match remote_tunn.encapsulate(&ipv4_packet(src), &mut remote_buf) {
+                TunnResult::WriteToNetwork(data) => data.to_vec(),
+                _ => panic!("expected encrypted packet"),
+            };
+            WgDevice::handle_datagram(
+                &datagram,
+                remote_addr,
+                &tun_queues,
+                &socket,
+                &routing,
+                &mut buf,
+                &counters,
+            )
+            .await;
+            assert_eq!(tun_handle.try_recv().is_some(), delivered, "source {:?}", src);
+        }
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.source_filter_drops, 1);
+        assert_eq!(stats.rx_packets, 1);
+    }
+
+    #[test]
+    fn test_hand_off_drops_when_worker_is_behind() {
+        let (worker, mut rx) = mpsc::channel(1);
+        let counters = DeviceCounters::default();
+        let datagram = || (vec![0u8; 32], "192.0.2.2:51820".parse().unwrap());
+
+        WgDevice::hand_off(&worker, vec![datagram()], &counters);
+        WgDevice::hand_off(&worker, vec![datagram(), datagram()], &counters);
+        assert_eq!(counters.snapshot().inbound_queue_drops, 2);
+
+        // Once the worker catches up, batches are queued again
+        assert_eq!(rx.try_recv().unwrap().len(), 1);
+        WgDevice::hand_off(&worker, vec![datagram()], &counters);
+        assert_eq!(counters.snapshot().inbound_queue_drops, 2);
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_packet_queue_follows_inner_flow() {
+        // IPv4/UDP from 10.0.0.2 to 10.0.0.1 with the given source port
+        let udp = |port: u16, flags: [u8; 2]| {
+            let mut packet = vec![0u8; 28];
+            packet[0] = 0x45;
+            packet[6..8].copy_from_slice(&flags);
+            packet[9] = 17;
+            packet[12..20].copy_from_slice(&[10, 0, 0, 2, 10, 0, 0, 1]);
+            packet[20..22].copy_from_slice(&port.to_be_bytes());
+            packet[22..24].copy_from_slice(&53u16.to_be_bytes());
+            packet
+        };
+
+        // A flow always maps to one queue, and a peer's flows use several
+        let queues: std::collections::HashSet<_> = (1000..1064)
+            .map(|port| {
+                let queue = packet_queue(&udp(port, [0, 0]), 4);
+                assert_eq!(packet_queue(&udp(port, [0, 0]), 4), queue);
+                queue
+            })
+            .collect();
+        assert!(queues.len() > 1);
+
+        // Fragments of a datagram ignore the ports only the first one carries
+        let first_fragment = packet_queue(&udp(1000, [0x20, 0]), 4);
+        assert_eq!(packet_queue(&udp(2000, [0x20, 0]), 4), first_fragment);
+        assert_eq!(packet_queue(&udp(3000, [0, 0x10]), 4), first_fragment);
+
+        let mut ipv6 = vec![0u8; 48];
+        ipv6[0] = 0x60;
+        ipv6[6] = 6;
+        assert!(packet_queue(&ipv6, 4) < 4);
+        assert_eq!(packet_queue(&[0x45, 0], 4), 0);
+        assert_eq!(packet_queue(&udp(1000, [0, 0]), 1), 0);
+    }
+
+    #[test]
+    fn test_peer_table_snapshots() {
+        let local = KeyPair::generate();
+        let remote = X25519PublicKey::from(*KeyPair::generate().public.as_bytes());
+        let shared = SharedPeerTable::default();
+
+        // A failed insert publishes nothing
+        let result = shared.try_update(|table| {
+            table.insert(test_peer(remote, &local), &["10.0.0.2/32".to_string(), "bogus".to_string()])
+        });
+        assert!(result.is_err());
+        assert!(shared.snapshot().peers.is_empty());
+
+        shared
+            .try_update(|table| table.insert(test_peer(remote, &local), &["10.0.0.2/32".to_string()]))
+            .unwrap();
+        let before = shared.snapshot();
+        assert_eq!(before.by_index(0), Some(remote));
+        assert_eq!(before.allowed_ips.lookup("10.0.0.2".parse().unwrap()), Some(&remote));
+
+        // Removal clears routes and indices without touching earlier snapshots
+        assert!(shared.update(|table| table.remove(&remote)).is_some());
+        let after = shared.snapshot();
+        assert!(after.peers.is_empty() && after.indices.is_empty() && after.allowed_ips.is_empty());
+        assert!(before.peers.contains_key(&remote));
+    }
+
+    #[tokio::test]
+    async fn test_add_peer_rejects_routed_allowed_ip() {
+        let (tun, _tun_handle) = MemoryTun::pair();
+        let (socket, _remote_socket) = MemorySocket::pair(
+            "192.0.2.1:51820".parse().unwrap(),
+            "192.0.2.2:51820".parse().unwrap(),
+        );
+        let mut first = PeerConfig::new("first".to_string(), KeyPair::generate().public);
+        first.allowed_ips = vec!["10.0.0.0/24".to_string()];
+        let config = DeviceConfig {
+            interface: "wg-test".to_string(),
+            mtu: 1420,
+            keypair: KeyPair::generate(),
+            listen_port: 0,
+            bind_address: None,
+            queues: 1,
+            peers: vec![first],
+        };
+        let device = WgDevice::with_io(config, Arc::new(tun), Arc::new(socket)).await.unwrap();
+
+        let mut second = PeerConfig::new("second".to_string(), KeyPair::generate().public);
+        second.allowed_ips = vec!["10.0.1.0/24".to_string(), "10.0.0.0/24".to_string()];
+        let err = device.add_peer(second.clone()).await.unwrap_err();
+        assert!(err.to_string().contains("'first'"), "{}", err);
+
+        // None of the rejected peer's routes were added
+        let table = device.peers.snapshot();
+        assert_eq!(table.peers.len(), 1);
+        assert!(table.allowed_ips.lookup("10.0.1.1".parse().unwrap()).is_none());
+
+        second.allowed_ips.pop();
+        device.add_peer(second).await.unwrap();
+        assert_eq!(device.peers.snapshot().peers.len(), 2);
+        device.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_peer_updates_report_unknown_peers() {
+        let (tun, _tun_handle) = MemoryTun::pair();
+        let (socket, _remote_socket) = MemorySocket::pair(
+            "192.0.2.1:51820".parse().unwrap(),
+            "192.0.2.2:51820".parse().unwrap(),
+        );
+        let peer = PeerConfig::new("old-name".to_string(), KeyPair::generate().public);
+        let config = DeviceConfig {
+            interface: "wg-test".to_string(),
+            mtu: 1420,
+            keypair: KeyPair::generate(),
+            listen_port: 0,
+            bind_address: None,
+            queues: 1,
+            peers: vec![peer.clone()],
+        };
+        let device = WgDevice::with_io(config, Arc::new(tun), Arc::new(socket)).await.unwrap();
+
+        let endpoint: SocketAddr = "192.0.2.9:51820".parse().unwrap();
+        device.rename_peer(&peer.public_key, "new-name").await.unwrap();
+        device.update_endpoint(&peer.public_key, endpoint).await.unwrap();
+        let (name, current) = {
+            let table = device.peers.snapshot();
+            let peer_tunnel = table.peers.values().next().unwrap().lock().unwrap();
+            (peer_tunnel.name.clone(), peer_tunnel.endpoint)
+        };
+        assert_eq!((name.as_str(), current), ("new-name", Some(endpoint)));
+
+        let unknown = KeyPair::generate().public;
+        assert!(device.rename_peer(&unknown, "other").await.is_err());
+        assert!(device.update_endpoint(&unknown, endpoint).await.is_err());
+        assert!(device.remove_peer(&unknown).await.is_err());
+        device.stop().await.unwrap();
+    }
+
+    #[test]
+    fn test_device_counters_snapshot() {
+        let counters = DeviceCounters::default();
+        counters.sent(100);
+        counters.sent(50);
+        counters.delivered(70);
+        DeviceCounters::increment(&counters.errors);
+
+        let stats = counters.snapshot();
+        assert_eq!((stats.tx_bytes, stats.tx_packets), (150, 2));
+        assert_eq!((stats.rx_bytes, stats.rx_packets), (70, 1));
+        assert_eq!(stats.errors, 1);
+        assert!(stats.peers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_device_socket_dual_stack() {
+        let socket = DeviceSocket::bind(None, 0).unwrap();
+        let port = socket.local_addr().unwrap().port();
+        assert_ne!(port, 0);
+
+        let client = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.send_to(b"ping", ("127.0.0.1", port)).unwrap();
+
+        let mut buf = [0u8; 16];
+        let (n, src) = socket.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ping");
+        assert_eq!(src, client.local_addr().unwrap());
+
+        socket.send_to(b"pong", src).await.unwrap();
+        let (n, _) = client.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"pong");
+    }
+
+    #[tokio::test]
+    async fn test_device_socket_bind_address() {
+        let socket = DeviceSocket::bind(Some("127.0.0.1".parse().unwrap()), 0).unwrap();
+        assert!(socket.local_addr().unwrap().ip().is_loopback());
+    }
+}
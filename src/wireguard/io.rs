@@ -0,0 +1,546 @@
+//! Packet I/O for the userspace WireGuard device
+//!
+//! [`WgDevice`](crate::wireguard::WgDevice) reads and writes IP packets
+//! through a [`TunIo`] and exchanges encrypted datagrams through a [`UdpIo`].
+//! Normally these are the platform TUN device and a UDP socket; the in-memory
+//! implementations let two devices talk to each other inside a test, without
+//! privileges or real interfaces.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+use tokio::sync::{mpsc, Mutex};
+
+/// Boxed future returned by [`TunIo`] and [`UdpIo`] operations
+pub type IoFuture<'a, T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'a>>;
+
+/// Source and sink of the IP packets the device tunnels
+pub trait TunIo: Send + Sync {
+    /// Wait for the next outbound IP packet
+    fn read<'a>(&'a self, buf: &'a mut [u8]) -> IoFuture<'a, usize>;
+
+    /// Wait for outbound IP packets, reading as many as are ready into `bufs`
+    ///
+    /// Stores each packet's length in `sizes` and returns the number of
+    /// buffers filled. The default reads a single packet.
+    fn read_batch<'a>(
+        &'a self,
+        bufs: &'a mut [Vec<u8>],
+        sizes: &'a mut [usize],
+    ) -> IoFuture<'a, usize> {
+        Box::pin(async move {
+            sizes[0] = self.read(&mut bufs[0]).await?;
+            Ok(1)
+        })
+    }
+
+    /// Deliver a decrypted IP packet
+    fn write<'a>(&'a self, packet: &'a [u8]) -> IoFuture<'a, usize>;
+}
+
+/// Datagram transport for encrypted WireGuard messages
+pub trait UdpIo: Send + Sync {
+    /// Local address datagrams are received on
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    /// Send a datagram to `target`
+    fn send_to<'a>(&'a self, data: &'a [u8], target: SocketAddr) -> IoFuture<'a, usize>;
+
+    /// Wait for the next datagram, returning its length and source
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> IoFuture<'a, (usize, SocketAddr)>;
+
+    /// Wait for datagrams, receiving as many as are ready into `bufs`
+    ///
+    /// Describes each buffer in `meta` and returns the number of buffers
+    /// filled. The default receives a single datagram.
+    fn recv_batch<'a>(
+        &'a self,
+        bufs: &'a mut [Vec<u8>],
+        meta: &'a mut [RecvMeta],
+    ) -> IoFuture<'a, usize> {
+        Box::pin(async move {
+            let (len, src) = self.recv_from(&mut bufs[0]).await?;
+            meta[0] = RecvMeta { len, src, stride: len };
+            Ok(1)
+        })
+    }
+
+    /// Send datagrams in order, returning how many were sent
+    ///
+    /// Stops at the first failure, whose error is returned only if nothing
+    /// was sent. The default sends one datagram at a time.
+    fn send_batch<'a>(&'a self, datagrams: &'a [(&'a [u8], SocketAddr)]) -> IoFuture<'a, usize> {
+        Box::pin(async move {
+            for (sent, (data, target)) in datagrams.iter().enumerate() {
+                if let Err(e) = self.send_to(data, *target).await {
+                    return if sent == 0 { Err(e) } else { Ok(sent) };
+                }
+            }
+            Ok(datagrams.len())
+        })
+    }
+}
+
+/// A buffer filled by [`UdpIo::recv_batch`]
+///
+/// With receive coalescing (UDP GRO) one buffer can hold several datagrams
+/// from the same source, each `stride` bytes long except possibly the last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvMeta {
+    /// Bytes received into the buffer
+    pub len: usize,
+    /// Source of the datagrams
+    pub src: SocketAddr,
+    /// Datagram size; equal to `len` for a single datagram
+    pub stride: usize,
+}
+
+impl RecvMeta {
+    /// Split the filled part of `buf` into its datagrams
+    pub fn datagrams<'a>(&self, buf: &'a [u8]) -> std::slice::Chunks<'a, u8> {
+        buf[..self.len].chunks(self.stride.max(1))
+    }
+}
+
+impl Default for RecvMeta {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            src: SocketAddr::from(([0, 0, 0, 0], 0)),
+            stride: 0,
+        }
+    }
+}
+
+/// Platform TUN device registered with the tokio reactor
+///
+/// Reads wait for read readiness and writes for write readiness on the same
+/// descriptor, so the outbound task reading packets and the inbound task
+/// delivering them never wait on each other.
+pub struct TunDeviceIo {
+    fd: AsyncFd<File>,
+}
+
+impl TunDeviceIo {
+    /// Register a TUN device that has been set to non-blocking
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn new(device: tun::platform::Device) -> io::Result<Self> {
+        // SAFETY: the descriptor comes straight from the device, which gives
+        // up ownership of it, so the file is its only owner
+        let file = unsafe { File::from_raw_fd(device.into_raw_fd()) };
+        Self::from_file(file)
+    }
+
+    /// Register every queue of a multi-queue TUN device
+    ///
+    /// Like [`TunDeviceIo::new`], the device must have been set to
+    /// non-blocking; that covers its first queue, and the others are set to
+    /// non-blocking here. Returns one handle per queue, starting with the
+    /// first. Must be called from within a tokio runtime.
+    #[cfg(target_os = "linux")]
+    pub fn queues(mut device: tun::platform::Device) -> io::Result<Vec<Self>> {
+        use std::os::unix::io::AsRawFd;
+        use tun::Device;
+
+        // The device only gives up its first queue, so keep duplicates of the
+        // others; a duplicate stays attached to the same queue
+        let mut files = Vec::new();
+        let mut index = 1;
+        while let Some(queue) = device.queue(index) {
+            queue.set_nonblock()?;
+            // SAFETY: dup() has no preconditions
+            let fd = unsafe { libc::dup(queue.as_raw_fd()) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: the duplicate was just created and nothing else owns it
+            files.push(unsafe { File::from_raw_fd(fd) });
+            index += 1;
+        }
+
+        let mut queues = vec![Self::new(device)?];
+        for file in files {
+            queues.push(Self::from_file(file)?);
+        }
+        Ok(queues)
+    }
+
+    fn from_file(file: File) -> io::Result<Self> {
+        let fd = AsyncFd::with_interest(file, Interest::READABLE | Interest::WRITABLE)?;
+        Ok(Self { fd })
+    }
+}
+
+impl TunIo for TunDeviceIo {
+    fn read<'a>(&'a self, buf: &'a mut [u8]) -> IoFuture<'a, usize> {
+        Box::pin(async move {
+            loop {
+                let mut guard = self.fd.readable().await?;
+                if let Ok(result) = guard.try_io(|file| file.get_ref().read(buf)) {
+                    return result;
+                }
+            }
+        })
+    }
+
+    /// Read until the device has no more packets ready or `bufs` is full
+    fn read_batch<'a>(
+        &'a self,
+        bufs: &'a mut [Vec<u8>],
+        sizes: &'a mut [usize],
+    ) -> IoFuture<'a, usize> {
+        Box::pin(async move {
+            loop {
+                let mut guard = self.fd.readable().await?;
+                let mut count = 0;
+                for (buf, size) in bufs.iter_mut().zip(sizes.iter_mut()) {
+                    match guard.try_io(|file| file.get_ref().read(buf)) {
+                        Ok(Ok(n)) => {
+                            *size = n;
+                            count += 1;
+                        }
+                        Ok(Err(e)) if count == 0 => return Err(e),
+                        // Deliver what was read; a lasting error shows up on the next call
+                        Ok(Err(_)) | Err(_) => break,
+                    }
+                }
+                if count > 0 {
+                    return Ok(count);
+                }
+            }
+        })
+    }
+
+    fn write<'a>(&'a self, packet: &'a [u8]) -> IoFuture<'a, usize> {
+        Box::pin(async move {
+            loop {
+                let mut guard = self.fd.writable().await?;
+                if let Ok(result) = guard.try_io(|file| file.get_ref().write(packet)) {
+                    return result;
+                }
+            }
+        })
+    }
+}
+
+/// In-memory TUN device; the paired [`MemoryTunHandle`] plays the host side
+pub struct MemoryTun {
+    outbound: Mutex<mpsc::UnboundedReceiver<Vec<u8>>>,
+    delivered: mpsc::UnboundedSender<Vec<u8>>,
+}
+
+/// Host side of a [`MemoryTun`]
+pub struct MemoryTunHandle {
+    outbound: mpsc::UnboundedSender<Vec<u8>>,
+    delivered: Mutex<mpsc::UnboundedReceiver<Vec<u8>>>,
+}
+
+impl MemoryTun {
+    /// Create a TUN device and the handle used to inject and collect packets
+    pub fn pair() -> (MemoryTun, MemoryTunHandle) {
+        let (outbound_tx, outbound_rx) = mpsc::unbounded_channel();
+        let (delivered_tx, delivered_rx) = mpsc::unbounded_channel();
+
+        let tun = MemoryTun {
+            outbound: Mutex::new(outbound_rx),
+            delivered: delivered_tx,
+        };
+        let handle = MemoryTunHandle {
+            outbound: outbound_tx,
+            delivered: Mutex::new(delivered_rx),
+        };
+        (tun, handle)
+    }
+}
+
+impl TunIo for MemoryTun {
+    fn read<'a>(&'a self, buf: &'a mut [u8]) -> IoFuture<'a, usize> {
+        Box::pin(async move {
+            match self.outbound.lock().await.recv().await {
+                Some(packet) => Ok(copy_truncated(&packet, buf)),
+                // No more packets will arrive once the handle is dropped
+                None => std::future::pending().await,
+            }
+        })
+    }
+
+    fn read_batch<'a>(
+        &'a self,
+        bufs: &'a mut [Vec<u8>],
+        sizes: &'a mut [usize],
+    ) -> IoFuture<'a, usize> {
+        Box::pin(async move {
+            let mut outbound = self.outbound.lock().await;
+            let Some(packet) = outbound.recv().await else {
+                return std::future::pending().await;
+            };
+            sizes[0] = copy_truncated(&packet, &mut bufs[0]);
+
+            let mut count = 1;
+            while count < bufs.len().min(sizes.len()) {
+                let Ok(packet) = outbound.try_recv() else {
+                    break;
+                };
+                sizes[count] = copy_truncated(&packet, &mut bufs[count]);
+                count += 1;
+            }
+            Ok(count)
+        })
+    }
+
+    fn write<'a>(&'a self, packet: &'a [u8]) -> IoFuture<'a, usize> {
+        let result = self
+            .delivered
+            .send(packet.to_vec())
+            .map(|_| packet.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "TUN handle dropped"));
+        Box::pin(async move { result })
+    }
+}
+
+impl MemoryTunHandle {
+    /// Queue an IP packet as if the host had routed it into the tunnel
+    pub fn send(&self, packet: &[u8]) {
+        // The device may already be stopped; like a real interface, drop the packet
+        let _ = self.outbound.send(packet.to_vec());
+    }
+
+    /// Wait for the next packet the device delivered to the host
+    pub async fn recv(&self) -> Option<Vec<u8>> {
+        self.delivered.lock().await.recv().await
+    }
+
+    /// Take a delivered packet if one is waiting
+    pub fn try_recv(&self) -> Option<Vec<u8>> {
+        self.delivered.try_lock().ok()?.try_recv().ok()
+    }
+}
+
+/// Datagram queue of a bound [`MemorySocket`]
+type Mailbox = mpsc::UnboundedSender<(Vec<u8>, SocketAddr)>;
+
+/// In-memory datagram network connecting any number of [`MemorySocket`]s
+///
+/// Datagrams sent to an address with no socket bound are dropped, as UDP
+/// would drop datagrams to an unreachable host.
+#[derive(Clone, Default)]
+pub struct MemoryNetwork {
+    sockets: Arc<RwLock<HashMap<SocketAddr, Mailbox>>>,
+}
+
+impl MemoryNetwork {
+    /// Create an empty network
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind a socket to `addr`, replacing any socket bound there before
+    pub fn bind(&self, addr: SocketAddr) -> MemorySocket {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.sockets.write().unwrap().insert(addr, tx);
+
+        MemorySocket {
+            local: addr,
+            network: self.clone(),
+            rx: Mutex::new(rx),
+        }
+    }
+
+    fn deliver(&self, data: &[u8], src: SocketAddr, target: SocketAddr) {
+        let sockets = self.sockets.read().unwrap();
+        let Some(mailbox) = sockets.get(&target) else {
+            return;
+        };
+        if mailbox.send((data.to_vec(), src)).is_err() {
+            // The socket was dropped; unbind it
+            drop(sockets);
+            let mut sockets = self.sockets.write().unwrap();
+            if sockets.get(&target).is_some_and(|m| m.is_closed()) {
+                sockets.remove(&target);
+            }
+        }
+    }
+}
+
+/// Socket on a [`MemoryNetwork`]
+pub struct MemorySocket {
+    local: SocketAddr,
+    network: MemoryNetwork,
+    rx: Mutex<mpsc::UnboundedReceiver<(Vec<u8>, SocketAddr)>>,
+}
+
+impl MemorySocket {
+    /// Create two sockets bound to `a` and `b` on a network of their own
+    pub fn pair(a: SocketAddr, b: SocketAddr) -> (MemorySocket, MemorySocket) {
+        let network = MemoryNetwork::new();
+        (network.bind(a), network.bind(b))
+    }
+}
+
+impl UdpIo for MemorySocket {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local)
+    }
+
+    fn send_to<'a>(&'a self, data: &'a [u8], target: SocketAddr) -> IoFuture<'a, usize> {
+        self.network.deliver(data, self.local, target);
+        Box::pin(async move { Ok(data.len()) })
+    }
+
+    fn recv_from<'a>(&'a self, buf: &'a mut [u8]) -> IoFuture<'a, (usize, SocketAddr)> {
+        Box::pin(async move {
+            match self.rx.lock().await.recv().await {
+                Some((data, src)) => Ok((copy_truncated(&data, buf), src)),
+                // Another socket was bound to our address; nothing more will arrive
+                None => std::future::pending().await,
+            }
+        })
+    }
+
+    fn recv_batch<'a>(
+        &'a self,
+        bufs: &'a mut [Vec<u8>],
+        meta: &'a mut [RecvMeta],
+    ) -> IoFuture<'a, usize> {
+        Box::pin(async move {
+            let mut rx = self.rx.lock().await;
+            let Some(mut datagram) = rx.recv().await else {
+                return std::future::pending().await;
+            };
+
+            let mut count = 0;
+            loop {
+                let (data, src) = datagram;
+                let len = copy_truncated(&data, &mut bufs[count]);
+                meta[count] = RecvMeta { len, src, stride: len };
+                count += 1;
+
+                if count == bufs.len().min(meta.len()) {
+                    break;
+                }
+                match rx.try_recv() {
+                    Ok(next) => datagram = next,
+                    Err(_) => break,
+                }
+            }
+            Ok(count)
+        })
+    }
+}
+
+/// Copy as much of `data` as fits into `buf`
+fn copy_truncated(data: &[u8], buf: &mut [u8]) -> usize {
+    let n = data.len().min(buf.len());
+    buf[..n].copy_from_slice(&data[..n]);
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_tun() {
+        let (tun, handle) = MemoryTun::pair();
+        handle.send(&[1, 2, 3]);
+
+        let mut buf = [0u8; 2];
+        assert_eq!(tun.read(&mut buf).await.unwrap(), 2);
+        assert_eq!(buf, [1, 2]);
+
+        tun.write(&[4, 5]).await.unwrap();
+        assert_eq!(handle.recv().await, Some(vec![4, 5]));
+        assert_eq!(handle.try_recv(), None);
+
+        drop(handle);
+        assert!(tun.write(&[6]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_memory_batches() {
+        let (tun, handle) = MemoryTun::pair();
+        for i in 0..3u8 {
+            handle.send(&[i; 4]);
+        }
+        let mut bufs = vec![vec![0u8; 16]; 2];
+        let mut sizes = [0; 2];
+        assert_eq!(tun.read_batch(&mut bufs, &mut sizes).await.unwrap(), 2);
+        assert_eq!(tun.read_batch(&mut bufs, &mut sizes).await.unwrap(), 1);
+        assert_eq!(&bufs[0][..sizes[0]], &[2; 4]);
+
+        let a_addr: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+        let b_addr: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+        let (a, b) = MemorySocket::pair(a_addr, b_addr);
+        let payloads = [&b"one"[..], b"two", b"three"];
+        let datagrams: Vec<_> = payloads.iter().map(|p| (*p, b_addr)).collect();
+        assert_eq!(a.send_batch(&datagrams).await.unwrap(), 3);
+
+        let mut meta = [RecvMeta::default(); 4];
+        let mut bufs = vec![vec![0u8; 16]; 4];
+        assert_eq!(b.recv_batch(&mut bufs, &mut meta).await.unwrap(), 3);
+        for ((buf, meta), payload) in bufs.iter().zip(&meta).zip(payloads) {
+            assert_eq!(meta.src, a_addr);
+            assert_eq!(meta.datagrams(buf).collect::<Vec<_>>(), vec![payload]);
+        }
+    }
+
+    #[test]
+    fn test_recv_meta_datagrams() {
+        let buf = [1, 1, 2, 2, 3, 0, 0];
+        let meta = RecvMeta { len: 5, stride: 2, ..RecvMeta::default() };
+        assert_eq!(meta.datagrams(&buf).collect::<Vec<_>>(), vec![&[1, 1][..], &[2, 2], &[3]]);
+    }
+
+    #[tokio::test]
+    async fn test_memory_socket_pair() {
+        let a_addr: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+        let b_addr: SocketAddr = "[2001:db8::2]:51820".parse().unwrap();
+        let (a, b) = MemorySocket::pair(a_addr, b_addr);
+
+        // Datagrams to other addresses are silently dropped
+        a.send_to(b"lost", "192.0.2.99:1".parse().unwrap()).await.unwrap();
+        a.send_to(b"hello", b_addr).await.unwrap();
+
+        let mut buf = [0u8; 16];
+        let (n, src) = b.recv_from(&mut buf).await.unwrap();
+        assert_eq!((&buf[..n], src), (&b"hello"[..], a_addr));
+        assert_eq!(b.local_addr().unwrap(), b_addr);
+    }
+
+    #[tokio::test]
+    async fn test_memory_network() {
+        let network = MemoryNetwork::new();
+        let hub = network.bind("192.0.2.1:51820".parse().unwrap());
+        let spokes: Vec<_> = (1..=3)
+            .map(|port| network.bind(SocketAddr::from(([192, 0, 2, 2], port))))
+            .collect();
+
+        for spoke in &spokes {
+            spoke.send_to(b"up", hub.local_addr().unwrap()).await.unwrap();
+        }
+
+        let mut buf = [0u8; 16];
+        for spoke in &spokes {
+            let (_, src) = hub.recv_from(&mut buf).await.unwrap();
+            assert_eq!(src, spoke.local_addr().unwrap());
+            hub.send_to(b"down", src).await.unwrap();
+
+            let (n, src) = spoke.recv_from(&mut buf).await.unwrap();
+            assert_eq!((&buf[..n], src), (&b"down"[..], hub.local_addr().unwrap()));
+        }
+
+        // Sockets that are gone are unbound on the next send to them
+        let gone = spokes[0].local_addr().unwrap();
+        drop(spokes);
+        hub.send_to(b"lost", gone).await.unwrap();
+        assert!(!network.sockets.read().unwrap().contains_key(&gone));
+    }
+}
@@ -0,0 +1,493 @@
+//! Linux in-kernel WireGuard device
+//!
+//! Creates a `wireguard` link and configures it over the generic netlink
+//! `wireguard` family, the same interface `wg(8)` uses. Packets are handled
+//! entirely by the kernel; the agent only manages keys, peers and statistics.
+
+use crate::error::{Result, WgAgentError};
+use crate::platform::netlink::{map_error, network_address, parse_cidr, NetlinkSocket};
+use crate::platform::Platform;
+use crate::wireguard::{DeviceConfig, DeviceStats, KeyPair, PeerConfig, PeerStats, PublicKey};
+use netlink_packet_core::NLM_F_DUMP;
+use netlink_packet_generic::ctrl::nlas::GenlCtrlAttrs;
+use netlink_packet_generic::ctrl::{GenlCtrl, GenlCtrlCmd};
+use netlink_packet_generic::GenlMessage;
+use netlink_packet_wireguard::{
+    WireguardAddressFamily, WireguardAllowedIp, WireguardAllowedIpAttr, WireguardAttribute,
+    WireguardCmd, WireguardDeviceFlags, WireguardMessage, WireguardPeer, WireguardPeerAttribute,
+    WireguardPeerFlags, WireguardTimeSpec,
+};
+use netlink_sys::protocols::NETLINK_GENERIC;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+/// Generic netlink channel bound to the `wireguard` family
+struct WireguardNetlink {
+    socket: NetlinkSocket,
+    family_id: u16,
+}
+
+impl WireguardNetlink {
+    /// Open a generic netlink socket and resolve the `wireguard` family
+    fn open() -> Result<Self> {
+        let socket = NetlinkSocket::open(NETLINK_GENERIC).map_err(|e| {
+            WgAgentError::Platform(format!("Failed to open generic netlink socket: {}", e))
+        })?;
+
+        let request = GenlMessage::from_payload(GenlCtrl {
+            cmd: GenlCtrlCmd::GetFamily,
+            nlas: vec![GenlCtrlAttrs::FamilyName("wireguard".to_string())],
+        });
+        let replies = socket.request(request, 0).map_err(|e| {
+            WgAgentError::Platform(format!("WireGuard kernel module is not available: {}", e))
+        })?;
+
+        let family_id = replies
+            .iter()
+            .flat_map(|reply| &reply.payload.nlas)
+            .find_map(|attribute| match attribute {
+                GenlCtrlAttrs::FamilyId(id) => Some(*id),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                WgAgentError::Platform("No family id for generic netlink family wireguard".to_string())
+            })?;
+
+        Ok(Self { socket, family_id })
+    }
+
+    /// Send a WireGuard command for `interface`
+    fn request(
+        &self,
+        interface: &str,
+        operation: &str,
+        cmd: WireguardCmd,
+        mut attributes: Vec<WireguardAttribute>,
+        flags: u16,
+    ) -> Result<Vec<WireguardMessage>> {
+        debug!("wireguard netlink: {} on {}", operation, interface);
+        attributes.insert(0, WireguardAttribute::IfName(interface.to_string()));
+
+        let mut message = GenlMessage::from_payload(WireguardMessage { cmd, attributes });
+        message.set_resolved_family_id(self.family_id);
+
+        self.socket
+            .request(message, flags)
+            .map(|replies| replies.into_iter().map(|reply| reply.payload).collect())
+            .map_err(|e| map_error(interface, format!("{} on {}", operation, interface), e).into())
+    }
+
+    /// Apply device settings and peers
+    fn set_device(
+        &self,
+        interface: &str,
+        operation: &str,
+        attributes: Vec<WireguardAttribute>,
+    ) -> Result<()> {
+        self.request(interface, operation, WireguardCmd::SetDevice, attributes, 0)?;
+        Ok(())
+    }
+
+    /// Read the device; large peer lists arrive split over several messages
+    fn get_device(&self, interface: &str) -> Result<Vec<WireguardMessage>> {
+        self.request(interface, "get device", WireguardCmd::GetDevice, vec![], NLM_F_DUMP)
+    }
+}
+
+/// In-kernel WireGuard device configured over generic netlink
+pub struct KernelWgDevice {
+    interface_name: String,
+    netlink: WireguardNetlink,
+    listen_port: Option<u16>,
+    /// Peer names by public key, for per-peer handshake reporting
+    names: Mutex<HashMap<PublicKey, String>>,
+    /// Statistics from the previous read, used to count new handshakes
+    peer_stats: Mutex<HashMap<PublicKey, PeerStats>>,
+}
+
+impl KernelWgDevice {
+    /// Create a kernel WireGuard link and configure its keys and peers
+    pub async fn new(config: DeviceConfig, platform: &dyn Platform) -> Result<Self> {
+        info!("Creating kernel WireGuard device for interface: {}", config.interface);
+
+        platform.create_wireguard_interface(&config.interface)?;
+        let device = Self::configure(&config, platform);
+        if device.is_err() {
+            if let Err(e) = platform.destroy_interface(&config.interface) {
+                warn!("Failed to remove interface {}: {}", config.interface, e);
+            }
+        }
+        device
+    }
+
+    /// Configure a freshly created link and bring it up
+    fn configure(config: &DeviceConfig, platform: &dyn Platform) -> Result<Self> {
+        // The family is registered once the module is loaded, which creating
+        // the link takes care of
+        let netlink = WireguardNetlink::open()?;
+        let interface = config.interface.as_str();
+
+        if let Some(bind_address) = config.bind_address {
+            warn!(
+                "Kernel WireGuard listens on all addresses; bind_address {} is ignored",
+                bind_address
+            );
+        }
+
+        let peers = config
+            .peers
+            .iter()
+            .map(peer_message)
+            .collect::<Result<Vec<_>>>()?;
+        netlink.set_device(
+            interface,
+            "configure device",
+            vec![
+                WireguardAttribute::PrivateKey(*config.keypair.private.as_bytes()),
+                WireguardAttribute::ListenPort(config.listen_port),
+                WireguardAttribute::Flags(WireguardDeviceFlags::ReplacePeers),
+                WireguardAttribute::Peers(peers),
+            ],
+        )?;
+
+        // Read back the port the kernel picked when none was configured
+        let listen_port = netlink
+            .get_device(interface)?
+            .iter()
+            .flat_map(|message| &message.attributes)
+            .find_map(|attribute| match attribute {
+                WireguardAttribute::ListenPort(port) => Some(*port),
+                _ => None,
+            });
+
+        platform.set_mtu(interface, config.mtu)?;
+        platform.interface_up(interface)?;
+
+        info!(
+            "Kernel WireGuard device {} configured with {} peers, listening on port {:?}",
+            interface,
+            config.peers.len(),
+            listen_port
+        );
+
+        let names = config
+            .peers
+            .iter()
+            .map(|peer| (peer.public_key.clone(), peer.name.clone()))
+            .collect();
+
+        Ok(Self {
+            interface_name: interface.to_string(),
+            netlink,
+            listen_port,
+            names: Mutex::new(names),
+            peer_stats: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Get the interface name
+    pub fn interface_name(&self) -> &str {
+        &self.interface_name
+    }
+
+    /// Get the UDP port the kernel is listening on
+    pub fn listen_port(&self) -> Option<u16> {
+        self.listen_port
+    }
+
+    /// Read per-peer counters and handshake times from the kernel
+    pub async fn stats(&self) -> DeviceStats {
+        let mut previous = self.peer_stats.lock().unwrap();
+
+        let messages = match self.netlink.get_device(&self.interface_name) {
+            Ok(messages) => messages,
+            Err(e) => {
+                warn!("Failed to read WireGuard device {}: {}", self.interface_name, e);
+                return self.device_stats(previous.clone());
+            }
+        };
+
+        let mut peers: HashMap<PublicKey, PeerStats> = HashMap::new();
+        for attribute in messages.iter().flat_map(|message| &message.attributes) {
+            let WireguardAttribute::Peers(list) = attribute else {
+                continue;
+            };
+            for peer in list {
+                let Some(public_key) = peer_public_key(peer) else {
+                    continue;
+                };
+                let stats = peers
+                    .entry(public_key.clone())
+                    .or_insert_with(|| previous.get(&public_key).cloned().unwrap_or_default());
+                apply_peer_attributes(stats, peer);
+            }
+        }
+
+        *previous = peers.clone();
+        self.device_stats(peers)
+    }
+
+    /// Build device totals from per-peer statistics
+    fn device_stats(&self, peers: HashMap<PublicKey, PeerStats>) -> DeviceStats {
+        let names = self.names.lock().unwrap();
+        let now = Instant::now();
+
+        let peer_handshakes = peers
+            .iter()
+            .filter_map(|(public_key, stats)| {
+                let elapsed = stats.last_handshake?.elapsed().ok()?;
+                Some((names.get(public_key)?.clone(), now.checked_sub(elapsed)?))
+            })
+            .collect();
+
+        DeviceStats {
+            tx_bytes: peers.values().map(|stats| stats.tx_bytes).sum(),
+            rx_bytes: peers.values().map(|stats| stats.rx_bytes).sum(),
+            peer_handshakes,
+            peers,
+            ..Default::default()
+        }
+    }
+
+    /// Add a peer, or replace the settings of an existing one
+    pub async fn add_peer(&self, peer: PeerConfig) -> Result<()> {
+        info!("Adding peer '{}' to kernel device {}", peer.name, self.interface_name);
+        self.netlink.set_device(
+            &self.interface_name,
+            &format!("add peer {}", peer.name),
+            vec![WireguardAttribute::Peers(vec![peer_message(&peer)?])],
+        )?;
+
+        self.names
+            .lock()
+            .unwrap()
+            .insert(peer.public_key.clone(), peer.name);
+        Ok(())
+    }
+
+    /// Remove a peer
+    pub async fn remove_peer(&self, public_key: &PublicKey) -> Result<()> {
+        self.netlink.set_device(
+            &self.interface_name,
+            "remove peer",
+            vec![WireguardAttribute::Peers(vec![WireguardPeer(vec![
+                WireguardPeerAttribute::PublicKey(*public_key.as_bytes()),
+                WireguardPeerAttribute::Flags(WireguardPeerFlags::RemoveMe),
+            ])])],
+        )?;
+
+        self.names.lock().unwrap().remove(public_key);
+        self.peer_stats.lock().unwrap().remove(public_key);
+        Ok(())
+    }
+
+    /// Point an existing peer at a new endpoint
+    pub async fn update_endpoint(&self, public_key: &PublicKey, endpoint: SocketAddr) -> Result<()> {
+        self.netlink.set_device(
+            &self.interface_name,
+            "update endpoint",
+            vec![WireguardAttribute::Peers(vec![WireguardPeer(vec![
+                WireguardPeerAttribute::PublicKey(*public_key.as_bytes()),
+                WireguardPeerAttribute::Flags(WireguardPeerFlags::UpdateOnly),
+                WireguardPeerAttribute::Endpoint(endpoint),
+            ])])],
+        )
+    }
+
+    /// Change the name a peer's statistics are reported under
+    ///
+    /// The kernel does not know peer names, so only the agent's record changes.
+    pub async fn rename_peer(&self, public_key: &PublicKey, name: &str) -> Result<()> {
+        match self.names.lock().unwrap().get_mut(public_key) {
+            Some(current) => {
+                *current = name.to_string();
+                Ok(())
+            }
+            None => Err(WgAgentError::NotFound(format!("Peer {} not found", public_key))),
+        }
+    }
+
+    /// Replace the interface private key
+    ///
+    /// The kernel expires every peer's sending keys when the private key
+    /// changes. Outbound packets are queued until a new handshake completes,
+    /// about one round trip after the next packet to each peer; packets from
+    /// peers are still accepted on the old sessions until they rekey.
+    pub async fn rotate_keys(&self, keypair: KeyPair) -> Result<()> {
+        self.netlink.set_device(
+            &self.interface_name,
+            "set private key",
+            vec![WireguardAttribute::PrivateKey(*keypair.private.as_bytes())],
+        )
+    }
+
+    /// Stop the device
+    ///
+    /// The link itself is deleted by the platform when the tunnel stops.
+    pub async fn stop(self) -> Result<()> {
+        info!("Stopping kernel WireGuard device {}", self.interface_name);
+        Ok(())
+    }
+}
+
+/// Build the netlink description of a peer, replacing any previous settings
+fn peer_message(peer: &PeerConfig) -> Result<WireguardPeer> {
+    let allowed_ips = peer
+        .allowed_ips
+        .iter()
+        .map(|cidr| allowed_ip(cidr))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut attributes = vec![
+        WireguardPeerAttribute::PublicKey(*peer.public_key.as_bytes()),
+        WireguardPeerAttribute::Flags(WireguardPeerFlags::ReplaceAllowedIps),
+        // An all-zero key clears a previously set preshared key
+        WireguardPeerAttribute::PresharedKey(
+            peer.preshared_key.as_ref().map_or([0; 32], |psk| *psk.as_bytes()),
+        ),
+        WireguardPeerAttribute::PersistentKeepalive(
+            peer.keepalive_interval
+                .map_or(0, |interval| interval.as_secs().min(u16::MAX as u64) as u16),
+        ),
+        WireguardPeerAttribute::AllowedIps(allowed_ips),
+    ];
+    if let Some(endpoint) = peer.endpoint {
+        attributes.push(WireguardPeerAttribute::Endpoint(endpoint));
+    }
+
+    Ok(WireguardPeer(attributes))
+}
+
+/// Build an allowed IP entry from CIDR notation
+fn allowed_ip(cidr: &str) -> Result<WireguardAllowedIp> {
+    let (ip, prefix_len) = parse_cidr(cidr)?;
+    let family = match ip {
+        IpAddr::V4(_) => WireguardAddressFamily::Ipv4,
+        IpAddr::V6(_) => WireguardAddressFamily::Ipv6,
+    };
+
+    Ok(WireguardAllowedIp(vec![
+        WireguardAllowedIpAttr::Family(family),
+        WireguardAllowedIpAttr::IpAddr(network_address(ip, prefix_len)),
+        WireguardAllowedIpAttr::Cidr(prefix_len),
+    ]))
+}
+
+fn peer_public_key(peer: &WireguardPeer) -> Option<PublicKey> {
+    peer.iter().find_map(|attribute| match attribute {
+        WireguardPeerAttribute::PublicKey(key) => Some(PublicKey::from_bytes(*key)),
+        _ => None,
+    })
+}
+
+/// Convert a kernel handshake time; zero means no handshake yet
+fn handshake_time(time: &WireguardTimeSpec) -> Option<SystemTime> {
+    if time.seconds <= 0 && time.nano_seconds <= 0 {
+        return None;
+    }
+    UNIX_EPOCH.checked_add(Duration::new(
+        time.seconds.max(0) as u64,
+        time.nano_seconds.clamp(0, 999_999_999) as u32,
+    ))
+}
+
+/// Update running statistics from a peer in a get-device reply
+fn apply_peer_attributes(stats: &mut PeerStats, peer: &WireguardPeer) {
+    for attribute in peer.iter() {
+        match attribute {
+            WireguardPeerAttribute::RxBytes(bytes) => stats.rx_bytes = *bytes,
+            WireguardPeerAttribute::TxBytes(bytes) => stats.tx_bytes = *bytes,
+            WireguardPeerAttribute::Endpoint(endpoint) => stats.endpoint = Some(*endpoint),
+            WireguardPeerAttribute::LastHandshake(time) => {
+                let Some(handshake_at) = handshake_time(time) else {
+                    continue;
+                };
+                if stats.last_handshake.is_none_or(|last| handshake_at > last) {
+                    stats.successful_handshakes += 1;
+                }
+                stats.last_handshake = Some(handshake_at);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wireguard::PresharedKey;
+
+    #[test]
+    fn test_peer_message() {
+        let public_key = KeyPair::generate().public;
+        let mut peer = PeerConfig::new("gateway".to_string(), public_key.clone());
+        peer.endpoint = Some("[2001:db8::1]:51820".parse().unwrap());
+        peer.allowed_ips = vec!["10.0.0.7/24".to_string(), "fd00::/64".to_string()];
+        peer.keepalive_interval = Some(Duration::from_secs(25));
+
+        let message = peer_message(&peer).unwrap();
+        assert!(message.contains(&WireguardPeerAttribute::PublicKey(*public_key.as_bytes())));
+        assert!(message.contains(&WireguardPeerAttribute::PresharedKey([0; 32])));
+        assert!(message.contains(&WireguardPeerAttribute::PersistentKeepalive(25)));
+        assert!(message.contains(&WireguardPeerAttribute::Endpoint(peer.endpoint.unwrap())));
+        assert!(message.contains(&WireguardPeerAttribute::AllowedIps(vec![
+            allowed_ip("10.0.0.0/24").unwrap(),
+            allowed_ip("fd00::/64").unwrap(),
+        ])));
+
+        let psk = PresharedKey::generate();
+        peer.preshared_key = Some(psk.clone());
+        let message = peer_message(&peer).unwrap();
+        assert!(message.contains(&WireguardPeerAttribute::PresharedKey(*psk.as_bytes())));
+
+        peer.allowed_ips = vec!["not-a-cidr".to_string()];
+        assert!(peer_message(&peer).is_err());
+    }
+
+    #[test]
+    fn test_allowed_ip_masks_host_bits() {
+        let entry = allowed_ip("10.1.2.3/16").unwrap();
+        assert_eq!(
+            entry.0,
+            vec![
+                WireguardAllowedIpAttr::Family(WireguardAddressFamily::Ipv4),
+                WireguardAllowedIpAttr::IpAddr("10.1.0.0".parse().unwrap()),
+                WireguardAllowedIpAttr::Cidr(16),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_peer_attributes() {
+        let mut stats = PeerStats::default();
+        let never = WireguardPeer(vec![
+            WireguardPeerAttribute::LastHandshake(WireguardTimeSpec::default()),
+            WireguardPeerAttribute::RxBytes(100),
+            WireguardPeerAttribute::TxBytes(200),
+            WireguardPeerAttribute::Endpoint("192.0.2.7:51820".parse().unwrap()),
+        ]);
+        apply_peer_attributes(&mut stats, &never);
+        assert_eq!((stats.rx_bytes, stats.tx_bytes), (100, 200));
+        assert_eq!(stats.endpoint, Some("192.0.2.7:51820".parse().unwrap()));
+        assert!(stats.last_handshake.is_none());
+
+        let handshake = |seconds| {
+            WireguardPeer(vec![WireguardPeerAttribute::LastHandshake(WireguardTimeSpec {
+                seconds,
+                nano_seconds: 0,
+            })])
+        };
+        apply_peer_attributes(&mut stats, &handshake(1_700_000_000));
+        apply_peer_attributes(&mut stats, &handshake(1_700_000_000));
+        assert_eq!(stats.successful_handshakes, 1);
+        apply_peer_attributes(&mut stats, &handshake(1_700_000_120));
+        assert_eq!(stats.successful_handshakes, 2);
+        assert_eq!(
+            stats.last_handshake,
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_120))
+        );
+    }
+}
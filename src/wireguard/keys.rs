@@ -21,11 +21,14 @@ pub struct PrivateKey {
 
 impl PrivateKey {
     /// Generate a new random private key
+    ///
+    /// The key is clamped like `wg genkey` output; x25519 clamps on use anyway.
     pub fn generate() -> Self {
         let secret = StaticSecret::random_from_rng(rand::rngs::OsRng);
-        Self {
-            secret: Zeroizing::new(secret.to_bytes()),
-        }
+        let mut bytes = Zeroizing::new(secret.to_bytes());
+        bytes[0] &= 248;
+        bytes[31] = (bytes[31] & 127) | 64;
+        Self { secret: bytes }
     }
 
     /// Create a private key from raw bytes
@@ -56,24 +59,7 @@ impl PrivateKey {
     /// Load a private key from a file
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
-        
-        // Check file permissions (should be 0600 or stricter)
-        #[cfg(unix)]
-        {
-            let metadata = fs::metadata(path).map_err(|e| {
-                WgAgentError::Config(format!("Failed to read key file {:?}: {}", path, e))
-            })?;
-            let permissions = metadata.permissions();
-            let mode = permissions.mode();
-            
-            // Check if file is readable by others
-            if mode & 0o077 != 0 {
-                return Err(WgAgentError::Permission(format!(
-                    "Private key file {:?} has insecure permissions: {:o} (should be 0600)",
-                    path, mode & 0o777
-                )));
-            }
-        }
+        check_key_file_permissions(path, "Private key")?;
 
         let content = fs::read_to_string(path).map_err(|e| {
             WgAgentError::Config(format!("Failed to read private key file {:?}: {}", path, e))
@@ -109,6 +95,43 @@ impl PrivateKey {
         Ok(())
     }
 
+    /// Atomically replace a key file with this key (0600)
+    ///
+    /// The key is written to a temporary file in the same directory and
+    /// renamed over `path`, so readers see either the old or the new key.
+    pub fn save_to_file_atomic<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file_name = path.file_name().ok_or_else(|| {
+            WgAgentError::Config(format!("Invalid key file path {:?}", path))
+        })?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let tmp_path = dir.join(format!(".{}.tmp", file_name.to_string_lossy()));
+
+        // A leftover temp file from an interrupted rotation is safe to discard
+        let _ = fs::remove_file(&tmp_path);
+
+        let result = self.save_to_file(&tmp_path).and_then(|()| {
+            fs::File::open(&tmp_path)
+                .and_then(|f| f.sync_all())
+                .and_then(|()| fs::rename(&tmp_path, path))
+                .map_err(|e| {
+                    WgAgentError::Config(format!("Failed to replace key file {:?}: {}", path, e))
+                })
+        });
+
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        } else if let Ok(dir) = fs::File::open(dir) {
+            // Persist the rename itself
+            let _ = dir.sync_all();
+        }
+
+        result
+    }
+
     /// Convert to base64-encoded string
     pub fn to_base64(&self) -> String {
         BASE64.encode(*self.secret)
@@ -142,6 +165,131 @@ impl fmt::Display for PrivateKey {
     }
 }
 
+/// WireGuard preshared key (32 bytes, symmetric)
+#[derive(Clone)]
+pub struct PresharedKey {
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl PresharedKey {
+    /// Generate a new random preshared key
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut bytes);
+        Self::from_bytes(bytes)
+    }
+
+    /// Create a preshared key from raw bytes
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self {
+            key: Zeroizing::new(bytes),
+        }
+    }
+
+    /// Parse a preshared key from base64-encoded string
+    pub fn from_base64(s: &str) -> Result<Self> {
+        let decoded = Zeroizing::new(
+            BASE64
+                .decode(s.trim())
+                .map_err(|e| WgAgentError::Config(format!("Invalid base64 preshared key: {}", e)))?,
+        );
+
+        if decoded.len() != 32 {
+            return Err(WgAgentError::Config(format!(
+                "Invalid preshared key length: expected 32 bytes, got {}",
+                decoded.len()
+            )));
+        }
+
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&decoded);
+        Ok(Self::from_bytes(bytes))
+    }
+
+    /// Load a preshared key from a file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        check_key_file_permissions(path, "Preshared key")?;
+
+        let content = Zeroizing::new(fs::read_to_string(path).map_err(|e| {
+            WgAgentError::Config(format!("Failed to read preshared key file {:?}: {}", path, e))
+        })?);
+
+        Self::from_base64(content.trim())
+    }
+
+    /// Convert to base64-encoded string
+    pub fn to_base64(&self) -> String {
+        BASE64.encode(*self.key)
+    }
+
+    /// Get raw bytes (for boringtun)
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.key
+    }
+}
+
+impl fmt::Debug for PresharedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PresharedKey([REDACTED])")
+    }
+}
+
+/// Write a base64-encoded key to a new file (0600)
+///
+/// The parent directory must not be world-writable. An existing file is
+/// only replaced when `force` is set.
+pub fn write_key_file<P: AsRef<Path>>(path: P, encoded: &str, force: bool) -> Result<()> {
+    let path = path.as_ref();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    crate::security::validate_directory_security(dir)?;
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .create_new(!force)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => {
+                WgAgentError::Config(format!("Key file {:?} already exists", path))
+            }
+            _ => WgAgentError::Config(format!("Failed to create key file {:?}: {}", path, e)),
+        })?;
+
+    // `mode` only applies to new files; tighten an overwritten one as well
+    file.set_permissions(fs::Permissions::from_mode(0o600))
+        .and_then(|()| file.write_all(encoded.as_bytes()))
+        .and_then(|()| file.write_all(b"\n"))
+        .map_err(|e| WgAgentError::Config(format!("Failed to write key file {:?}: {}", path, e)))
+}
+
+/// Reject key files that are readable by group or others (should be 0600)
+fn check_key_file_permissions(path: &Path, kind: &str) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let metadata = fs::metadata(path).map_err(|e| {
+            WgAgentError::Config(format!("Failed to read key file {:?}: {}", path, e))
+        })?;
+        let mode = metadata.permissions().mode();
+
+        if mode & 0o077 != 0 {
+            return Err(WgAgentError::Permission(format!(
+                "{} file {:?} has insecure permissions: {:o} (should be 0600)",
+                kind,
+                path,
+                mode & 0o777
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 /// WireGuard public key (32 bytes, x25519)
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct PublicKey {
@@ -245,6 +393,11 @@ mod tests {
         let keypair = KeyPair::generate();
         assert_eq!(keypair.private.as_bytes().len(), 32);
         assert_eq!(keypair.public.as_bytes().len(), 32);
+
+        // Clamped like `wg genkey`
+        let bytes = keypair.private.as_bytes();
+        assert_eq!(bytes[0] & 7, 0);
+        assert_eq!(bytes[31] & 0xc0, 0x40);
     }
 
     #[test]
@@ -305,6 +458,67 @@ mod tests {
         assert_eq!(private.as_bytes(), loaded.as_bytes());
     }
 
+    #[test]
+    fn test_preshared_key_from_file() {
+        let psk = PresharedKey::generate();
+        let temp_file = NamedTempFile::new().unwrap();
+        fs::write(temp_file.path(), format!("{}\n", psk.to_base64())).unwrap();
+
+        // Default temp file mode is 0600
+        let loaded = PresharedKey::from_file(temp_file.path()).unwrap();
+        assert_eq!(psk.as_bytes(), loaded.as_bytes());
+
+        fs::set_permissions(temp_file.path(), fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(matches!(
+            PresharedKey::from_file(temp_file.path()),
+            Err(WgAgentError::Permission(_))
+        ));
+    }
+
+    #[test]
+    fn test_preshared_key_not_logged() {
+        let psk = PresharedKey::generate();
+        let debug_str = format!("{:?}", psk);
+        assert!(debug_str.contains("REDACTED"));
+        assert!(!debug_str.contains(&psk.to_base64()));
+        assert!(PresharedKey::from_base64(&BASE64.encode([0u8; 16])).is_err());
+    }
+
+    #[test]
+    fn test_save_private_key_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("private.key");
+
+        let first = PrivateKey::generate();
+        first.save_to_file_atomic(&path).unwrap();
+        let second = PrivateKey::generate();
+        second.save_to_file_atomic(&path).unwrap();
+
+        let loaded = PrivateKey::from_file(&path).unwrap();
+        assert_eq!(second.as_bytes(), loaded.as_bytes());
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // No temp files are left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_write_key_file_refuses_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.key");
+
+        let first = PrivateKey::generate();
+        write_key_file(&path, &first.to_base64(), false).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(write_key_file(&path, &PrivateKey::generate().to_base64(), false).is_err());
+        assert_eq!(PrivateKey::from_file(&path).unwrap().as_bytes(), first.as_bytes());
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let psk = PresharedKey::generate();
+        write_key_file(&path, &psk.to_base64(), true).unwrap();
+        assert_eq!(PresharedKey::from_file(&path).unwrap().as_bytes(), psk.as_bytes());
+    }
+
     #[test]
     fn test_invalid_base64() {
         assert!(PrivateKey::from_base64("invalid!@#$").is_err());
@@ -4,12 +4,14 @@
 //! because the TUN device integration is more mature and stable.
 
 use crate::error::{Result, WgAgentError};
+use crate::platform::macos::route_family;
+use crate::platform::AddressFamily;
 use crate::wireguard::{DeviceConfig, DeviceStats};
 use std::path::PathBuf;
 use std::process::{Child, Command, Stdio};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use std::fs;
 
 /// macOS WireGuard device using wireguard-go
@@ -81,6 +83,12 @@ impl MacOsWgDevice {
         if config.listen_port > 0 {
             wg_config.push_str(&format!("ListenPort = {}\n", config.listen_port));
         }
+        if let Some(bind_address) = config.bind_address {
+            warn!(
+                "wireguard-go listens on all addresses; bind_address {} is ignored",
+                bind_address
+            );
+        }
 
         wg_config.push('\n');
 
@@ -92,6 +100,10 @@ impl MacOsWgDevice {
                 peer.public_key.to_base64()
             ));
 
+            if let Some(ref psk) = peer.preshared_key {
+                wg_config.push_str(&format!("PresharedKey = {}\n", psk.to_base64()));
+            }
+
             if let Some(endpoint) = peer.endpoint {
                 wg_config.push_str(&format!("Endpoint = {}\n", endpoint));
             }
@@ -117,7 +129,7 @@ impl MacOsWgDevice {
     }
 
     /// Start the WireGuard tunnel using wg-quick
-    pub async fn start(&mut self, address: &str, routes: &[String]) -> Result<()> {
+    pub async fn start(&mut self, addresses: &[String], routes: &[String]) -> Result<()> {
         info!("Starting wireguard-go tunnel on {}", self.interface_name);
 
         // First, bring up the interface with wireguard-go
@@ -154,25 +166,28 @@ impl MacOsWgDevice {
 
         info!("Applied WireGuard configuration to {}", actual_interface);
 
-        // Configure IP address
-        Command::new("ifconfig")
-            .args([
-                &actual_interface,
-                address.split('/').next().unwrap(),
-                address.split('/').next().unwrap(),
-                "netmask",
-                "255.255.255.0",
-            ])
-            .output()
-            .map_err(|e| {
+        // Configure IP addresses
+        for address in addresses {
+            let (ip, prefix_len) = address.split_once('/').unwrap_or((address, "128"));
+            let args = match AddressFamily::of(ip)? {
+                AddressFamily::V4 => {
+                    vec![actual_interface.as_str(), ip, ip, "netmask", "255.255.255.0"]
+                }
+                AddressFamily::V6 => {
+                    vec![actual_interface.as_str(), "inet6", ip, "prefixlen", prefix_len, "alias"]
+                }
+            };
+            Command::new("ifconfig").args(&args).output().map_err(|e| {
                 WgAgentError::Platform(format!("Failed to set interface address: {}", e))
             })?;
+        }
 
         // Add routes
         for route in routes {
             debug!("Adding route: {} via {}", route, actual_interface);
+            let family = route_family(route)?;
             let _ = Command::new("route")
-                .args(["add", "-net", route, "-interface", &actual_interface])
+                .args(["add", family, "-net", route, "-interface", &actual_interface])
                 .output();
         }
 
@@ -209,11 +224,141 @@ impl MacOsWgDevice {
         &self.interface_name
     }
 
+    /// Get the configured UDP listen port (None if wireguard-go picked one)
+    pub fn listen_port(&self) -> Option<u16> {
+        Some(self._config.listen_port).filter(|port| *port > 0)
+    }
+
     /// Get device statistics
     pub async fn stats(&self) -> DeviceStats {
         self.stats.read().await.clone()
     }
 
+    /// Replace the interface private key via `wg set`
+    ///
+    /// wireguard-go keeps existing sessions until new handshakes complete.
+    pub async fn set_private_key(&self, private_key: &crate::wireguard::PrivateKey) -> Result<()> {
+        let key_file = self.config_file.with_extension("key");
+        private_key.save_to_file(&key_file)?;
+
+        let output = Command::new("wg")
+            .arg("set")
+            .arg(&self.interface_name)
+            .arg("private-key")
+            .arg(&key_file)
+            .output();
+        let _ = fs::remove_file(&key_file);
+
+        let output = output
+            .map_err(|e| WgAgentError::Platform(format!("Failed to run wg set: {}", e)))?;
+        if !output.status.success() {
+            return Err(WgAgentError::Platform(format!(
+                "Failed to set private key: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Add or replace a peer via `wg set`
+    pub async fn add_peer(&self, peer: &crate::wireguard::PeerConfig) -> Result<()> {
+        let mut args = vec![
+            "set".to_string(),
+            self.interface_name.clone(),
+            "peer".to_string(),
+            peer.public_key.to_base64(),
+            "allowed-ips".to_string(),
+            peer.allowed_ips.join(","),
+        ];
+
+        if let Some(endpoint) = peer.endpoint {
+            args.push("endpoint".to_string());
+            args.push(endpoint.to_string());
+        }
+
+        if let Some(interval) = peer.keepalive_interval {
+            args.push("persistent-keepalive".to_string());
+            args.push(interval.as_secs().to_string());
+        }
+
+        // wg only reads preshared keys from files
+        let psk_file = self.config_file.with_extension("psk");
+        if let Some(ref psk) = peer.preshared_key {
+            fs::write(&psk_file, format!("{}\n", psk.to_base64())).map_err(|e| {
+                WgAgentError::Platform(format!("Failed to write preshared key file: {}", e))
+            })?;
+            args.push("preshared-key".to_string());
+            args.push(psk_file.to_string_lossy().to_string());
+        }
+
+        let output = Command::new("wg").args(&args).output();
+        let _ = fs::remove_file(&psk_file);
+
+        let output = output
+            .map_err(|e| WgAgentError::Platform(format!("Failed to run wg set: {}", e)))?;
+        if !output.status.success() {
+            return Err(WgAgentError::Platform(format!(
+                "Failed to add peer '{}': {}",
+                peer.name,
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Remove a peer via `wg set`
+    pub async fn remove_peer(&self, public_key: &crate::wireguard::PublicKey) -> Result<()> {
+        let output = Command::new("wg")
+            .args([
+                "set",
+                &self.interface_name,
+                "peer",
+                &public_key.to_base64(),
+                "remove",
+            ])
+            .output()
+            .map_err(|e| WgAgentError::Platform(format!("Failed to run wg set: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(WgAgentError::Platform(format!(
+                "Failed to remove peer: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Set a peer's endpoint via `wg set`
+    pub async fn update_endpoint(
+        &self,
+        public_key: &crate::wireguard::PublicKey,
+        endpoint: std::net::SocketAddr,
+    ) -> Result<()> {
+        let output = Command::new("wg")
+            .args([
+                "set",
+                &self.interface_name,
+                "peer",
+                &public_key.to_base64(),
+                "endpoint",
+                &endpoint.to_string(),
+            ])
+            .output()
+            .map_err(|e| WgAgentError::Platform(format!("Failed to run wg set: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(WgAgentError::Platform(format!(
+                "Failed to update peer endpoint: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(())
+    }
+
     /// Stop the device and clean up
     pub async fn stop(mut self) -> Result<()> {
         info!("Stopping macOS WireGuard device");
@@ -2,19 +2,40 @@
 //!
 //! This module handles the WireGuard protocol implementation, key management,
 //! and peer configuration using boringtun on Linux/Windows, and wireguard-go on macOS.
+//! On Linux the in-kernel WireGuard module is used instead of boringtun when available.
 
+mod allowed_ips;
 mod device;
+mod io;
 mod keys;
 mod peer;
+mod resolver;
 mod tunnel;
 
+#[cfg(target_os = "linux")]
+mod batch_io;
+
+#[cfg(target_os = "linux")]
+mod kernel_device;
+
 #[cfg(target_os = "macos")]
 mod macos_device;
 
-pub use device::{DeviceConfig, DeviceStats, WgDevice};
-pub use keys::{KeyPair, PrivateKey, PublicKey};
+pub use allowed_ips::AllowedIps;
+pub use device::{DeviceConfig, DeviceEvent, DeviceStats, UdpBatchStats, WgDevice};
+pub use io::{
+    IoFuture, MemoryNetwork, MemorySocket, MemoryTun, MemoryTunHandle, RecvMeta, TunDeviceIo,
+    TunIo, UdpIo,
+};
+pub use keys::{write_key_file, KeyPair, PresharedKey, PrivateKey, PublicKey};
 pub use peer::{Peer, PeerConfig, PeerStats};
-pub use tunnel::{Tunnel, TunnelConfig, TunnelState};
+pub use resolver::{
+    resolve_endpoint, EndpointResolver, ResolveFuture, StaticResolver, SystemResolver,
+};
+pub use tunnel::{DeviceIo, PeerInfo, Tunnel, TunnelConfig, TunnelState};
+
+#[cfg(target_os = "linux")]
+pub use kernel_device::KernelWgDevice;
 
 #[cfg(target_os = "macos")]
 pub use macos_device::MacOsWgDevice;
@@ -4,7 +4,7 @@
 
 use crate::config::PeerConfig as ConfigPeer;
 use crate::error::{Result, WgAgentError};
-use crate::wireguard::PublicKey;
+use crate::wireguard::{PresharedKey, PublicKey};
 use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration, SystemTime};
 use tracing::{debug, warn};
@@ -22,6 +22,8 @@ pub struct PeerStats {
     pub handshake_attempts: u64,
     /// Number of successful handshakes
     pub successful_handshakes: u64,
+    /// Endpoint the device currently sends to, if it knows one
+    pub endpoint: Option<SocketAddr>,
 }
 
 impl PeerStats {
@@ -53,12 +55,16 @@ pub struct PeerConfig {
     pub public_key: PublicKey,
     /// Peer endpoint address
     pub endpoint: Option<SocketAddr>,
+    /// Unresolved hostname endpoint (host:port), resolved when the tunnel starts
+    pub endpoint_host: Option<String>,
+    /// How often to re-resolve `endpoint_host` (None = never)
+    pub endpoint_refresh: Option<Duration>,
     /// Allowed IP addresses/ranges
     pub allowed_ips: Vec<String>,
     /// Persistent keepalive interval
     pub keepalive_interval: Option<Duration>,
     /// Preshared key (optional, for additional security)
-    pub preshared_key: Option<[u8; 32]>,
+    pub preshared_key: Option<PresharedKey>,
 }
 
 impl PeerConfig {
@@ -68,6 +74,8 @@ impl PeerConfig {
             name,
             public_key,
             endpoint: None,
+            endpoint_host: None,
+            endpoint_refresh: None,
             allowed_ips: Vec::new(),
             keepalive_interval: None,
             preshared_key: None,
@@ -75,14 +83,27 @@ impl PeerConfig {
     }
 
     /// Parse endpoint from string (host:port)
+    ///
+    /// IP endpoints are used directly; hostname endpoints are stored in
+    /// `endpoint_host` and resolved when the tunnel starts.
     pub fn set_endpoint(&mut self, endpoint: &str) -> Result<()> {
-        let addr: SocketAddr = endpoint.parse().map_err(|e| {
-            WgAgentError::Config(format!("Invalid endpoint '{}': {}", endpoint, e))
-        })?;
-        self.endpoint = Some(addr);
+        if let Ok(addr) = endpoint.parse::<SocketAddr>() {
+            self.endpoint = Some(addr);
+            self.endpoint_host = None;
+            return Ok(());
+        }
+
+        crate::config::validation::validate_endpoint(endpoint)?;
+        self.endpoint = None;
+        self.endpoint_host = Some(endpoint.to_string());
         Ok(())
     }
 
+    /// Set endpoint re-resolution interval in seconds (0 = never)
+    pub fn set_endpoint_refresh_secs(&mut self, secs: u64) {
+        self.endpoint_refresh = (secs > 0).then(|| Duration::from_secs(secs));
+    }
+
     /// Set keepalive interval in seconds
     pub fn set_keepalive_secs(&mut self, secs: u16) {
         if secs > 0 {
@@ -100,7 +121,7 @@ impl PeerConfig {
         }
 
         // Warn if no endpoint and no allowed IPs
-        if self.endpoint.is_none() && self.allowed_ips.is_empty() {
+        if self.endpoint.is_none() && self.endpoint_host.is_none() && self.allowed_ips.is_empty() {
             warn!(
                 "Peer '{}' has no endpoint and no allowed IPs - this peer may not be reachable",
                 self.name
@@ -149,6 +170,23 @@ impl PeerConfig {
     }
 }
 
+impl PeerConfig {
+    /// Build a peer configuration, loading its preshared key if configured
+    pub fn from_config(config: ConfigPeer) -> Result<Self> {
+        let preshared_key = match (&config.preshared_key, &config.preshared_key_path) {
+            (Some(inline), _) => Some(PresharedKey::from_base64(inline)?),
+            (None, Some(path)) => Some(PresharedKey::from_file(path)?),
+            (None, None) => None,
+        };
+
+        let mut peer = Self::from(config);
+        peer.preshared_key = preshared_key;
+        Ok(peer)
+    }
+}
+
+/// Converts everything except the preshared key; use [`PeerConfig::from_config`]
+/// to also load it.
 impl From<ConfigPeer> for PeerConfig {
     fn from(config: ConfigPeer) -> Self {
         let mut peer = Self::new(
@@ -157,9 +195,11 @@ impl From<ConfigPeer> for PeerConfig {
                 .expect("Public key should be validated in config phase"),
         );
 
-        // Set endpoint
-        if let Err(e) = peer.set_endpoint(&config.endpoint) {
-            warn!("Failed to parse peer endpoint: {}", e);
+        // Set endpoint (peers without one are reachable once they contact us)
+        if !config.endpoint.is_empty() {
+            if let Err(e) = peer.set_endpoint(&config.endpoint) {
+                warn!("Failed to parse peer endpoint: {}", e);
+            }
         }
 
         // Set allowed IPs
@@ -168,6 +208,9 @@ impl From<ConfigPeer> for PeerConfig {
         // Set keepalive
         peer.set_keepalive_secs(config.persistent_keepalive_secs);
 
+        // Set hostname re-resolution interval
+        peer.set_endpoint_refresh_secs(config.endpoint_refresh_secs);
+
         peer
     }
 }
@@ -285,6 +328,32 @@ mod tests {
     use super::*;
     use crate::wireguard::PrivateKey;
 
+    #[test]
+    fn test_peer_config_from_config_preshared_key() {
+        let psk = PresharedKey::generate();
+        let config = ConfigPeer {
+            name: "psk-peer".to_string(),
+            public_key: PrivateKey::generate().public_key().to_base64(),
+            endpoint: "192.168.1.1:51820".to_string(),
+            allowed_ips: vec!["10.0.0.0/24".to_string()],
+            persistent_keepalive_secs: 25,
+            endpoint_refresh_secs: 300,
+            preshared_key_path: None,
+            preshared_key: Some(psk.to_base64()),
+        };
+
+        let peer = PeerConfig::from_config(config.clone()).unwrap();
+        assert_eq!(peer.preshared_key.unwrap().as_bytes(), psk.as_bytes());
+
+        // A missing key file is an error rather than silently dropping the PSK
+        let config = ConfigPeer {
+            preshared_key: None,
+            preshared_key_path: Some("/nonexistent/psk.key".to_string()),
+            ..config
+        };
+        assert!(PeerConfig::from_config(config).is_err());
+    }
+
     #[test]
     fn test_peer_config_new() {
         let public_key = PrivateKey::generate().public_key();
@@ -306,6 +375,22 @@ mod tests {
         assert_eq!(config.endpoint.unwrap().port(), 51820);
     }
 
+    #[test]
+    fn test_peer_config_set_hostname_endpoint() {
+        let public_key = PrivateKey::generate().public_key();
+        let mut config = PeerConfig::new("test-peer".to_string(), public_key);
+
+        config.set_endpoint("vpn.example.com:51820").unwrap();
+        assert!(config.endpoint.is_none());
+        assert_eq!(config.endpoint_host.as_deref(), Some("vpn.example.com:51820"));
+
+        config.set_endpoint("192.168.1.1:51820").unwrap();
+        assert!(config.endpoint.is_some());
+        assert!(config.endpoint_host.is_none());
+
+        assert!(config.set_endpoint("no-port").is_err());
+    }
+
     #[test]
     fn test_peer_config_set_keepalive() {
         let public_key = PrivateKey::generate().public_key();
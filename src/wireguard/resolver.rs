@@ -0,0 +1,116 @@
+//! Peer endpoint resolution
+//!
+//! This module resolves peer endpoints given as `host:port` into socket
+//! addresses. The resolver is pluggable so tests can substitute a static
+//! table for system DNS.
+
+use crate::error::{Result, WgAgentError};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::RwLock;
+
+/// Boxed future returned by [`EndpointResolver::resolve`]
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<SocketAddr>>> + Send + 'a>>;
+
+/// Resolves `host:port` endpoints into socket addresses
+pub trait EndpointResolver: Send + Sync {
+    /// Resolve an endpoint string to one or more socket addresses
+    fn resolve<'a>(&'a self, endpoint: &'a str) -> ResolveFuture<'a>;
+}
+
+/// Resolver backed by the system DNS configuration
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl EndpointResolver for SystemResolver {
+    fn resolve<'a>(&'a self, endpoint: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host(endpoint).await.map_err(|e| {
+                WgAgentError::Config(format!("Failed to resolve endpoint '{}': {}", endpoint, e))
+            })?;
+            Ok(addrs.collect())
+        })
+    }
+}
+
+/// Resolver with a fixed table of endpoints, for tests and offline use
+#[derive(Debug, Default)]
+pub struct StaticResolver {
+    entries: RwLock<HashMap<String, Vec<SocketAddr>>>,
+}
+
+impl StaticResolver {
+    /// Create an empty static resolver
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the addresses returned for an endpoint
+    pub fn insert(&self, endpoint: &str, addrs: Vec<SocketAddr>) {
+        self.entries
+            .write()
+            .unwrap()
+            .insert(endpoint.to_string(), addrs);
+    }
+}
+
+impl EndpointResolver for StaticResolver {
+    fn resolve<'a>(&'a self, endpoint: &'a str) -> ResolveFuture<'a> {
+        let result = self
+            .entries
+            .read()
+            .unwrap()
+            .get(endpoint)
+            .cloned()
+            .ok_or_else(|| {
+                WgAgentError::NotFound(format!("No static entry for endpoint '{}'", endpoint))
+            });
+        Box::pin(async move { result })
+    }
+}
+
+/// Resolve an endpoint and pick the address to use
+pub async fn resolve_endpoint(
+    resolver: &dyn EndpointResolver,
+    endpoint: &str,
+) -> Result<SocketAddr> {
+    resolver
+        .resolve(endpoint)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            WgAgentError::Config(format!("Endpoint '{}' resolved to no addresses", endpoint))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_static_resolver() {
+        let resolver = StaticResolver::new();
+        let addr: SocketAddr = "192.0.2.10:51820".parse().unwrap();
+        resolver.insert("vpn.example.com:51820", vec![addr]);
+
+        let resolved = resolve_endpoint(&resolver, "vpn.example.com:51820").await.unwrap();
+        assert_eq!(resolved, addr);
+        assert!(resolve_endpoint(&resolver, "other.example.com:51820").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_static_resolver_empty_result() {
+        let resolver = StaticResolver::new();
+        resolver.insert("empty.example.com:51820", vec![]);
+        assert!(resolve_endpoint(&resolver, "empty.example.com:51820").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_system_resolver_literal() {
+        let resolved = resolve_endpoint(&SystemResolver, "127.0.0.1:51820").await.unwrap();
+        assert_eq!(resolved, "127.0.0.1:51820".parse::<SocketAddr>().unwrap());
+    }
+}
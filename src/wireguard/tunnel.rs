@@ -3,18 +3,28 @@
 //! This module handles the complete lifecycle of a WireGuard tunnel,
 //! including creation, configuration, and teardown.
 
-use crate::config::NetworkConfig;
+use crate::config::{NetworkConfig, WireguardBackend};
 use crate::error::{Result, WgAgentError};
 use crate::platform::{get_platform, Platform};
-use crate::wireguard::{DeviceConfig, KeyPair, Peer, PeerConfig};
+use crate::wireguard::{
+    resolve_endpoint, AllowedIps, DeviceConfig, EndpointResolver, KeyPair, Peer, PeerConfig, PeerStats,
+    PublicKey, SystemResolver, TunIo, UdpBatchStats, UdpIo,
+};
+#[cfg(target_os = "linux")]
+use crate::platform::kernel_wireguard_available;
+#[cfg(target_os = "linux")]
+use crate::wireguard::KernelWgDevice;
 #[cfg(target_os = "macos")]
 use crate::wireguard::MacOsWgDevice;
 #[cfg(not(target_os = "macos"))]
 use crate::wireguard::WgDevice;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{error, info, warn};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
 
 /// Tunnel state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -25,6 +35,10 @@ pub enum TunnelState {
     Starting,
     /// Tunnel is active and running
     Active,
+    /// Tunnel is running but no peer expected to handshake has done so recently
+    Degraded,
+    /// Tunnel failed and is waiting to be restarted by the supervisor
+    Reconnecting,
     /// Tunnel is being torn down
     Stopping,
     /// Tunnel is stopped
@@ -36,20 +50,30 @@ pub enum TunnelState {
 impl TunnelState {
     /// Check if the tunnel is in a running state
     pub fn is_running(&self) -> bool {
-        matches!(self, TunnelState::Active)
+        matches!(self, TunnelState::Active | TunnelState::Degraded)
     }
 
     /// Check if the tunnel can be started
     pub fn can_start(&self) -> bool {
         matches!(
             self,
-            TunnelState::Uninitialized | TunnelState::Stopped | TunnelState::Error
+            TunnelState::Uninitialized
+                | TunnelState::Stopped
+                | TunnelState::Error
+                | TunnelState::Reconnecting
         )
     }
 
     /// Check if the tunnel can be stopped
     pub fn can_stop(&self) -> bool {
-        matches!(self, TunnelState::Active | TunnelState::Starting)
+        matches!(
+            self,
+            TunnelState::Active
+                | TunnelState::Starting
+                | TunnelState::Degraded
+                | TunnelState::Reconnecting
+                | TunnelState::Error
+        )
     }
 }
 
@@ -59,6 +83,8 @@ impl std::fmt::Display for TunnelState {
             TunnelState::Uninitialized => write!(f, "uninitialized"),
             TunnelState::Starting => write!(f, "starting"),
             TunnelState::Active => write!(f, "active"),
+            TunnelState::Degraded => write!(f, "degraded"),
+            TunnelState::Reconnecting => write!(f, "reconnecting"),
             TunnelState::Stopping => write!(f, "stopping"),
             TunnelState::Stopped => write!(f, "stopped"),
             TunnelState::Error => write!(f, "error"),
@@ -75,8 +101,16 @@ pub struct TunnelConfig {
     pub mtu: u16,
     /// DNS servers
     pub dns_servers: Vec<String>,
-    /// Interface IP address (CIDR notation)
-    pub address: Option<String>,
+    /// Interface IP addresses (CIDR notation, IPv4 and/or IPv6)
+    pub addresses: Vec<String>,
+    /// UDP listen port (0 = random)
+    pub listen_port: u16,
+    /// Address to bind the UDP socket to (None = dual-stack wildcard)
+    pub bind_address: Option<IpAddr>,
+    /// WireGuard implementation to use
+    pub backend: WireguardBackend,
+    /// TUN queues for the userspace device (0 = one per CPU)
+    pub queues: usize,
     /// Our key pair
     pub keypair: KeyPair,
     /// Peer configurations
@@ -93,14 +127,28 @@ impl TunnelConfig {
         let peers: Vec<PeerConfig> = config
             .peers
             .iter()
-            .map(|p| PeerConfig::from(p.clone()))
-            .collect();
+            .map(|p| PeerConfig::from_config(p.clone()))
+            .collect::<Result<_>>()?;
+
+        let bind_address = config
+            .bind_address
+            .as_deref()
+            .map(|addr| {
+                addr.parse::<IpAddr>().map_err(|_| {
+                    WgAgentError::Config(format!("Invalid bind address: {}", addr))
+                })
+            })
+            .transpose()?;
 
         Ok(Self {
             interface: config.interface.clone(),
             mtu: config.mtu,
             dns_servers: config.dns.clone(),
-            address: config.address.clone(),
+            addresses: config.addresses.clone(),
+            listen_port: config.listen_port,
+            bind_address,
+            backend: config.backend,
+            queues: config.queues,
             keypair,
             peers,
         })
@@ -126,6 +174,22 @@ impl TunnelConfig {
             peer.validate()?;
         }
 
+        // A range can only be routed to one peer
+        let mut routes = AllowedIps::new();
+        for (index, peer) in self.peers.iter().enumerate() {
+            for allowed_ip in &peer.allowed_ips {
+                match routes.insert_cidr(allowed_ip, index)? {
+                    Some(owner) if owner != index => {
+                        return Err(WgAgentError::Config(format!(
+                            "Allowed IP {} of peer '{}' is already routed to peer '{}'",
+                            allowed_ip, peer.name, self.peers[owner].name
+                        )));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
         if self.peers.is_empty() {
             warn!("Tunnel has no peers configured");
         }
@@ -134,47 +198,217 @@ impl TunnelConfig {
     }
 }
 
-/// Device wrapper to handle both implementations
+impl TunnelConfig {
+    /// Check if moving to `other` needs the device to be recreated
+    fn requires_restart(&self, other: &TunnelConfig) -> bool {
+        self.interface != other.interface
+            || self.mtu != other.mtu
+            || self.addresses != other.addresses
+            || self.listen_port != other.listen_port
+            || self.bind_address != other.bind_address
+            || self.backend != other.backend
+            || self.queues != other.queues
+            || self.keypair.public != other.keypair.public
+    }
+}
+
+/// How a reload applies a peer whose public key stays configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PeerChange {
+    /// Nothing to do
+    Unchanged,
+    /// Only the name or endpoint changed; the peer keeps its sessions
+    Endpoint,
+    /// The peer must be removed and added again, dropping its sessions
+    Replace,
+}
+
+/// Work out how a reload moves a peer from `old` to `new`
+fn peer_change(old: &PeerConfig, new: &PeerConfig) -> PeerChange {
+    if old.allowed_ips != new.allowed_ips
+        || old.keepalive_interval != new.keepalive_interval
+        || old.preshared_key.as_ref().map(|k| k.as_bytes())
+            != new.preshared_key.as_ref().map(|k| k.as_bytes())
+    {
+        PeerChange::Replace
+    } else if old.name != new.name
+        || old.endpoint != new.endpoint
+        || old.endpoint_host != new.endpoint_host
+        || old.endpoint_refresh != new.endpoint_refresh
+    {
+        PeerChange::Endpoint
+    } else {
+        PeerChange::Unchanged
+    }
+}
+
+/// Peer changes computed by a reload
+#[derive(Default)]
+struct PeerChanges {
+    /// Peers to remove, including replaced ones
+    removed: Vec<PeerConfig>,
+    /// Peers changed in place, as (old, new)
+    updated: Vec<(PeerConfig, PeerConfig)>,
+    /// Peers to add, including replaced ones
+    added: Vec<PeerConfig>,
+}
+
+/// Collect the routes needed for a set of peers
+fn route_set(peers: &[PeerConfig]) -> HashSet<String> {
+    peers.iter().flat_map(|p| p.allowed_ips.iter().cloned()).collect()
+}
+
+/// Device wrapper to handle each implementation
 enum DeviceWrapper {
     #[cfg(not(target_os = "macos"))]
     Boringtun(WgDevice),
+    #[cfg(target_os = "linux")]
+    Kernel(KernelWgDevice),
     #[cfg(target_os = "macos")]
     WireguardGo(MacOsWgDevice),
 }
 
 impl DeviceWrapper {
-    fn _interface_name(&self) -> &str {
+    /// Short name of the implementation, as shown in status output
+    fn backend_name(&self) -> &'static str {
+        match self {
+            #[cfg(not(target_os = "macos"))]
+            DeviceWrapper::Boringtun(_) => "userspace",
+            #[cfg(target_os = "linux")]
+            DeviceWrapper::Kernel(_) => "kernel",
+            #[cfg(target_os = "macos")]
+            DeviceWrapper::WireguardGo(_) => "wireguard-go",
+        }
+    }
+
+    fn interface_name(&self) -> &str {
         match self {
             #[cfg(not(target_os = "macos"))]
             DeviceWrapper::Boringtun(d) => d.interface_name(),
+            #[cfg(target_os = "linux")]
+            DeviceWrapper::Kernel(d) => d.interface_name(),
             #[cfg(target_os = "macos")]
             DeviceWrapper::WireguardGo(d) => d.interface_name(),
         }
     }
 
+    fn listen_port(&self) -> Option<u16> {
+        match self {
+            #[cfg(not(target_os = "macos"))]
+            DeviceWrapper::Boringtun(d) => d.local_addr().map(|addr| addr.port()),
+            #[cfg(target_os = "linux")]
+            DeviceWrapper::Kernel(d) => d.listen_port(),
+            #[cfg(target_os = "macos")]
+            DeviceWrapper::WireguardGo(d) => d.listen_port(),
+        }
+    }
+
+    /// Check that the device is still processing packets
+    ///
+    /// Kernel and wireguard-go devices run outside the agent and are assumed alive.
+    fn is_alive(&self) -> bool {
+        match self {
+            #[cfg(not(target_os = "macos"))]
+            DeviceWrapper::Boringtun(d) => d.is_alive(),
+            #[cfg(target_os = "linux")]
+            DeviceWrapper::Kernel(_) => true,
+            #[cfg(target_os = "macos")]
+            DeviceWrapper::WireguardGo(_) => true,
+        }
+    }
+
     async fn stats(&self) -> crate::wireguard::DeviceStats {
         match self {
             #[cfg(not(target_os = "macos"))]
             DeviceWrapper::Boringtun(d) => d.stats().await,
+            #[cfg(target_os = "linux")]
+            DeviceWrapper::Kernel(d) => d.stats().await,
             #[cfg(target_os = "macos")]
             DeviceWrapper::WireguardGo(d) => d.stats().await,
         }
     }
 
+    async fn add_peer(&self, peer: PeerConfig) -> Result<()> {
+        match self {
+            #[cfg(not(target_os = "macos"))]
+            DeviceWrapper::Boringtun(d) => d.add_peer(peer).await,
+            #[cfg(target_os = "linux")]
+            DeviceWrapper::Kernel(d) => d.add_peer(peer).await,
+            #[cfg(target_os = "macos")]
+            DeviceWrapper::WireguardGo(d) => d.add_peer(&peer).await,
+        }
+    }
+
+    async fn remove_peer(&self, public_key: &PublicKey) -> Result<()> {
+        match self {
+            #[cfg(not(target_os = "macos"))]
+            DeviceWrapper::Boringtun(d) => d.remove_peer(public_key).await,
+            #[cfg(target_os = "linux")]
+            DeviceWrapper::Kernel(d) => d.remove_peer(public_key).await,
+            #[cfg(target_os = "macos")]
+            DeviceWrapper::WireguardGo(d) => d.remove_peer(public_key).await,
+        }
+    }
+
+    async fn update_endpoint(&self, public_key: &PublicKey, endpoint: SocketAddr) -> Result<()> {
+        match self {
+            #[cfg(not(target_os = "macos"))]
+            DeviceWrapper::Boringtun(d) => d.update_endpoint(public_key, endpoint).await,
+            #[cfg(target_os = "linux")]
+            DeviceWrapper::Kernel(d) => d.update_endpoint(public_key, endpoint).await,
+            #[cfg(target_os = "macos")]
+            DeviceWrapper::WireguardGo(d) => d.update_endpoint(public_key, endpoint).await,
+        }
+    }
+
+    async fn rename_peer(&self, public_key: &PublicKey, name: &str) -> Result<()> {
+        match self {
+            #[cfg(not(target_os = "macos"))]
+            DeviceWrapper::Boringtun(d) => d.rename_peer(public_key, name).await,
+            #[cfg(target_os = "linux")]
+            DeviceWrapper::Kernel(d) => d.rename_peer(public_key, name).await,
+            // wireguard-go does not know peer names
+            #[cfg(target_os = "macos")]
+            DeviceWrapper::WireguardGo(_) => Ok(()),
+        }
+    }
+
+    async fn rotate_keys(&self, keypair: KeyPair) -> Result<()> {
+        match self {
+            #[cfg(not(target_os = "macos"))]
+            DeviceWrapper::Boringtun(d) => d.rotate_keys(keypair).await,
+            #[cfg(target_os = "linux")]
+            DeviceWrapper::Kernel(d) => d.rotate_keys(keypair).await,
+            #[cfg(target_os = "macos")]
+            DeviceWrapper::WireguardGo(d) => d.set_private_key(&keypair.private).await,
+        }
+    }
+
     async fn stop(self) -> Result<()> {
         match self {
             #[cfg(not(target_os = "macos"))]
             DeviceWrapper::Boringtun(d) => d.stop().await,
+            #[cfg(target_os = "linux")]
+            DeviceWrapper::Kernel(d) => d.stop().await,
             #[cfg(target_os = "macos")]
             DeviceWrapper::WireguardGo(d) => d.stop().await,
         }
     }
 }
 
+/// Opens the packet I/O of userspace devices in place of a TUN device and UDP socket
+///
+/// Used to run tunnels over in-memory links. Ignored on macOS, where tunnels
+/// always run wireguard-go.
+pub trait DeviceIo: Send + Sync {
+    /// Open the I/O for a device about to be created from `config`
+    fn open(&self, config: &DeviceConfig) -> Result<(Arc<dyn TunIo>, Arc<dyn UdpIo>)>;
+}
+
 /// WireGuard tunnel
 pub struct Tunnel {
     /// Tunnel configuration
-    config: TunnelConfig,
+    config: RwLock<TunnelConfig>,
     /// Current tunnel state
     state: Arc<RwLock<TunnelState>>,
     /// Active peers
@@ -183,19 +417,69 @@ pub struct Tunnel {
     platform: Box<dyn Platform>,
     /// WireGuard device (None when stopped)
     device: Arc<RwLock<Option<DeviceWrapper>>>,
+    /// Resolver for hostname peer endpoints
+    resolver: Arc<dyn EndpointResolver>,
+    /// Background endpoint re-resolution tasks
+    refresh_tasks: Mutex<Vec<JoinHandle<()>>>,
+    /// When the tunnel last became active
+    started_at: std::sync::Mutex<Option<Instant>>,
+    /// Held across every configuration change, start and stop, so a peer
+    /// change that edits a copy of the configuration cannot overwrite a
+    /// concurrent one or land on a device that is being replaced
+    reconfigure: Mutex<()>,
+    /// Packet I/O for userspace devices (None = TUN device and UDP socket)
+    #[cfg_attr(target_os = "macos", allow(dead_code))]
+    device_io: Option<Arc<dyn DeviceIo>>,
 }
 
 impl Tunnel {
     /// Create a new tunnel from configuration
     pub fn new(config: TunnelConfig) -> Result<Self> {
+        Self::with_resolver(config, Arc::new(SystemResolver))
+    }
+
+    /// Create a new tunnel using a custom endpoint resolver
+    pub fn with_resolver(config: TunnelConfig, resolver: Arc<dyn EndpointResolver>) -> Result<Self> {
+        Self::with_parts(config, get_platform(), resolver)
+    }
+
+    /// Create a new tunnel using a custom platform implementation
+    pub fn with_platform(config: TunnelConfig, platform: Box<dyn Platform>) -> Result<Self> {
+        Self::with_parts(config, platform, Arc::new(SystemResolver))
+    }
+
+    /// Create a tunnel whose userspace device exchanges packets through `io`
+    ///
+    /// The userspace device is used whatever the configured backend; `platform`
+    /// still configures addresses, routes and DNS.
+    pub fn with_device_io(
+        config: TunnelConfig,
+        platform: Box<dyn Platform>,
+        io: Arc<dyn DeviceIo>,
+    ) -> Result<Self> {
+        let mut tunnel = Self::with_parts(config, platform, Arc::new(SystemResolver))?;
+        tunnel.device_io = Some(io);
+        Ok(tunnel)
+    }
+
+    fn with_parts(
+        config: TunnelConfig,
+        platform: Box<dyn Platform>,
+        resolver: Arc<dyn EndpointResolver>,
+    ) -> Result<Self> {
         config.validate()?;
 
         Ok(Self {
-            config,
+            config: RwLock::new(config),
             state: Arc::new(RwLock::new(TunnelState::Uninitialized)),
             peers: Arc::new(RwLock::new(HashMap::new())),
-            platform: get_platform(),
+            platform,
             device: Arc::new(RwLock::new(None)),
+            resolver,
+            refresh_tasks: Mutex::new(Vec::new()),
+            started_at: std::sync::Mutex::new(None),
+            reconfigure: Mutex::new(()),
+            device_io: None,
         })
     }
 
@@ -210,8 +494,20 @@ impl Tunnel {
         *self.state.read().await
     }
 
+    /// WireGuard implementation in use ("kernel", "userspace" or "wireguard-go"; None when not running)
+    pub async fn backend(&self) -> Option<&'static str> {
+        self.device.read().await.as_ref().map(|d| d.backend_name())
+    }
+
     /// Start the tunnel
     pub async fn start(&self) -> Result<()> {
+        let _reconfigure = self.reconfigure.lock().await;
+        self.start_locked().await
+    }
+
+    /// Start with the reconfiguration lock held
+    async fn start_locked(&self) -> Result<()> {
+        let config = self.config.read().await.clone();
         let mut state = self.state.write().await;
 
         if !state.can_start() {
@@ -223,7 +519,7 @@ impl Tunnel {
 
         info!(
             "Starting WireGuard tunnel on interface: {}",
-            self.config.interface
+            config.interface
         );
         *state = TunnelState::Starting;
         drop(state);
@@ -240,18 +536,31 @@ impl Tunnel {
             }
         }
 
+        // Resolve hostname endpoints; unresolved peers can still be reached
+        // once they contact us or a later re-resolution succeeds
+        let peer_configs = self.resolve_peers(config.peers.clone()).await;
+
         // Create WireGuard device configuration
         let device_config = DeviceConfig {
-            interface: self.config.interface.clone(),
-            mtu: self.config.mtu,
-            keypair: self.config.keypair.clone(),
-            listen_port: 0, // Use random port
-            peers: self.config.peers.clone(),
+            interface: config.interface.clone(),
+            mtu: config.mtu,
+            keypair: config.keypair.clone(),
+            listen_port: config.listen_port,
+            bind_address: config.bind_address,
+            queues: config.queues,
+            peers: peer_configs.clone(),
         };
 
         // Create WireGuard device - platform-specific implementation
         #[cfg(target_os = "macos")]
         let device = {
+            if config.backend == WireguardBackend::Kernel {
+                *self.state.write().await = TunnelState::Error;
+                return Err(WgAgentError::Config(
+                    "Kernel WireGuard backend is only available on Linux".to_string(),
+                ));
+            }
+
             let mut macos_device = match MacOsWgDevice::new(device_config).await {
                 Ok(d) => d,
                 Err(e) => {
@@ -262,16 +571,19 @@ impl Tunnel {
             };
 
             // Start wireguard-go and configure interface
-            let address = self.config.address.as_ref().ok_or_else(|| {
-                WgAgentError::Config("Address required for macOS WireGuard".to_string())
-            })?;
+            if config.addresses.is_empty() {
+                *self.state.write().await = TunnelState::Error;
+                return Err(WgAgentError::Config(
+                    "Address required for macOS WireGuard".to_string(),
+                ));
+            }
 
-            let routes: Vec<String> = self.config.peers
+            let routes: Vec<String> = config.peers
                 .iter()
                 .flat_map(|p| p.allowed_ips.clone())
                 .collect();
 
-            if let Err(e) = macos_device.start(address, &routes).await {
+            if let Err(e) = macos_device.start(&config.addresses, &routes).await {
                 error!("Failed to start macOS WireGuard device: {}", e);
                 *self.state.write().await = TunnelState::Error;
                 return Err(e);
@@ -282,16 +594,13 @@ impl Tunnel {
         };
 
         #[cfg(not(target_os = "macos"))]
-        let device = {
-            let wg_device = match WgDevice::new(device_config, self.platform.as_ref()).await {
-                Ok(d) => d,
-                Err(e) => {
-                    error!("Failed to create WireGuard device: {}", e);
-                    *self.state.write().await = TunnelState::Error;
-                    return Err(e);
-                }
-            };
-            DeviceWrapper::Boringtun(wg_device)
+        let device = match self.create_device(config.backend, device_config).await {
+            Ok(d) => d,
+            Err(e) => {
+                error!("Failed to create WireGuard device: {}", e);
+                *self.state.write().await = TunnelState::Error;
+                return Err(e);
+            }
         };
 
         // On non-macOS platforms, configure address/routes/DNS manually
@@ -302,18 +611,24 @@ impl Tunnel {
             let interface_name = device.interface_name();
             info!("Configuring Linux WireGuard interface: {}", interface_name);
 
-            // Assign IP address to interface if specified
-            if let Some(ref address) = self.config.address {
-                debug!("Assigning address {} to interface {}", address, interface_name);
-                if let Err(e) = self.platform.set_address(interface_name, address) {
-                    error!("Failed to assign address to interface: {}", e);
-                    *self.state.write().await = TunnelState::Error;
-                    return Err(e);
+            // Assign IPv4 and IPv6 addresses to the interface
+            debug!(
+                "Assigning addresses {:?} to interface {}",
+                config.addresses, interface_name
+            );
+            if let Err(e) = self.platform.set_addresses(interface_name, &config.addresses) {
+                error!("Failed to assign address to interface: {}", e);
+                *self.state.write().await = TunnelState::Error;
+                // Kernel links outlive the device handle, so remove them explicitly
+                #[cfg(target_os = "linux")]
+                if matches!(device, DeviceWrapper::Kernel(_)) {
+                    let _ = self.platform.destroy_interface(interface_name);
                 }
+                return Err(e);
             }
 
             // Configure routes for all peers
-            for peer_config in &self.config.peers {
+            for peer_config in &config.peers {
                 if !peer_config.allowed_ips.is_empty() {
                     debug!(
                         "Configuring routes for peer: {} ({} routes)",
@@ -331,15 +646,15 @@ impl Tunnel {
             }
 
             // Configure DNS
-            if !self.config.dns_servers.is_empty() {
+            if !config.dns_servers.is_empty() {
                 debug!(
                     "Configuring DNS servers: {:?}",
-                    self.config.dns_servers
+                    config.dns_servers
                 );
                 
                 if let Err(e) = self.platform.configure_dns(
                     interface_name,
-                    &self.config.dns_servers,
+                    &config.dns_servers,
                 ) {
                     warn!("Failed to configure DNS: {}", e);
                 }
@@ -348,7 +663,7 @@ impl Tunnel {
 
         // Initialize peer tracking (for stats/monitoring)
         let mut peers = self.peers.write().await;
-        for peer_config in &self.config.peers {
+        for peer_config in &peer_configs {
             match Peer::new(peer_config.clone()) {
                 Ok(mut peer) => {
                     peer.activate();
@@ -365,18 +680,76 @@ impl Tunnel {
         // WireGuard device has already brought the interface up, skip manual interface_up
         // Store the device
         *self.device.write().await = Some(device);
+        self.spawn_endpoint_refresh(&peer_configs).await;
 
+        *self.started_at.lock().unwrap() = Some(Instant::now());
         *self.state.write().await = TunnelState::Active;
         info!(
             "WireGuard tunnel started successfully on interface: {}",
-            self.config.interface
+            config.interface
         );
 
         Ok(())
     }
 
+    /// Create the WireGuard device for the configured backend
+    ///
+    /// With `auto`, the kernel module is used when detected and boringtun is
+    /// used if it is missing or the kernel device cannot be created.
+    #[cfg(not(target_os = "macos"))]
+    async fn create_device(
+        &self,
+        backend: WireguardBackend,
+        device_config: DeviceConfig,
+    ) -> Result<DeviceWrapper> {
+        if let Some(io) = &self.device_io {
+            let (tun, udp) = io.open(&device_config)?;
+            let device = WgDevice::with_io(device_config, tun, udp).await?;
+            return Ok(DeviceWrapper::Boringtun(device));
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let use_kernel = match backend {
+                WireguardBackend::Auto => kernel_wireguard_available(),
+                WireguardBackend::Kernel => true,
+                WireguardBackend::Userspace => false,
+            };
+
+            if use_kernel {
+                match KernelWgDevice::new(device_config.clone(), self.platform.as_ref()).await {
+                    Ok(device) => {
+                        info!("Using kernel WireGuard for {}", device.interface_name());
+                        return Ok(DeviceWrapper::Kernel(device));
+                    }
+                    Err(e) if backend.is_auto() => {
+                        warn!("Kernel WireGuard unavailable ({}), using userspace", e);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        if backend == WireguardBackend::Kernel {
+            return Err(WgAgentError::Config(
+                "Kernel WireGuard backend is only available on Linux".to_string(),
+            ));
+        }
+
+        let device = WgDevice::new(device_config, self.platform.as_ref()).await?;
+        Ok(DeviceWrapper::Boringtun(device))
+    }
+
     /// Stop the tunnel
     pub async fn stop(&self) -> Result<()> {
+        let _reconfigure = self.reconfigure.lock().await;
+        self.stop_locked().await
+    }
+
+    /// Stop with the reconfiguration lock held
+    async fn stop_locked(&self) -> Result<()> {
+        let config = self.config.read().await.clone();
         let mut state = self.state.write().await;
 
         if !state.can_stop() {
@@ -388,11 +761,15 @@ impl Tunnel {
 
         info!(
             "Stopping WireGuard tunnel on interface: {}",
-            self.config.interface
+            config.interface
         );
         *state = TunnelState::Stopping;
         drop(state);
 
+        for task in self.refresh_tasks.lock().await.drain(..) {
+            task.abort();
+        }
+
         // Stop WireGuard device first (this stops packet processing and TUN device)
         let device = self.device.write().await.take();
         if let Some(device) = device {
@@ -411,15 +788,15 @@ impl Tunnel {
         drop(peers);
 
         // Remove DNS configuration
-        if let Err(e) = self.platform.remove_dns(&self.config.interface) {
+        if let Err(e) = self.platform.remove_dns(&config.interface) {
             warn!("Failed to remove DNS configuration: {}", e);
         }
 
         // Remove routes for all peers
-        for peer_config in &self.config.peers {
+        for peer_config in &config.peers {
             if !peer_config.allowed_ips.is_empty() {
                 if let Err(e) = self.platform.remove_routes(
-                    &self.config.interface,
+                    &config.interface,
                     &peer_config.allowed_ips,
                 ) {
                     warn!(
@@ -431,34 +808,464 @@ impl Tunnel {
         }
 
         // Destroy the interface
-        if let Err(e) = self.platform.destroy_interface(&self.config.interface) {
+        if let Err(e) = self.platform.destroy_interface(&config.interface) {
             warn!("Failed to destroy interface: {}", e);
         }
 
         *self.state.write().await = TunnelState::Stopped;
         info!(
             "WireGuard tunnel stopped on interface: {}",
-            self.config.interface
+            config.interface
         );
 
         Ok(())
     }
 
-    /// Reload the tunnel configuration
+    /// Stop the tunnel if needed and start it again
+    pub async fn restart(&self) -> Result<()> {
+        let _reconfigure = self.reconfigure.lock().await;
+        // A tunnel that failed to start has already been torn down
+        if self.device.read().await.is_some() {
+            self.stop_locked().await?;
+        }
+        self.start_locked().await
+    }
+
+    /// Mark a failed tunnel as waiting to be restarted
+    pub async fn set_reconnecting(&self) {
+        let mut state = self.state.write().await;
+        if *state == TunnelState::Error {
+            *state = TunnelState::Reconnecting;
+        }
+    }
+
+    /// Re-evaluate the state of a running tunnel
+    ///
+    /// A tunnel whose device has stopped processing packets moves to `Error`.
+    /// With a `handshake_timeout`, the tunnel is `Degraded` while none of the
+    /// peers expected to handshake (those with an endpoint and a persistent
+    /// keepalive) has done so within the timeout, and `Active` once one does.
+    pub async fn check_health(&self, handshake_timeout: Option<Duration>) -> TunnelState {
+        let state = self.state().await;
+        if !state.is_running() {
+            return state;
+        }
+
+        let alive = self.device.read().await.as_ref().is_some_and(|d| d.is_alive());
+        let next = if !alive {
+            TunnelState::Error
+        } else {
+            match handshake_timeout {
+                Some(timeout) if self.handshakes_stale(timeout).await => TunnelState::Degraded,
+                _ => TunnelState::Active,
+            }
+        };
+
+        // Leave the state alone if the tunnel was stopped meanwhile
+        let mut state = self.state.write().await;
+        if state.is_running() && *state != next {
+            let interface = self.config.read().await.interface.clone();
+            match next {
+                TunnelState::Error => error!("WireGuard device on {} stopped", interface),
+                TunnelState::Degraded => warn!("No recent handshakes on {}", interface),
+                _ => info!("Handshakes resumed on {}", interface),
+            }
+            *state = next;
+        }
+        *state
+    }
+
+    /// Check whether every peer expected to handshake has gone `timeout` without one
+    async fn handshakes_stale(&self, timeout: Duration) -> bool {
+        // Give a freshly started tunnel the whole window
+        let started_at = *self.started_at.lock().unwrap();
+        if started_at.is_none_or(|at| at.elapsed() < timeout) {
+            return false;
+        }
+
+        self.sync_peer_stats().await;
+        let peers = self.peers.read().await;
+        let mut expected = peers
+            .values()
+            .filter(|peer| {
+                peer.config.keepalive_interval.is_some()
+                    && (peer.config.endpoint.is_some() || peer.config.endpoint_host.is_some())
+            })
+            .peekable();
+        if expected.peek().is_none() {
+            return false;
+        }
+
+        expected.all(|peer| {
+            peer.stats
+                .last_handshake
+                .and_then(|at| at.elapsed().ok())
+                .is_none_or(|elapsed| elapsed >= timeout)
+        })
+    }
+
+    /// Get our current public key
+    pub async fn public_key(&self) -> PublicKey {
+        self.config.read().await.keypair.public.clone()
+    }
+
+    /// Switch the tunnel to a new static key pair, returning the one it replaced.
+    ///
+    /// A userspace device keeps its existing sessions until handshakes with the
+    /// new key complete, while kernel WireGuard restarts every session; later
+    /// restarts use the new key.
+    pub async fn rotate_keys(&self, keypair: KeyPair) -> Result<KeyPair> {
+        let _reconfigure = self.reconfigure.lock().await;
+        if let Some(device) = self.device.read().await.as_ref() {
+            device.rotate_keys(keypair.clone()).await?;
+        }
+
+        Ok(std::mem::replace(&mut self.config.write().await.keypair, keypair))
+    }
+
+    /// Resolve hostname endpoints, returning peer configs ready for the device
+    async fn resolve_peers(&self, mut peers: Vec<PeerConfig>) -> Vec<PeerConfig> {
+        for peer in &mut peers {
+            let Some(host) = peer.endpoint_host.as_deref() else {
+                continue;
+            };
+
+            match resolve_endpoint(self.resolver.as_ref(), host).await {
+                Ok(addr) => {
+                    info!("Resolved endpoint {} for peer '{}' to {}", host, peer.name, addr);
+                    peer.endpoint = Some(addr);
+                }
+                Err(e) => {
+                    warn!("Failed to resolve endpoint for peer '{}': {}", peer.name, e);
+                }
+            }
+        }
+
+        peers
+    }
+
+    /// Spawn background tasks that re-resolve hostname endpoints
+    async fn spawn_endpoint_refresh(&self, peers: &[PeerConfig]) {
+        let mut tasks = self.refresh_tasks.lock().await;
+
+        for peer in peers {
+            let (Some(host), Some(interval)) = (peer.endpoint_host.clone(), peer.endpoint_refresh)
+            else {
+                continue;
+            };
+
+            let name = peer.name.clone();
+            let public_key = peer.public_key.clone();
+            let mut current = peer.endpoint;
+            let resolver = Arc::clone(&self.resolver);
+            let device = Arc::clone(&self.device);
+
+            tasks.push(tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                // First tick fires immediately; we just resolved
+                ticker.tick().await;
+
+                loop {
+                    ticker.tick().await;
+
+                    let addr = match resolve_endpoint(resolver.as_ref(), &host).await {
+                        Ok(addr) => addr,
+                        Err(e) => {
+                            warn!("Failed to re-resolve endpoint for peer '{}': {}", name, e);
+                            continue;
+                        }
+                    };
+
+                    if current == Some(addr) {
+                        continue;
+                    }
+
+                    let device = device.read().await;
+                    let Some(device) = device.as_ref() else {
+                        break;
+                    };
+
+                    info!("Endpoint {} for peer '{}' now resolves to {}", host, name, addr);
+                    match device.update_endpoint(&public_key, addr).await {
+                        Ok(()) => current = Some(addr),
+                        Err(e) => warn!("Failed to update endpoint for peer '{}': {}", name, e),
+                    }
+                }
+            }));
+        }
+    }
+
+    /// Reload the tunnel configuration.
+    ///
+    /// Peer additions, removals and changes are applied to the running device;
+    /// sessions with unchanged peers, and with peers whose name or endpoint
+    /// changed, are kept. The device is only recreated if the interface, MTU,
+    /// addresses, ports, backend or private key changed.
+    ///
+    /// If the device rejects a peer change, the changes made up to that point
+    /// stay in effect and in the recorded configuration, and the error is returned.
     pub async fn reload(&self, new_config: TunnelConfig) -> Result<()> {
+        let _reconfigure = self.reconfigure.lock().await;
+        self.reload_locked(new_config).await
+    }
+
+    /// Reload with the reconfiguration lock held
+    async fn reload_locked(&self, new_config: TunnelConfig) -> Result<()> {
         info!("Reloading tunnel configuration");
+        new_config.validate()?;
 
-        // For now, simple approach: stop and restart
-        // Future: implement hot-reload for peer changes
-        self.stop().await?;
-        
-        // Create new tunnel with new config
-        let new_tunnel = Self::new(new_config)?;
-        new_tunnel.start().await?;
+        let old_config = self.config.read().await.clone();
+
+        if !self.state().await.is_running() {
+            *self.config.write().await = new_config;
+            return Ok(());
+        }
+
+        if old_config.requires_restart(&new_config) {
+            info!("Interface settings changed, restarting tunnel");
+            self.stop_locked().await?;
+            *self.config.write().await = new_config;
+            return self.start_locked().await;
+        }
+
+        let device_guard = self.device.read().await;
+        let Some(device) = device_guard.as_ref() else {
+            *self.config.write().await = new_config;
+            return Ok(());
+        };
+        let interface = device.interface_name().to_string();
+
+        // Diff peers by public key
+        let old_peers: HashMap<&PublicKey, &PeerConfig> =
+            old_config.peers.iter().map(|p| (&p.public_key, p)).collect();
+        let new_peers: HashMap<&PublicKey, &PeerConfig> =
+            new_config.peers.iter().map(|p| (&p.public_key, p)).collect();
+
+        let mut changes = PeerChanges::default();
+        for (key, old) in &old_peers {
+            match new_peers.get(key).map(|new| (new, peer_change(old, new))) {
+                None => changes.removed.push((*old).clone()),
+                Some((_, PeerChange::Unchanged)) => {}
+                Some((new, PeerChange::Endpoint)) => {
+                    changes.updated.push(((*old).clone(), (*new).clone()))
+                }
+                Some((new, PeerChange::Replace)) => {
+                    changes.removed.push((*old).clone());
+                    changes.added.push((*new).clone());
+                }
+            }
+        }
+        for (key, new) in &new_peers {
+            if !old_peers.contains_key(key) {
+                changes.added.push((*new).clone());
+            }
+        }
+        let (removed, updated, added) =
+            (changes.removed.len(), changes.updated.len(), changes.added.len());
+
+        // If a step fails, keep the peers the device did take so the recorded
+        // configuration, routes and re-resolution match the device
+        let mut applied = old_config.peers.clone();
+        let result = self.apply_peer_changes(device, changes, &mut applied).await;
+        let new_config = match result {
+            Ok(()) => new_config,
+            Err(_) => TunnelConfig {
+                peers: applied,
+                ..new_config
+            },
+        };
+
+        // Routes are shared between peers, so diff the combined sets
+        let old_routes = route_set(&old_config.peers);
+        let new_routes = route_set(&new_config.peers);
+        let stale: Vec<String> = old_routes.difference(&new_routes).cloned().collect();
+        let fresh: Vec<String> = new_routes.difference(&old_routes).cloned().collect();
+        if !stale.is_empty() {
+            if let Err(e) = self.platform.remove_routes(&interface, &stale) {
+                warn!("Failed to remove routes during reload: {}", e);
+            }
+        }
+        if !fresh.is_empty() {
+            if let Err(e) = self.platform.configure_routes(&interface, &fresh) {
+                warn!("Failed to configure routes during reload: {}", e);
+            }
+        }
+
+        if old_config.dns_servers != new_config.dns_servers {
+            let result = if new_config.dns_servers.is_empty() {
+                self.platform.remove_dns(&interface)
+            } else {
+                self.platform.configure_dns(&interface, &new_config.dns_servers)
+            };
+            if let Err(e) = result {
+                warn!("Failed to update DNS during reload: {}", e);
+            }
+        }
+        drop(device_guard);
+
+        // Restart re-resolution for the new peer set
+        for task in self.refresh_tasks.lock().await.drain(..) {
+            task.abort();
+        }
+        let refresh_peers: Vec<PeerConfig> = new_config
+            .peers
+            .iter()
+            .filter(|p| p.endpoint_host.is_some())
+            .cloned()
+            .collect();
+        let refresh_peers = self.resolve_peers(refresh_peers).await;
+        self.spawn_endpoint_refresh(&refresh_peers).await;
+        *self.config.write().await = new_config;
+
+        match result {
+            Ok(()) => info!(
+                "Reload complete: {} peers removed, {} updated, {} added",
+                removed, updated, added
+            ),
+            Err(ref e) => warn!("Reload partially applied: {}", e),
+        }
+        result
+    }
+
+    /// Apply a reload's peer changes to the running device
+    ///
+    /// Each change is recorded once the device has carried it out: `applied`
+    /// starts as the previous peer list and is updated after every step, so it
+    /// matches the device even when a step fails.
+    async fn apply_peer_changes(
+        &self,
+        device: &DeviceWrapper,
+        changes: PeerChanges,
+        applied: &mut Vec<PeerConfig>,
+    ) -> Result<()> {
+        for peer in &changes.removed {
+            info!("Reload: removing peer '{}'", peer.name);
+            device.remove_peer(&peer.public_key).await?;
+            self.peers.write().await.remove(&peer.name);
+            applied.retain(|p| p.public_key != peer.public_key);
+        }
+
+        for (old, new) in changes.updated {
+            if old.name != new.name {
+                info!("Reload: renaming peer '{}' to '{}'", old.name, new.name);
+                device.rename_peer(&new.public_key, &new.name).await?;
+            }
+            if old.endpoint != new.endpoint || old.endpoint_host != new.endpoint_host {
+                let resolved = self.resolve_peers(vec![new.clone()]).await;
+                if let Some(endpoint) = resolved[0].endpoint {
+                    info!("Reload: moving peer '{}' to {}", new.name, endpoint);
+                    device.update_endpoint(&new.public_key, endpoint).await?;
+                }
+            }
+
+            let mut peers = self.peers.write().await;
+            if let Some(mut tracked) = peers.remove(&old.name) {
+                tracked.config = new.clone();
+                peers.insert(new.name.clone(), tracked);
+            }
+            drop(peers);
+            if let Some(entry) = applied.iter_mut().find(|p| p.public_key == new.public_key) {
+                *entry = new;
+            }
+        }
+
+        let resolved = self.resolve_peers(changes.added.clone()).await;
+        for (peer, resolved) in changes.added.into_iter().zip(resolved) {
+            info!("Reload: adding peer '{}'", peer.name);
+            device.add_peer(resolved.clone()).await?;
+            match Peer::new(resolved) {
+                Ok(mut tracked) => {
+                    tracked.activate();
+                    self.peers.write().await.insert(peer.name.clone(), tracked);
+                }
+                Err(e) => warn!("Failed to initialize peer '{}': {}", peer.name, e),
+            }
+            applied.push(peer);
+        }
 
         Ok(())
     }
 
+    /// Add a peer to the tunnel
+    pub async fn add_peer(&self, peer: PeerConfig) -> Result<()> {
+        let _reconfigure = self.reconfigure.lock().await;
+        let mut new_config = self.config.read().await.clone();
+        if let Some(existing) = new_config
+            .peers
+            .iter()
+            .find(|p| p.public_key == peer.public_key || p.name == peer.name)
+        {
+            return Err(WgAgentError::InvalidState(format!(
+                "Peer '{}' already exists",
+                existing.name
+            )));
+        }
+
+        new_config.peers.push(peer);
+        self.reload_locked(new_config).await
+    }
+
+    /// Replace the configuration of the peer with the same public key
+    pub async fn update_peer(&self, peer: PeerConfig) -> Result<()> {
+        let _reconfigure = self.reconfigure.lock().await;
+        let mut new_config = self.config.read().await.clone();
+        let existing = new_config
+            .peers
+            .iter_mut()
+            .find(|p| p.public_key == peer.public_key)
+            .ok_or_else(|| {
+                WgAgentError::NotFound(format!("Peer {} not found", peer.public_key))
+            })?;
+
+        *existing = peer;
+        self.reload_locked(new_config).await
+    }
+
+    /// Remove a peer by public key, returning its configuration
+    pub async fn remove_peer(&self, public_key: &PublicKey) -> Result<PeerConfig> {
+        let _reconfigure = self.reconfigure.lock().await;
+        let mut new_config = self.config.read().await.clone();
+        let index = new_config
+            .peers
+            .iter()
+            .position(|p| &p.public_key == public_key)
+            .ok_or_else(|| WgAgentError::NotFound(format!("Peer {} not found", public_key)))?;
+
+        let removed = new_config.peers.remove(index);
+        self.reload_locked(new_config).await?;
+        Ok(removed)
+    }
+
+    /// Get configuration and statistics for every configured peer
+    pub async fn peer_info(&self) -> Vec<PeerInfo> {
+        self.sync_peer_stats().await;
+        let config = self.config.read().await;
+        let peers = self.peers.read().await;
+
+        config
+            .peers
+            .iter()
+            .map(|peer_config| {
+                let tracked = peers.get(&peer_config.name);
+                // A running device may have roamed away from the configured endpoint
+                let live_endpoint = tracked
+                    .filter(|p| p.active)
+                    .and_then(|p| p.stats.endpoint);
+                PeerInfo {
+                    name: peer_config.name.clone(),
+                    public_key: peer_config.public_key.clone(),
+                    endpoint: live_endpoint
+                        .or(peer_config.endpoint)
+                        .map(|e| e.to_string())
+                        .or_else(|| peer_config.endpoint_host.clone()),
+                    allowed_ips: peer_config.allowed_ips.clone(),
+                    active: tracked.is_some_and(|p| p.active),
+                    stats: tracked.map(|p| p.stats.clone()).unwrap_or_default(),
+                }
+            })
+            .collect()
+    }
+
     /// Get peer status
     pub async fn peer_status(&self, peer_name: &str) -> Option<String> {
         let peers = self.peers.read().await;
@@ -473,12 +1280,18 @@ impl Tunnel {
 
     /// Get tunnel statistics
     pub async fn stats(&self) -> TunnelStats {
+        let device_stats = self.sync_peer_stats().await;
         let peers = self.peers.read().await;
         let state = self.state.read().await;
 
+        let udp_batches = device_stats.as_ref().map(|d| d.udp_batches).unwrap_or_default();
+        let handshakes_rate_limited = device_stats
+            .as_ref()
+            .map(|d| d.handshakes_rate_limited)
+            .unwrap_or_default();
+
         // Get real stats from WgDevice if available
-        let (total_tx, total_rx) = if let Some(device) = self.device.read().await.as_ref() {
-            let device_stats = device.stats().await;
+        let (total_tx, total_rx) = if let Some(device_stats) = device_stats {
             (device_stats.tx_bytes, device_stats.rx_bytes)
         } else {
             // Fallback to peer stats if device not available
@@ -493,19 +1306,62 @@ impl Tunnel {
 
         let active_peers = peers.values().filter(|p| p.active).count();
         let healthy_peers = peers.values().filter(|p| p.is_healthy()).count();
+        let device = self.device.read().await;
+        let listen_port = device.as_ref().and_then(|d| d.listen_port());
+        let backend = device.as_ref().map(|d| d.backend_name());
+        drop(device);
 
         TunnelStats {
             state: *state,
-            interface: self.config.interface.clone(),
+            interface: self.config.read().await.interface.clone(),
+            listen_port,
+            backend,
             total_peers: peers.len(),
             active_peers,
             healthy_peers,
             total_tx_bytes: total_tx,
             total_rx_bytes: total_rx,
+            udp_batches,
+            handshakes_rate_limited,
+            peer_stats: peers
+                .iter()
+                .map(|(name, peer)| (name.clone(), peer.stats.clone()))
+                .collect(),
+        }
+    }
+
+    /// Copy per-peer statistics from the device into the peer tracking map
+    async fn sync_peer_stats(&self) -> Option<crate::wireguard::DeviceStats> {
+        let device_stats = self.device.read().await.as_ref()?.stats().await;
+
+        let mut peers = self.peers.write().await;
+        for peer in peers.values_mut() {
+            if let Some(stats) = device_stats.peers.get(&peer.config.public_key) {
+                peer.stats = stats.clone();
+            }
         }
+
+        Some(device_stats)
     }
 }
 
+/// Per-peer view returned by [`Tunnel::peer_info`]
+#[derive(Debug, Clone)]
+pub struct PeerInfo {
+    /// Peer name
+    pub name: String,
+    /// Peer public key
+    pub public_key: PublicKey,
+    /// Endpoint the device sends to, else the configured address or hostname
+    pub endpoint: Option<String>,
+    /// Allowed IP ranges
+    pub allowed_ips: Vec<String>,
+    /// Whether the peer is active on the running device
+    pub active: bool,
+    /// Peer statistics
+    pub stats: PeerStats,
+}
+
 /// Tunnel statistics
 #[derive(Debug, Clone)]
 pub struct TunnelStats {
@@ -513,6 +1369,10 @@ pub struct TunnelStats {
     pub state: TunnelState,
     /// Interface name
     pub interface: String,
+    /// UDP port the device is bound to (None when not running)
+    pub listen_port: Option<u16>,
+    /// WireGuard implementation in use ("kernel", "userspace" or "wireguard-go"; None when not running)
+    pub backend: Option<&'static str>,
     /// Total number of configured peers
     pub total_peers: usize,
     /// Number of active peers
@@ -523,6 +1383,12 @@ pub struct TunnelStats {
     pub total_tx_bytes: u64,
     /// Total bytes received
     pub total_rx_bytes: u64,
+    /// UDP batching of the userspace data path (zero for other backends)
+    pub udp_batches: UdpBatchStats,
+    /// Handshake initiations answered with a cookie or dropped under load (zero for other backends)
+    pub handshakes_rate_limited: u64,
+    /// Statistics per peer name
+    pub peer_stats: HashMap<String, PeerStats>,
 }
 
 impl std::fmt::Display for TunnelStats {
@@ -556,6 +1422,16 @@ mod tests {
         assert!(!state.can_start());
         assert!(state.can_stop());
         assert!(state.is_running());
+
+        let state = TunnelState::Degraded;
+        assert!(!state.can_start());
+        assert!(state.is_running());
+
+        let state = TunnelState::Reconnecting;
+        assert!(state.can_start());
+        assert!(state.can_stop());
+        assert!(!state.is_running());
+        assert_eq!(state.to_string(), "reconnecting");
     }
 
     #[test]
@@ -565,7 +1441,11 @@ mod tests {
         let config = TunnelConfig {
             interface: "wg0".to_string(),
             mtu: 1420,
-            address: Some("10.0.0.1/24".to_string()),
+            addresses: vec!["10.0.0.1/24".to_string()],
+            listen_port: 0,
+            bind_address: None,
+            backend: WireguardBackend::Auto,
+            queues: 0,
             dns_servers: vec![],
             keypair,
             peers: vec![],
@@ -581,7 +1461,11 @@ mod tests {
         let config = TunnelConfig {
             interface: "wg0".to_string(),
             mtu: 2000, // Invalid MTU
-            address: Some("10.0.0.1/24".to_string()),
+            addresses: vec!["10.0.0.1/24".to_string()],
+            listen_port: 0,
+            bind_address: None,
+            backend: WireguardBackend::Auto,
+            queues: 0,
             dns_servers: vec![],
             keypair,
             peers: vec![],
@@ -597,7 +1481,11 @@ mod tests {
         let config = TunnelConfig {
             interface: "".to_string(), // Empty interface
             mtu: 1420,
-            address: Some("10.0.0.1/24".to_string()),
+            addresses: vec!["10.0.0.1/24".to_string()],
+            listen_port: 0,
+            bind_address: None,
+            backend: WireguardBackend::Auto,
+            queues: 0,
             dns_servers: vec![],
             keypair,
             peers: vec![],
@@ -613,7 +1501,11 @@ mod tests {
         let config = TunnelConfig {
             interface: "wg0".to_string(),
             mtu: 1420,
-            address: Some("10.0.0.1/24".to_string()),
+            addresses: vec!["10.0.0.1/24".to_string()],
+            listen_port: 0,
+            bind_address: None,
+            backend: WireguardBackend::Auto,
+            queues: 0,
             dns_servers: vec![],
             keypair,
             peers: vec![],
@@ -623,6 +1515,168 @@ mod tests {
         assert_eq!(tunnel.state().await, TunnelState::Uninitialized);
     }
 
+    #[tokio::test]
+    async fn test_resolve_hostname_peers() {
+        use crate::wireguard::StaticResolver;
+
+        let mut resolved = PeerConfig::new("resolved".to_string(), KeyPair::generate().public);
+        resolved.set_endpoint("vpn.example.com:51820").unwrap();
+        let mut unresolved = PeerConfig::new("unresolved".to_string(), KeyPair::generate().public);
+        unresolved.set_endpoint("missing.example.com:51820").unwrap();
+
+        let config = TunnelConfig {
+            interface: "wg0".to_string(),
+            mtu: 1420,
+            addresses: vec!["10.0.0.1/24".to_string()],
+            listen_port: 0,
+            bind_address: None,
+            backend: WireguardBackend::Auto,
+            queues: 0,
+            dns_servers: vec![],
+            keypair: KeyPair::generate(),
+            peers: vec![resolved, unresolved],
+        };
+
+        let resolver = StaticResolver::new();
+        let addr: SocketAddr = "192.0.2.10:51820".parse().unwrap();
+        resolver.insert("vpn.example.com:51820", vec![addr]);
+
+        let peers = config.peers.clone();
+        let tunnel = Tunnel::with_resolver(config, Arc::new(resolver)).unwrap();
+        let peers = tunnel.resolve_peers(peers).await;
+
+        assert_eq!(peers[0].endpoint, Some(addr));
+        assert_eq!(peers[1].endpoint, None);
+        assert!(peers[1].endpoint_host.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_keys_stopped_tunnel() {
+        let config = TunnelConfig {
+            interface: "wg0".to_string(),
+            mtu: 1420,
+            addresses: vec!["10.0.0.1/24".to_string()],
+            listen_port: 0,
+            bind_address: None,
+            backend: WireguardBackend::Auto,
+            queues: 0,
+            dns_servers: vec![],
+            keypair: KeyPair::generate(),
+            peers: vec![],
+        };
+        let tunnel = Tunnel::new(config).unwrap();
+
+        let previous = tunnel.public_key().await;
+        let keypair = KeyPair::generate();
+        let replaced = tunnel.rotate_keys(keypair.clone()).await.unwrap();
+        assert_eq!(replaced.public, previous);
+        assert_eq!(tunnel.public_key().await, keypair.public);
+    }
+
+    fn reload_test_config(peers: Vec<PeerConfig>) -> TunnelConfig {
+        TunnelConfig {
+            interface: "wg0".to_string(),
+            mtu: 1420,
+            addresses: vec!["10.0.0.1/24".to_string()],
+            listen_port: 0,
+            bind_address: None,
+            backend: WireguardBackend::Auto,
+            queues: 0,
+            dns_servers: vec![],
+            keypair: KeyPair::generate(),
+            peers,
+        }
+    }
+
+    #[test]
+    fn test_reload_diff_helpers() {
+        let old = reload_test_config(vec![]);
+        let mut new = old.clone();
+        new.dns_servers = vec!["10.0.0.53".to_string()];
+        assert!(!old.requires_restart(&new));
+        new.mtu = 1380;
+        assert!(old.requires_restart(&new));
+        let mut new = old.clone();
+        new.backend = WireguardBackend::Userspace;
+        assert!(old.requires_restart(&new));
+
+        let mut peer = PeerConfig::new("a".to_string(), KeyPair::generate().public);
+        peer.allowed_ips = vec!["10.1.0.0/16".to_string()];
+        let mut changed = peer.clone();
+        assert_eq!(peer_change(&peer, &changed), PeerChange::Unchanged);
+        changed.endpoint = Some("192.0.2.1:51820".parse().unwrap());
+        assert_eq!(peer_change(&peer, &changed), PeerChange::Endpoint);
+        changed.allowed_ips.push("10.2.0.0/16".to_string());
+        assert_eq!(peer_change(&peer, &changed), PeerChange::Replace);
+
+        let routes = route_set(&[peer, changed]);
+        assert_eq!(routes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reload_stopped_tunnel_updates_config() {
+        let tunnel = Tunnel::new(reload_test_config(vec![])).unwrap();
+
+        let peer = PeerConfig::new("new-peer".to_string(), KeyPair::generate().public);
+        let mut new_config = reload_test_config(vec![peer]);
+        new_config.mtu = 1380;
+        tunnel.reload(new_config).await.unwrap();
+
+        // Not running, so nothing is started; the config is kept for the next start
+        assert_eq!(tunnel.state().await, TunnelState::Uninitialized);
+        assert_eq!(tunnel.config.read().await.mtu, 1380);
+        assert_eq!(tunnel.config.read().await.peers.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_peer_operations_on_stopped_tunnel() {
+        let tunnel = Tunnel::new(reload_test_config(vec![])).unwrap();
+
+        let mut peer = PeerConfig::new("site-a".to_string(), KeyPair::generate().public);
+        peer.allowed_ips = vec!["10.1.0.0/16".to_string()];
+        tunnel.add_peer(peer.clone()).await.unwrap();
+        assert!(tunnel.add_peer(peer.clone()).await.is_err());
+
+        // Another peer cannot take over a routed range
+        let mut clash = PeerConfig::new("site-b".to_string(), KeyPair::generate().public);
+        clash.allowed_ips = vec!["10.1.0.0/16".to_string()];
+        assert!(tunnel.add_peer(clash).await.is_err());
+
+        peer.allowed_ips.push("10.2.0.0/16".to_string());
+        tunnel.update_peer(peer.clone()).await.unwrap();
+
+        let info = tunnel.peer_info().await;
+        assert_eq!(info.len(), 1);
+        assert_eq!(info[0].allowed_ips.len(), 2);
+        assert!(!info[0].active);
+
+        let removed = tunnel.remove_peer(&peer.public_key).await.unwrap();
+        assert_eq!(removed.name, "site-a");
+        assert!(tunnel.peer_info().await.is_empty());
+        assert!(tunnel.remove_peer(&peer.public_key).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_peer_changes() {
+        let tunnel = Arc::new(Tunnel::new(reload_test_config(vec![])).unwrap());
+
+        let tasks: Vec<_> = (0..32)
+            .map(|i| {
+                let tunnel = tunnel.clone();
+                tokio::spawn(async move {
+                    let peer = PeerConfig::new(format!("peer-{}", i), KeyPair::generate().public);
+                    tunnel.add_peer(peer).await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        // Every change is applied on top of the previous one
+        assert_eq!(tunnel.peer_info().await.len(), 32);
+    }
+
     #[tokio::test]
     async fn test_tunnel_stats() {
         let keypair = KeyPair::generate();
@@ -630,7 +1684,11 @@ mod tests {
         let config = TunnelConfig {
             interface: "wg0".to_string(),
             mtu: 1420,
-            address: Some("10.0.0.1/24".to_string()),
+            addresses: vec!["10.0.0.1/24".to_string()],
+            listen_port: 0,
+            bind_address: None,
+            backend: WireguardBackend::Auto,
+            queues: 0,
             dns_servers: vec![],
             keypair,
             peers: vec![],
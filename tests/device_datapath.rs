@@ -0,0 +1,419 @@
+//! Data path tests for the userspace WireGuard device
+//!
+//! Two `WgDevice`s are connected through in-memory TUN devices and an
+//! in-memory datagram link, so real handshakes and encrypted traffic run
+//! without root privileges or network interfaces.
+
+use boringtun::noise::{Tunn, TunnResult};
+use harmony_agent::wireguard::{
+    DeviceConfig, KeyPair, MemoryNetwork, MemorySocket, MemoryTun, MemoryTunHandle, PeerConfig,
+    TunIo, UdpIo, WgDevice,
+};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+const A_TUNNEL_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+const B_TUNNEL_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+
+/// How long to wait for something that should happen
+const WAIT: Duration = Duration::from_secs(5);
+
+/// One side of the test link
+struct Node {
+    device: WgDevice,
+    tun: MemoryTunHandle,
+    keypair: KeyPair,
+}
+
+/// Two devices peered with each other over an in-memory link
+struct Pair {
+    a: Node,
+    b: Node,
+    a_addr: SocketAddr,
+}
+
+fn peer(name: &str, keypair: &KeyPair, endpoint: SocketAddr, tunnel_ip: Ipv4Addr) -> PeerConfig {
+    let mut config = PeerConfig::new(name.to_string(), keypair.public.clone());
+    config.endpoint = Some(endpoint);
+    config.allowed_ips = vec![format!("{}/32", tunnel_ip)];
+    config
+}
+
+async fn node(
+    interface: &str,
+    keypair: KeyPair,
+    peer: PeerConfig,
+    socket: MemorySocket,
+) -> Node {
+    let (tun, handle) = MemoryTun::pair();
+    let config = DeviceConfig {
+        interface: interface.to_string(),
+        mtu: 1420,
+        keypair: keypair.clone(),
+        listen_port: 0,
+        bind_address: None,
+        queues: 1,
+        peers: vec![peer],
+    };
+
+    let device = WgDevice::with_io(config, Arc::new(tun), Arc::new(socket))
+        .await
+        .unwrap();
+    Node {
+        device,
+        tun: handle,
+        keypair,
+    }
+}
+
+/// Create two peered devices; `keepalive` is A's persistent keepalive to B
+async fn pair(keepalive: Option<Duration>) -> Pair {
+    let a_addr: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+    let b_addr: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+    let (a_socket, b_socket) = MemorySocket::pair(a_addr, b_addr);
+    let (a_keys, b_keys) = (KeyPair::generate(), KeyPair::generate());
+
+    let mut a_peer = peer("b", &b_keys, b_addr, B_TUNNEL_IP);
+    a_peer.keepalive_interval = keepalive;
+    let b_peer = peer("a", &a_keys, a_addr, A_TUNNEL_IP);
+
+    Pair {
+        a: node("wg-a", a_keys, a_peer, a_socket).await,
+        b: node("wg-b", b_keys, b_peer, b_socket).await,
+        a_addr,
+    }
+}
+
+/// Build a minimal IPv4/UDP packet carrying `payload`
+fn ipv4_packet(src: Ipv4Addr, dst: Ipv4Addr, payload: &[u8]) -> Vec<u8> {
+    let total_len = (20 + 8 + payload.len()) as u16;
+    let mut packet = vec![0x45, 0];
+    packet.extend_from_slice(&total_len.to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0, 64, 17, 0, 0]);
+    packet.extend_from_slice(&src.octets());
+    packet.extend_from_slice(&dst.octets());
+    packet.extend_from_slice(&[0x30, 0x39, 0x30, 0x39]);
+    packet.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Send a packet from `from` and wait for `to` to deliver it to its host
+async fn transfer(from: &Node, to: &Node, src: Ipv4Addr, dst: Ipv4Addr, payload: &[u8]) {
+    let packet = ipv4_packet(src, dst, payload);
+    from.tun.send(&packet);
+
+    let delivered = time::timeout(WAIT, to.tun.recv())
+        .await
+        .expect("packet was not delivered")
+        .unwrap();
+    assert_eq!(delivered, packet);
+}
+
+/// Wait until `peer` has completed a handshake with `node`'s device
+async fn wait_for_handshake(node: &Node, peer: &KeyPair) {
+    time::timeout(WAIT, async {
+        loop {
+            let stats = node.device.stats().await;
+            if stats
+                .peers
+                .get(&peer.public)
+                .is_some_and(|p| p.last_handshake.is_some())
+            {
+                return;
+            }
+            time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("handshake did not complete");
+}
+
+async fn stop(pair: Pair) {
+    pair.a.device.stop().await.unwrap();
+    pair.b.device.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_handshake() {
+    let pair = pair(None).await;
+
+    // The first packet is queued until the handshake completes, then sent
+    transfer(&pair.a, &pair.b, A_TUNNEL_IP, B_TUNNEL_IP, b"first").await;
+
+    wait_for_handshake(&pair.a, &pair.b.keypair).await;
+    wait_for_handshake(&pair.b, &pair.a.keypair).await;
+
+    let stats = pair.a.device.stats().await;
+    assert!(stats.peers[&pair.b.keypair.public].handshake_attempts >= 1);
+    stop(pair).await;
+}
+
+#[tokio::test]
+async fn test_data_transfer() {
+    let pair = pair(None).await;
+
+    for i in 0..10u8 {
+        transfer(&pair.a, &pair.b, A_TUNNEL_IP, B_TUNNEL_IP, &[i; 100]).await;
+        transfer(&pair.b, &pair.a, B_TUNNEL_IP, A_TUNNEL_IP, &[i; 1000]).await;
+    }
+
+    let stats = pair.b.device.stats().await;
+    assert_eq!(stats.rx_packets, 10);
+    assert_eq!(stats.source_filter_drops, 0);
+    stop(pair).await;
+}
+
+#[tokio::test]
+async fn test_batched_transfer() {
+    let pair = pair(None).await;
+    transfer(&pair.a, &pair.b, A_TUNNEL_IP, B_TUNNEL_IP, b"hello").await;
+    wait_for_handshake(&pair.a, &pair.b.keypair).await;
+
+    // Packets queued together are encrypted and sent as one batch
+    let before = pair.a.device.stats().await.udp_batches;
+    let packets: Vec<_> = (0..8u8)
+        .map(|i| ipv4_packet(A_TUNNEL_IP, B_TUNNEL_IP, &[i; 100]))
+        .collect();
+    for packet in &packets {
+        pair.a.tun.send(packet);
+    }
+    for packet in &packets {
+        let delivered = time::timeout(WAIT, pair.b.tun.recv())
+            .await
+            .expect("packet was not delivered")
+            .unwrap();
+        assert_eq!(&delivered, packet);
+    }
+
+    let after = pair.a.device.stats().await.udp_batches;
+    assert_eq!(after.send_datagrams - before.send_datagrams, 8);
+    assert!(after.send_batches - before.send_batches < 8);
+    assert!(pair.b.device.stats().await.udp_batches.recv_datagrams >= 9);
+    stop(pair).await;
+}
+
+#[tokio::test]
+async fn test_multiqueue_device() {
+    let a_addr: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+    let b_addr: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+    let (a_socket, b_socket) = MemorySocket::pair(a_addr, b_addr);
+    let (a_keys, b_keys) = (KeyPair::generate(), KeyPair::generate());
+    let a = node("wg-a", a_keys.clone(), peer("b", &b_keys, b_addr, B_TUNNEL_IP), a_socket).await;
+
+    // B reads and writes through four TUN queues
+    let (queues, handles): (Vec<_>, Vec<_>) = (0..4).map(|_| MemoryTun::pair()).unzip();
+    let queues = queues
+        .into_iter()
+        .map(|queue| Arc::new(queue) as Arc<dyn TunIo>)
+        .collect();
+    let config = DeviceConfig {
+        interface: "wg-b".to_string(),
+        mtu: 1420,
+        keypair: b_keys,
+        listen_port: 0,
+        bind_address: None,
+        queues: 4,
+        peers: vec![peer("a", &a_keys, a_addr, A_TUNNEL_IP)],
+    };
+    let b = WgDevice::with_queues(config, queues, Arc::new(b_socket))
+        .await
+        .unwrap();
+
+    // A flow is delivered on one queue, in order
+    let packets: Vec<_> = (0..10u8)
+        .map(|i| ipv4_packet(A_TUNNEL_IP, B_TUNNEL_IP, &[i; 100]))
+        .collect();
+    for packet in &packets {
+        a.tun.send(packet);
+    }
+    let delivered = time::timeout(WAIT, async {
+        let mut delivered = vec![Vec::new(); handles.len()];
+        while delivered.iter().map(Vec::len).sum::<usize>() < packets.len() {
+            for (handle, queue) in handles.iter().zip(&mut delivered) {
+                queue.extend(handle.try_recv());
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        delivered
+    })
+    .await
+    .expect("packets were not delivered");
+    assert!(delivered.contains(&packets));
+
+    // Packets read from every queue reach the peer
+    let mut sent: Vec<_> = (0..4u8)
+        .map(|i| ipv4_packet(B_TUNNEL_IP, A_TUNNEL_IP, &[i; 100]))
+        .collect();
+    for (handle, packet) in handles.iter().zip(&sent) {
+        handle.send(packet);
+    }
+    let mut received = Vec::new();
+    while received.len() < sent.len() {
+        let packet = time::timeout(WAIT, a.tun.recv())
+            .await
+            .expect("packet was not delivered")
+            .unwrap();
+        received.push(packet);
+    }
+    sent.sort();
+    received.sort();
+    assert_eq!(received, sent);
+
+    // Counters cover all queues
+    let stats = b.stats().await;
+    assert_eq!(stats.rx_packets, 10);
+    assert_eq!(stats.udp_batches.send_datagrams, 4);
+
+    a.device.stop().await.unwrap();
+    b.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_spoofed_source_dropped() {
+    let pair = pair(None).await;
+    transfer(&pair.a, &pair.b, A_TUNNEL_IP, B_TUNNEL_IP, b"hello").await;
+
+    // A's allowed IPs on B do not cover this source
+    let spoofed = ipv4_packet(Ipv4Addr::new(10, 0, 0, 99), B_TUNNEL_IP, b"spoofed");
+    pair.a.tun.send(&spoofed);
+
+    time::timeout(WAIT, async {
+        while pair.b.device.stats().await.source_filter_drops == 0 {
+            time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("spoofed packet was not filtered");
+    assert!(pair.b.tun.try_recv().is_none());
+    stop(pair).await;
+}
+
+#[tokio::test]
+async fn test_persistent_keepalive() {
+    let pair = pair(Some(Duration::from_secs(1))).await;
+    transfer(&pair.a, &pair.b, A_TUNNEL_IP, B_TUNNEL_IP, b"hello").await;
+
+    // With no traffic, A keeps sending keepalives to B
+    let before = pair.a.device.stats().await;
+    time::sleep(Duration::from_millis(2500)).await;
+    let after = pair.a.device.stats().await;
+
+    assert!(
+        after.tx_bytes > before.tx_bytes,
+        "no keepalives sent ({} -> {})",
+        before.tx_bytes,
+        after.tx_bytes
+    );
+    // Each keepalive counts as a packet as well as bytes
+    assert!(after.tx_packets >= before.tx_packets + 2);
+    assert!(pair.b.tun.try_recv().is_none());
+    stop(pair).await;
+}
+
+#[tokio::test]
+async fn test_rekey_after_key_rotation() {
+    let mut pair = pair(None).await;
+    transfer(&pair.a, &pair.b, A_TUNNEL_IP, B_TUNNEL_IP, b"old key").await;
+
+    // B learns A's new key before A switches to it
+    let new_keys = KeyPair::generate();
+    pair.b.device.remove_peer(&pair.a.keypair.public).await.unwrap();
+    pair.b
+        .device
+        .add_peer(peer("a", &new_keys, pair.a_addr, A_TUNNEL_IP))
+        .await
+        .unwrap();
+    time::timeout(WAIT, async {
+        while !pair.b.device.stats().await.peers.contains_key(&new_keys.public) {
+            time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("peer was not added");
+
+    // Rotation starts a handshake with the new key right away
+    pair.a.device.rotate_keys(new_keys.clone()).await.unwrap();
+    pair.a.keypair = new_keys;
+    wait_for_handshake(&pair.b, &pair.a.keypair).await;
+
+    transfer(&pair.a, &pair.b, A_TUNNEL_IP, B_TUNNEL_IP, b"new key").await;
+    transfer(&pair.b, &pair.a, B_TUNNEL_IP, A_TUNNEL_IP, b"reply").await;
+    stop(pair).await;
+}
+
+#[tokio::test]
+async fn test_removed_peer_traffic_dropped() {
+    let pair = pair(None).await;
+    transfer(&pair.a, &pair.b, A_TUNNEL_IP, B_TUNNEL_IP, b"hello").await;
+
+    pair.b.device.remove_peer(&pair.a.keypair.public).await.unwrap();
+    time::sleep(Duration::from_millis(100)).await;
+
+    pair.a.tun.send(&ipv4_packet(A_TUNNEL_IP, B_TUNNEL_IP, b"after removal"));
+    assert!(time::timeout(Duration::from_secs(1), pair.b.tun.recv())
+        .await
+        .is_err());
+    assert!(!pair
+        .b
+        .device
+        .stats()
+        .await
+        .peers
+        .contains_key(&pair.a.keypair.public));
+    stop(pair).await;
+}
+
+#[tokio::test]
+async fn test_handshake_flood_rate_limited() {
+    let a_addr: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+    let b_addr: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+    let attacker_addr: SocketAddr = "198.51.100.1:51820".parse().unwrap();
+    let network = MemoryNetwork::new();
+    let (a_keys, b_keys) = (KeyPair::generate(), KeyPair::generate());
+    let a_peer = peer("b", &b_keys, b_addr, B_TUNNEL_IP);
+    let b_peer = peer("a", &a_keys, a_addr, A_TUNNEL_IP);
+    let a = node("wg-a", a_keys, a_peer, network.bind(a_addr)).await;
+    let b = node("wg-b", b_keys.clone(), b_peer, network.bind(b_addr)).await;
+
+    // An unknown key with valid MAC1s, far past the limit within one second
+    let attacker = network.bind(attacker_addr);
+    let attacker_keys = KeyPair::generate();
+    let mut tunn = Tunn::new(
+        StaticSecret::from(*attacker_keys.private.as_bytes()),
+        PublicKey::from(*b_keys.public.as_bytes()),
+        None,
+        None,
+        0,
+        None,
+    )
+    .unwrap();
+    let mut buf = vec![0u8; 2048];
+    let initiation = match tunn.format_handshake_initiation(&mut buf, true) {
+        TunnResult::WriteToNetwork(data) => data.to_vec(),
+        _ => panic!("expected handshake initiation"),
+    };
+    for _ in 0..500 {
+        attacker.send_to(&initiation, b_addr).await.unwrap();
+    }
+
+    // B answers with cookie replies instead of doing the DH
+    let (len, src) = time::timeout(WAIT, attacker.recv_from(&mut buf))
+        .await
+        .expect("no cookie reply")
+        .unwrap();
+    assert_eq!(src, b_addr);
+    assert_eq!((len, buf[0]), (64, 3));
+    assert!(b.device.stats().await.handshakes_rate_limited > 0);
+
+    // The limiter resets and the real peer gets through
+    time::sleep(Duration::from_millis(1500)).await;
+    transfer(&a, &b, A_TUNNEL_IP, B_TUNNEL_IP, b"hello").await;
+
+    a.device.stop().await.unwrap();
+    b.device.stop().await.unwrap();
+}
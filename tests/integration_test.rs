@@ -2,7 +2,7 @@
 //!
 //! These tests verify the interaction between different modules.
 
-use harmony_agent::config::{Config, NetworkConfig, PeerConfig};
+use harmony_agent::config::{Config, NetworkConfig, PeerConfig, WireguardBackend};
 use harmony_agent::monitoring::{ConnectionState, Monitor};
 use harmony_agent::security::{validate_interface_name, validate_network_name};
 
@@ -15,7 +15,11 @@ fn test_config_integration() {
         enable_wireguard: true,
         interface: "wg0".to_string(),
         mtu: 1420,
-        address: Some("10.0.0.1/24".to_string()),
+        addresses: vec!["10.0.0.1/24".to_string()],
+        listen_port: 0,
+        bind_address: None,
+        backend: WireguardBackend::Auto,
+        queues: 0,
         private_key_path: "/tmp/test.key".to_string(),
         dns: vec![],
         peers: vec![],
@@ -71,6 +75,9 @@ fn test_peer_configuration() {
         endpoint: "192.168.1.1:51820".to_string(),
         allowed_ips: vec!["10.0.0.0/24".to_string()],
         persistent_keepalive_secs: 25,
+        endpoint_refresh_secs: 300,
+        preshared_key_path: None,
+        preshared_key: None,
     };
     
     assert_eq!(peer.name, "test-peer");
@@ -86,6 +93,9 @@ fn test_network_configuration_with_peers() {
         endpoint: "192.168.1.1:51820".to_string(),
         allowed_ips: vec!["10.0.1.0/24".to_string()],
         persistent_keepalive_secs: 25,
+        endpoint_refresh_secs: 300,
+        preshared_key_path: None,
+        preshared_key: None,
     };
     
     let peer2 = PeerConfig {
@@ -94,13 +104,20 @@ fn test_network_configuration_with_peers() {
         endpoint: "192.168.1.2:51820".to_string(),
         allowed_ips: vec!["10.0.2.0/24".to_string()],
         persistent_keepalive_secs: 25,
+        endpoint_refresh_secs: 300,
+        preshared_key_path: None,
+        preshared_key: None,
     };
     
     let network = NetworkConfig {
         enable_wireguard: true,
         interface: "wg1".to_string(),
         mtu: 1420,
-        address: Some("10.0.0.1/24".to_string()),
+        addresses: vec!["10.0.0.1/24".to_string()],
+        listen_port: 0,
+        bind_address: None,
+        backend: WireguardBackend::Auto,
+        queues: 0,
         private_key_path: "/tmp/test.key".to_string(),
         dns: vec![],
         peers: vec![peer1, peer2],
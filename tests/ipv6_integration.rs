@@ -0,0 +1,201 @@
+//! Integration tests for IPv6 and dual-stack configuration
+//!
+//! These tests use a fake `Platform` that records the operations it is asked
+//! to perform, so they need neither root privileges nor network namespaces.
+
+use harmony_agent::config::TomlConfig;
+use harmony_agent::error::{Result, WgAgentError};
+use harmony_agent::platform::{AddressFamily, Platform, PlatformInfo};
+use harmony_agent::wireguard::{KeyPair, Tunnel, TunnelConfig, TunnelState};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tempfile::TempDir;
+
+/// Platform that records address and route operations
+struct FakePlatform {
+    info: PlatformInfo,
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakePlatform {
+    fn new() -> (Self, Arc<Mutex<Vec<String>>>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let platform = Self {
+            info: PlatformInfo::new(),
+            calls: calls.clone(),
+        };
+        (platform, calls)
+    }
+
+    fn record(&self, op: &str, value: &str) -> Result<()> {
+        let family = match AddressFamily::of(value)? {
+            AddressFamily::V4 => "v4",
+            AddressFamily::V6 => "v6",
+        };
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("{} {} {}", op, family, value));
+        Ok(())
+    }
+}
+
+impl Platform for FakePlatform {
+    fn info(&self) -> &PlatformInfo {
+        &self.info
+    }
+
+    fn create_interface(&self, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn destroy_interface(&self, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_mtu(&self, _interface: &str, _mtu: u16) -> Result<()> {
+        Ok(())
+    }
+
+    fn interface_up(&self, _interface: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn interface_down(&self, _interface: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_address(&self, _interface: &str, address: &str) -> Result<()> {
+        self.record("addr", address)
+    }
+
+    fn configure_routes(&self, _interface: &str, routes: &[String]) -> Result<()> {
+        routes.iter().try_for_each(|route| self.record("route add", route))
+    }
+
+    fn remove_routes(&self, _interface: &str, routes: &[String]) -> Result<()> {
+        routes.iter().try_for_each(|route| self.record("route del", route))
+    }
+
+    fn configure_dns(&self, _interface: &str, _dns_servers: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    fn remove_dns(&self, _interface: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn check_capabilities(&self) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    fn create_tun_device(&self, name: &str, _mtu: u16) -> Result<tun::platform::Device> {
+        Err(WgAgentError::TunDevice(format!(
+            "Fake platform cannot create {}",
+            name
+        )))
+    }
+}
+
+/// Write a private key and return a dual-stack TOML network using it
+fn dual_stack_toml(dir: &TempDir) -> String {
+    let key_path = dir.path().join("private.key");
+    KeyPair::generate().private.save_to_file(&key_path).unwrap();
+
+    format!(
+        r#"
+        [network.dual]
+        interface = "wg-dual"
+        private_key_path = "{}"
+        addresses = ["10.100.0.2/24", "fd00:100::2/64"]
+
+        [[network.dual.peers]]
+        name = "gateway"
+        public_key = "{}"
+        endpoint = "[2001:db8::1]:51820"
+        allowed_ips = ["10.100.0.0/24", "fd00:100::/64"]
+        "#,
+        key_path.display(),
+        KeyPair::generate().public.to_base64()
+    )
+}
+
+#[test]
+fn test_dual_stack_network_config() {
+    let dir = TempDir::new().unwrap();
+    let config: harmony_agent::config::Config =
+        TomlConfig::parse(&dual_stack_toml(&dir)).unwrap().into();
+    config.validate().unwrap();
+
+    let network = config.get_network("dual").unwrap();
+    let tunnel_config = TunnelConfig::from_network_config(network).unwrap();
+    assert_eq!(
+        tunnel_config.addresses,
+        vec!["10.100.0.2/24", "fd00:100::2/64"]
+    );
+
+    let peer = &tunnel_config.peers[0];
+    assert_eq!(
+        peer.endpoint,
+        Some("[2001:db8::1]:51820".parse::<SocketAddr>().unwrap())
+    );
+    assert!(peer.endpoint_host.is_none());
+}
+
+#[test]
+fn test_unbracketed_ipv6_endpoint_rejected() {
+    let dir = TempDir::new().unwrap();
+    let toml = dual_stack_toml(&dir).replace("[2001:db8::1]:51820", "2001:db8::1:51820");
+    let config: harmony_agent::config::Config = TomlConfig::parse(&toml).unwrap().into();
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_platform_assigns_both_families() {
+    let (platform, calls) = FakePlatform::new();
+    let addresses = vec!["10.100.0.2/24".to_string(), "fd00:100::2/64".to_string()];
+    let routes = vec!["10.100.0.0/24".to_string(), "::/0".to_string()];
+
+    platform.set_addresses("wg-dual", &addresses).unwrap();
+    platform.configure_routes("wg-dual", &routes).unwrap();
+    platform.remove_routes("wg-dual", &routes).unwrap();
+
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec![
+            "addr v4 10.100.0.2/24",
+            "addr v6 fd00:100::2/64",
+            "route add v4 10.100.0.0/24",
+            "route add v6 ::/0",
+            "route del v4 10.100.0.0/24",
+            "route del v6 ::/0",
+        ]
+    );
+}
+
+#[test]
+fn test_set_addresses_stops_at_invalid_address() {
+    let (platform, calls) = FakePlatform::new();
+    let addresses = vec!["fd00::2/64".to_string(), "bogus/24".to_string()];
+
+    assert!(platform.set_addresses("wg-dual", &addresses).is_err());
+    assert_eq!(*calls.lock().unwrap(), vec!["addr v6 fd00::2/64"]);
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tokio::test]
+async fn test_tunnel_uses_injected_platform() {
+    let (platform, calls) = FakePlatform::new();
+    let dir = TempDir::new().unwrap();
+    let config: harmony_agent::config::Config =
+        TomlConfig::parse(&dual_stack_toml(&dir)).unwrap().into();
+    let tunnel_config = TunnelConfig::from_network_config(config.get_network("dual").unwrap())
+        .unwrap();
+
+    let tunnel = Tunnel::with_platform(tunnel_config, Box::new(platform)).unwrap();
+
+    // The fake cannot create a TUN device, so no addresses or routes are applied
+    assert!(tunnel.start().await.is_err());
+    assert_eq!(tunnel.state().await, TunnelState::Error);
+    assert!(calls.lock().unwrap().is_empty());
+}
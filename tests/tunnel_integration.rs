@@ -9,7 +9,7 @@
 //! Note: These tests require root privileges to create TUN devices.
 //! Run with: sudo -E cargo test --test tunnel_integration
 
-use harmony_agent::config::NetworkConfig;
+use harmony_agent::config::{NetworkConfig, WireguardBackend};
 use harmony_agent::wireguard::{KeyPair, Tunnel, TunnelConfig, TunnelState};
 use std::time::Duration;
 
@@ -24,7 +24,11 @@ fn create_test_tunnel_config() -> TunnelConfig {
             "wg-test".to_string()
         },
         mtu: 1420,
-        address: Some("10.0.0.1/24".to_string()),
+        addresses: vec!["10.0.0.1/24".to_string()],
+        listen_port: 0,
+        bind_address: None,
+        backend: WireguardBackend::Auto,
+        queues: 0,
         dns_servers: vec![],
         keypair,
         peers: vec![],
@@ -40,6 +44,8 @@ fn create_tunnel_config_with_peer() -> TunnelConfig {
         name: "test-peer".to_string(),
         public_key: peer_keypair.public,
         endpoint: Some("127.0.0.1:51820".parse().unwrap()),
+        endpoint_host: None,
+        endpoint_refresh: None,
         allowed_ips: vec!["10.0.0.0/24".to_string()],
         keepalive_interval: Some(Duration::from_secs(25)),
         preshared_key: None,
@@ -51,7 +57,11 @@ fn create_tunnel_config_with_peer() -> TunnelConfig {
             "wg-test".to_string()
         },
         mtu: 1420,
-        address: Some("10.0.0.1/24".to_string()),
+        addresses: vec!["10.0.0.1/24".to_string()],
+        listen_port: 0,
+        bind_address: None,
+        backend: WireguardBackend::Auto,
+        queues: 0,
         dns_servers: vec!["10.0.0.2".to_string()],
         keypair: local_keypair,
         peers: vec![peer_config],
@@ -78,7 +88,11 @@ async fn test_tunnel_config_validation() {
     let config = TunnelConfig {
         interface: "wg0".to_string(),
         mtu: 2000, // Invalid
-        address: Some("10.0.0.1/24".to_string()),
+        addresses: vec!["10.0.0.1/24".to_string()],
+        listen_port: 0,
+        bind_address: None,
+        backend: WireguardBackend::Auto,
+        queues: 0,
         dns_servers: vec![],
         keypair: keypair.clone(),
         peers: vec![],
@@ -90,7 +104,11 @@ async fn test_tunnel_config_validation() {
     let config = TunnelConfig {
         interface: "".to_string(),
         mtu: 1420,
-        address: Some("10.0.0.1/24".to_string()),
+        addresses: vec!["10.0.0.1/24".to_string()],
+        listen_port: 0,
+        bind_address: None,
+        backend: WireguardBackend::Auto,
+        queues: 0,
         dns_servers: vec![],
         keypair: keypair.clone(),
         peers: vec![],
@@ -102,7 +120,11 @@ async fn test_tunnel_config_validation() {
     let config = TunnelConfig {
         interface: "wg0".to_string(),
         mtu: 1420,
-        address: Some("10.0.0.1/24".to_string()),
+        addresses: vec!["10.0.0.1/24".to_string()],
+        listen_port: 0,
+        bind_address: None,
+        backend: WireguardBackend::Auto,
+        queues: 0,
         dns_servers: vec![],
         keypair,
         peers: vec![],
@@ -207,6 +229,9 @@ async fn test_tunnel_from_network_config() {
         endpoint: "127.0.0.1:51820".to_string(),
         allowed_ips: vec!["10.0.0.0/24".to_string()],
         persistent_keepalive_secs: 25,
+        endpoint_refresh_secs: 300,
+        preshared_key_path: None,
+        preshared_key: None,
     };
     
     let network_config = NetworkConfig {
@@ -217,7 +242,11 @@ async fn test_tunnel_from_network_config() {
             "wg-test".to_string()
         },
         mtu: 1420,
-        address: Some("10.0.0.1/24".to_string()),
+        addresses: vec!["10.0.0.1/24".to_string()],
+        listen_port: 0,
+        bind_address: None,
+        backend: WireguardBackend::Auto,
+        queues: 0,
         private_key_path: key_path.to_string_lossy().to_string(),
         dns: vec!["10.0.0.2".to_string()],
         peers: vec![peer_config],
@@ -275,6 +304,8 @@ async fn test_peer_config_validation() {
         name: "test".to_string(),
         public_key: keypair.public.clone(),
         endpoint: Some("127.0.0.1:51820".parse().unwrap()),
+        endpoint_host: None,
+        endpoint_refresh: None,
         allowed_ips: vec!["10.0.0.0/24".to_string()],
         keepalive_interval: Some(Duration::from_secs(25)),
         preshared_key: None,
@@ -286,6 +317,8 @@ async fn test_peer_config_validation() {
         name: "test".to_string(),
         public_key: keypair.public.clone(),
         endpoint: Some("127.0.0.1:51820".parse().unwrap()),
+        endpoint_host: None,
+        endpoint_refresh: None,
         allowed_ips: vec!["fd42::/48".to_string()],
         keepalive_interval: None,
         preshared_key: None,
@@ -297,6 +330,8 @@ async fn test_peer_config_validation() {
         name: "test".to_string(),
         public_key: keypair.public.clone(),
         endpoint: Some("127.0.0.1:51820".parse().unwrap()),
+        endpoint_host: None,
+        endpoint_refresh: None,
         allowed_ips: vec!["10.0.0.0".to_string()],
         keepalive_interval: None,
         preshared_key: None,
@@ -308,6 +343,8 @@ async fn test_peer_config_validation() {
         name: "test".to_string(),
         public_key: keypair.public,
         endpoint: Some("127.0.0.1:51820".parse().unwrap()),
+        endpoint_host: None,
+        endpoint_refresh: None,
         allowed_ips: vec!["10.0.0.0/33".to_string()],
         keepalive_interval: None,
         preshared_key: None,
@@ -0,0 +1,320 @@
+//! Reload tests for a running tunnel
+//!
+//! The tunnel's userspace device runs over an in-memory TUN device and
+//! datagram link to a second `WgDevice`, and a fake `Platform` accepts the
+//! address, route and DNS changes, so no root privileges are needed.
+
+use harmony_agent::config::WireguardBackend;
+use harmony_agent::error::{Result, WgAgentError};
+use harmony_agent::platform::{Platform, PlatformInfo};
+use harmony_agent::wireguard::{
+    DeviceConfig, DeviceIo, KeyPair, MemoryNetwork, MemorySocket, MemoryTun, MemoryTunHandle,
+    PeerConfig, TunIo, Tunnel, TunnelConfig, TunnelState, UdpIo, WgDevice,
+};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time;
+
+const LOCAL_TUNNEL_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+const REMOTE_TUNNEL_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+
+/// How long to wait for something that should happen
+const WAIT: Duration = Duration::from_secs(5);
+
+/// Platform that accepts every configuration change
+struct FakePlatform {
+    info: PlatformInfo,
+}
+
+impl Platform for FakePlatform {
+    fn info(&self) -> &PlatformInfo {
+        &self.info
+    }
+
+    fn create_interface(&self, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn destroy_interface(&self, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_mtu(&self, _interface: &str, _mtu: u16) -> Result<()> {
+        Ok(())
+    }
+
+    fn interface_up(&self, _interface: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn interface_down(&self, _interface: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_address(&self, _interface: &str, _address: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn configure_routes(&self, _interface: &str, _routes: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    fn remove_routes(&self, _interface: &str, _routes: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    fn configure_dns(&self, _interface: &str, _dns_servers: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    fn remove_dns(&self, _interface: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn check_capabilities(&self) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    fn create_tun_device(&self, name: &str, _mtu: u16) -> Result<tun::platform::Device> {
+        Err(WgAgentError::TunDevice(format!(
+            "Fake platform cannot create {}",
+            name
+        )))
+    }
+}
+
+/// Hands the tunnel's device one in-memory TUN device and socket
+struct MemoryIo(Mutex<Option<(MemoryTun, MemorySocket)>>);
+
+impl DeviceIo for MemoryIo {
+    fn open(&self, _config: &DeviceConfig) -> Result<(Arc<dyn TunIo>, Arc<dyn UdpIo>)> {
+        let (tun, socket) = self
+            .0
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| WgAgentError::TunDevice("I/O already in use".to_string()))?;
+        Ok((Arc::new(tun), Arc::new(socket)))
+    }
+}
+
+/// Gives every device a fresh in-memory TUN device and socket, so the tunnel
+/// can be restarted
+struct FreshIo;
+
+impl DeviceIo for FreshIo {
+    fn open(&self, _config: &DeviceConfig) -> Result<(Arc<dyn TunIo>, Arc<dyn UdpIo>)> {
+        let (tun, _) = MemoryTun::pair();
+        let socket = MemoryNetwork::new().bind("192.0.2.1:51820".parse().unwrap());
+        Ok((Arc::new(tun), Arc::new(socket)))
+    }
+}
+
+/// Like `FreshIo`, but slow to open, as creating a real TUN device can be
+struct SlowIo;
+
+impl DeviceIo for SlowIo {
+    fn open(&self, config: &DeviceConfig) -> Result<(Arc<dyn TunIo>, Arc<dyn UdpIo>)> {
+        std::thread::sleep(Duration::from_millis(20));
+        FreshIo.open(config)
+    }
+}
+
+fn fake_platform() -> Box<dyn Platform> {
+    Box::new(FakePlatform {
+        info: PlatformInfo::new(),
+    })
+}
+
+fn peer(name: &str, keypair: &KeyPair, endpoint: SocketAddr, tunnel_ip: Ipv4Addr) -> PeerConfig {
+    let mut config = PeerConfig::new(name.to_string(), keypair.public.clone());
+    config.endpoint = Some(endpoint);
+    config.allowed_ips = vec![format!("{}/32", tunnel_ip)];
+    config
+}
+
+/// Build a minimal IPv4/UDP packet carrying `payload`
+fn ipv4_packet(src: Ipv4Addr, dst: Ipv4Addr, payload: &[u8]) -> Vec<u8> {
+    let total_len = (20 + 8 + payload.len()) as u16;
+    let mut packet = vec![0x45, 0];
+    packet.extend_from_slice(&total_len.to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0, 64, 17, 0, 0]);
+    packet.extend_from_slice(&src.octets());
+    packet.extend_from_slice(&dst.octets());
+    packet.extend_from_slice(&[0x30, 0x39, 0x30, 0x39]);
+    packet.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Send a packet from one host and wait for the other side to deliver it
+async fn transfer(from: &MemoryTunHandle, to: &MemoryTunHandle, src: Ipv4Addr, dst: Ipv4Addr) {
+    let packet = ipv4_packet(src, dst, b"hello");
+    from.send(&packet);
+
+    let delivered = time::timeout(WAIT, to.recv())
+        .await
+        .expect("packet was not delivered")
+        .unwrap();
+    assert_eq!(delivered, packet);
+}
+
+#[tokio::test]
+async fn test_reload_keeps_peer_sessions() {
+    let local_addr: SocketAddr = "192.0.2.1:51820".parse().unwrap();
+    let remote_addr: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+    let (local_socket, remote_socket) = MemorySocket::pair(local_addr, remote_addr);
+    let (local_keys, remote_keys) = (KeyPair::generate(), KeyPair::generate());
+
+    let (remote_tun, remote_handle) = MemoryTun::pair();
+    let remote = WgDevice::with_io(
+        DeviceConfig {
+            interface: "wg-remote".to_string(),
+            mtu: 1420,
+            keypair: remote_keys.clone(),
+            listen_port: 0,
+            bind_address: None,
+            queues: 1,
+            peers: vec![peer("local", &local_keys, local_addr, LOCAL_TUNNEL_IP)],
+        },
+        Arc::new(remote_tun),
+        Arc::new(remote_socket),
+    )
+    .await
+    .unwrap();
+
+    let other_addr: SocketAddr = "192.0.2.3:51820".parse().unwrap();
+    let other = peer("other", &KeyPair::generate(), other_addr, Ipv4Addr::new(10, 0, 0, 3));
+    let config = TunnelConfig {
+        interface: "wg-reload".to_string(),
+        mtu: 1420,
+        dns_servers: vec![],
+        addresses: vec!["10.0.0.1/24".to_string()],
+        listen_port: 0,
+        bind_address: None,
+        backend: WireguardBackend::Userspace,
+        queues: 1,
+        keypair: local_keys,
+        peers: vec![
+            peer("remote", &remote_keys, remote_addr, REMOTE_TUNNEL_IP),
+            other.clone(),
+        ],
+    };
+
+    let (local_tun, local_handle) = MemoryTun::pair();
+    let io = Arc::new(MemoryIo(Mutex::new(Some((local_tun, local_socket)))));
+    let tunnel = Tunnel::with_device_io(config.clone(), fake_platform(), io).unwrap();
+    tunnel.start().await.unwrap();
+
+    transfer(&local_handle, &remote_handle, LOCAL_TUNNEL_IP, REMOTE_TUNNEL_IP).await;
+
+    // Replace the other peer, add one and move DNS; "remote" is untouched
+    let mut new_config = config.clone();
+    new_config.peers[1].allowed_ips = vec!["10.0.1.0/24".to_string()];
+    new_config.peers.push(peer(
+        "third",
+        &KeyPair::generate(),
+        "192.0.2.4:51820".parse().unwrap(),
+        Ipv4Addr::new(10, 0, 0, 4),
+    ));
+    new_config.dns_servers = vec!["10.0.0.53".to_string()];
+    tunnel.reload(new_config.clone()).await.unwrap();
+
+    // The remote keeps using its session; had the tunnel re-added the peer,
+    // it would not know the session and drop the packet
+    transfer(&remote_handle, &local_handle, REMOTE_TUNNEL_IP, LOCAL_TUNNEL_IP).await;
+
+    let names: Vec<String> = tunnel.peer_info().await.into_iter().map(|p| p.name).collect();
+    assert_eq!(names, vec!["remote", "other", "third"]);
+
+    // Moving the endpoint and renaming the peer also keeps the session
+    new_config.peers[0].name = "gateway".to_string();
+    new_config.peers[0].endpoint = Some("192.0.2.9:51820".parse().unwrap());
+    tunnel.reload(new_config).await.unwrap();
+    transfer(&remote_handle, &local_handle, REMOTE_TUNNEL_IP, LOCAL_TUNNEL_IP).await;
+
+    let info = tunnel.peer_info().await;
+    assert_eq!(info[0].name, "gateway");
+    assert!(info[0].active);
+
+    tunnel.stop().await.unwrap();
+    remote.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_reload_to_no_peers_keeps_tunnel_restartable() {
+    let remote_addr: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+    let config = TunnelConfig {
+        interface: "wg-empty".to_string(),
+        mtu: 1420,
+        dns_servers: vec![],
+        addresses: vec!["10.0.0.1/24".to_string()],
+        listen_port: 0,
+        bind_address: None,
+        backend: WireguardBackend::Userspace,
+        queues: 1,
+        keypair: KeyPair::generate(),
+        peers: vec![peer("remote", &KeyPair::generate(), remote_addr, REMOTE_TUNNEL_IP)],
+    };
+
+    let tunnel = Tunnel::with_device_io(config.clone(), fake_platform(), Arc::new(FreshIo)).unwrap();
+    tunnel.start().await.unwrap();
+
+    // Removing the last peer leaves a running tunnel that peers can be added to
+    let mut empty = config.clone();
+    empty.peers.clear();
+    tunnel.reload(empty).await.unwrap();
+    assert!(tunnel.peer_info().await.is_empty());
+
+    tunnel.restart().await.unwrap();
+    assert_eq!(tunnel.state().await, TunnelState::Active);
+
+    tunnel.add_peer(config.peers[0].clone()).await.unwrap();
+    assert!(tunnel.peer_info().await[0].active);
+
+    tunnel.stop().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_peer_added_during_restart_reaches_new_device() {
+    let config = TunnelConfig {
+        interface: "wg-race".to_string(),
+        mtu: 1420,
+        dns_servers: vec![],
+        addresses: vec!["10.0.0.1/24".to_string()],
+        listen_port: 0,
+        bind_address: None,
+        backend: WireguardBackend::Userspace,
+        queues: 1,
+        keypair: KeyPair::generate(),
+        peers: vec![],
+    };
+    let tunnel = Arc::new(
+        Tunnel::with_device_io(config, fake_platform(), Arc::new(SlowIo)).unwrap(),
+    );
+    tunnel.start().await.unwrap();
+
+    // Add a peer while the restart is creating the new device
+    let restart = {
+        let tunnel = Arc::clone(&tunnel);
+        tokio::spawn(async move { tunnel.restart().await })
+    };
+    time::timeout(WAIT, async {
+        while tunnel.state().await != TunnelState::Starting {
+            time::sleep(Duration::from_millis(1)).await;
+        }
+    })
+    .await
+    .expect("tunnel did not restart");
+    let remote_addr: SocketAddr = "192.0.2.2:51820".parse().unwrap();
+    let remote = peer("remote", &KeyPair::generate(), remote_addr, REMOTE_TUNNEL_IP);
+    tunnel.add_peer(remote.clone()).await.unwrap();
+    restart.await.unwrap().unwrap();
+
+    // The peer waited for the restart and is on the device now running
+    assert!(tunnel.peer_info().await[0].active);
+    tunnel.remove_peer(&remote.public_key).await.unwrap();
+    tunnel.stop().await.unwrap();
+}